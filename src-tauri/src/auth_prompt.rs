@@ -0,0 +1,76 @@
+use crate::ssh::ChallengeResponder;
+use crate::types::*;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+pub const AUTH_PROMPT_EVENT: &str = "ssh://auth-prompt";
+
+/// How long a prompt waits for the user before the login attempt is abandoned
+const PROMPT_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// Keyboard-interactive prompts waiting for an answer from the frontend
+#[derive(Default)]
+pub struct AuthPromptState {
+    pending: Mutex<HashMap<String, mpsc::Sender<Option<Vec<String>>>>>,
+    next_id: AtomicU64,
+}
+
+/// Relays challenges to the frontend as `ssh://auth-prompt` events and blocks
+/// until `answer_auth_prompt` is called for the same request id.
+pub struct EventChallengeResponder {
+    app: AppHandle,
+    host: String,
+}
+
+impl EventChallengeResponder {
+    pub fn new(app: AppHandle, host: String) -> Self {
+        EventChallengeResponder { app, host }
+    }
+}
+
+impl ChallengeResponder for EventChallengeResponder {
+    fn respond(&self, challenge: &AuthChallenge) -> Option<Vec<String>> {
+        let state = self.app.state::<AuthPromptState>();
+        let request_id = format!("auth-{}", state.next_id.fetch_add(1, Ordering::Relaxed));
+
+        let (tx, rx) = mpsc::channel();
+        state.pending.lock().unwrap().insert(request_id.clone(), tx);
+
+        let request = AuthPromptRequest {
+            request_id: request_id.clone(),
+            host: self.host.clone(),
+            challenge: challenge.clone(),
+        };
+        if let Err(e) = self.app.emit(AUTH_PROMPT_EVENT, request) {
+            log::warn!("Failed to emit auth prompt: {}", e);
+            state.pending.lock().unwrap().remove(&request_id);
+            return None;
+        }
+
+        let answer = rx.recv_timeout(PROMPT_TIMEOUT).ok().flatten();
+        state.pending.lock().unwrap().remove(&request_id);
+        answer
+    }
+}
+
+/// Answer a pending keyboard-interactive prompt; `None` cancels the login
+#[tauri::command]
+pub async fn answer_auth_prompt(
+    request_id: String,
+    responses: Option<Vec<String>>,
+    state: State<'_, AuthPromptState>,
+) -> Result<(), String> {
+    let sender = state
+        .pending
+        .lock()
+        .unwrap()
+        .remove(&request_id)
+        .ok_or_else(|| format!("No pending prompt '{}'", request_id))?;
+    sender
+        .send(responses)
+        .map_err(|_| "Login attempt is no longer waiting for an answer".to_string())
+}
@@ -1,18 +1,33 @@
+use crate::auth_prompt::EventChallengeResponder;
 use crate::compose_discovery::{ComposeDiscoveryCache, scan_compose_files, refresh_compose_scan};
-use crate::ssh::SshClient;
+use crate::connection_monitor;
+use crate::known_hosts;
+use crate::metrics;
+use crate::metrics_poller;
+use crate::metrics_store::MetricsStore;
+use crate::privilege;
+use crate::remote_file::{self, WriteOptions};
+use crate::shell::{self, Cmd};
+use crate::ssh::{CancelToken, CommandCategory, SshClient};
+use crate::terminal::TerminalState;
 use crate::types::*;
+use crate::vault::VaultState;
 use serde_json::Value as JsonValue;
 use std::collections::HashMap;
 use std::sync::Arc;
-use tauri::State;
+use std::time::Duration;
+use tauri::{Manager, State};
 use tauri_plugin_store::StoreExt;
 use tokio::sync::Mutex;
 
-const STORE_FILENAME: &str = "server_profiles.json";
-const PROFILES_KEY: &str = "server_profiles";
+pub(crate) const STORE_FILENAME: &str = "server_profiles.json";
+pub(crate) const PROFILES_KEY: &str = "server_profiles";
 const MAX_HISTORY_POINTS: usize = 10; // Optimized: reduced for better performance
+/// A CPU baseline older than this many intervals is dropped, so a reading after a long
+/// pause shows current usage rather than the average over the pause
+const CPU_BASELINE_MAX_INTERVALS: u32 = 3;
 
-fn profiles_from_json(value: Option<JsonValue>) -> HashMap<String, SavedServerProfile> {
+pub(crate) fn profiles_from_json(value: Option<JsonValue>) -> HashMap<String, SavedServerProfile> {
     match value {
         Some(JsonValue::Object(obj)) => {
             obj.into_iter()
@@ -27,7 +42,7 @@ fn profiles_from_json(value: Option<JsonValue>) -> HashMap<String, SavedServerPr
     }
 }
 
-fn profiles_to_json(profiles: &HashMap<String, SavedServerProfile>) -> JsonValue {
+pub(crate) fn profiles_to_json(profiles: &HashMap<String, SavedServerProfile>) -> JsonValue {
     let obj: serde_json::Map<String, JsonValue> = profiles
         .iter()
         .filter_map(|(k, v)| {
@@ -38,77 +53,375 @@ fn profiles_to_json(profiles: &HashMap<String, SavedServerProfile>) -> JsonValue
 }
 
 pub struct AppState {
-    pub ssh_client: Mutex<Option<Arc<SshClient>>>,
+    /// Live connections keyed by profile id
+    pub connections: Mutex<HashMap<String, Arc<ServerConnection>>>,
     pub server_profiles: Mutex<HashMap<String, ServerProfile>>,
-    pub cpu_history: Mutex<Vec<f64>>,
-    pub memory_history: Mutex<Vec<f64>>,
-    pub network_history: Mutex<Vec<NetworkHistoryPoint>>,
-    pub last_network_stats: Mutex<Option<NetworkStats>>,
     pub compose_cache: Arc<ComposeDiscoveryCache>,
+    pub running_commands: RunningCommands,
+}
+
+/// A connected server and what is remembered about it between calls
+pub struct ServerConnection {
+    pub client: Arc<SshClient>,
+    pub metrics: Mutex<MetricsHistory>,
+}
+
+impl ServerConnection {
+    pub fn new(client: Arc<SshClient>) -> Self {
+        ServerConnection {
+            client,
+            metrics: Mutex::new(MetricsHistory::default()),
+        }
+    }
+
+    /// Take a metrics reading and add it to the history. `interval` is how long after the
+    /// previous reading this one was expected.
+    pub async fn sample_metrics(&self, interval: Duration) -> Result<SystemMetrics, String> {
+        let client = Arc::clone(&self.client);
+
+        // Everything comes from one script; the first poll has nothing to diff CPU time
+        // against, so that one samples /proc/stat twice. So does one after a long pause.
+        let sample_cpu = {
+            let mut history = self.metrics.lock().await;
+            let max_age = interval * CPU_BASELINE_MAX_INTERVALS;
+            if history.last_cpu.as_ref().is_some_and(|(taken, _)| taken.elapsed() > max_age) {
+                history.last_cpu = None;
+            }
+            history.last_cpu.is_none()
+        };
+        let output = tokio::task::spawn_blocking(move || client.execute_command(&metrics::proc_script(sample_cpu)))
+            .await
+            .map_err(|e| format!("Metrics task failed: {}", e))?
+            .map_err(|e| e.message)?;
+        let snapshot = metrics::ProcSnapshot::parse(&output);
+
+        // Update history
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+
+        let mut history = self.metrics.lock().await;
+        let cpu_percent = snapshot.cpu_percent(history.last_cpu.as_ref().map(|(_, cpu)| cpu));
+        if let Some(cpu) = snapshot.cpu {
+            history.last_cpu = Some((std::time::Instant::now(), cpu));
+        }
+        history.record(cpu_percent, snapshot.memory_percent(), &snapshot.network, timestamp);
+
+        let metrics = SystemMetrics {
+            cpu_percent,
+            memory_used: snapshot.memory_used,
+            memory_total: snapshot.memory_total,
+            disk_usage: snapshot.disks,
+            load_avg: snapshot.load_avg,
+            uptime: snapshot.uptime,
+            process_count: snapshot.process_count,
+            network: snapshot.network,
+            cpu_history: history.cpu.clone(),
+            memory_history: history.memory.clone(),
+            network_history: history.network.clone(),
+        };
+        history.latest = Some(metrics.clone());
+        Ok(metrics)
+    }
+}
+
+/// The last `MAX_HISTORY_POINTS` metrics readings of one server
+#[derive(Default)]
+pub struct MetricsHistory {
+    pub cpu: Vec<f64>,
+    pub memory: Vec<f64>,
+    pub network: Vec<NetworkHistoryPoint>,
+    last_network: Option<NetworkStats>,
+    /// /proc/stat from the previous poll and when it was read; CPU usage is the change since then
+    pub last_cpu: Option<(std::time::Instant, metrics::CpuTimes)>,
+    /// The most recent reading, for windows that open between polls
+    pub latest: Option<SystemMetrics>,
+}
+
+fn push_bounded<T>(history: &mut Vec<T>, value: T) {
+    history.push(value);
+    if history.len() > MAX_HISTORY_POINTS {
+        history.remove(0);
+    }
+}
+
+impl MetricsHistory {
+    /// Add a reading; network traffic is recorded as the delta since the previous one
+    pub fn record(&mut self, cpu_percent: f64, memory_percent: f64, network: &NetworkStats, timestamp: u64) {
+        push_bounded(&mut self.cpu, cpu_percent);
+        push_bounded(&mut self.memory, memory_percent);
+        let point = match &self.last_network {
+            Some(last) => NetworkHistoryPoint {
+                timestamp,
+                bytes_sent: network.bytes_sent.saturating_sub(last.bytes_sent),
+                bytes_recv: network.bytes_recv.saturating_sub(last.bytes_recv),
+            },
+            None => NetworkHistoryPoint {
+                timestamp,
+                bytes_sent: 0,
+                bytes_recv: 0,
+            },
+        };
+        push_bounded(&mut self.network, point);
+        self.last_network = Some(network.clone());
+    }
+}
+
+/// Cancel tokens for in-flight commands the frontend gave an id to
+#[derive(Default)]
+pub struct RunningCommands {
+    tokens: std::sync::Mutex<HashMap<String, CancelToken>>,
+}
+
+impl RunningCommands {
+    /// Track a command under `command_id` until the returned guard is dropped
+    pub fn register(&self, command_id: Option<String>) -> RunningCommand<'_> {
+        let token = CancelToken::default();
+        if let Some(id) = &command_id {
+            self.tokens.lock().unwrap().insert(id.clone(), token.clone());
+        }
+        RunningCommand { registry: self, command_id, token }
+    }
+
+    pub fn cancel(&self, command_id: &str) -> bool {
+        match self.tokens.lock().unwrap().get(command_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+pub struct RunningCommand<'a> {
+    registry: &'a RunningCommands,
+    command_id: Option<String>,
+    pub token: CancelToken,
+}
+
+impl Drop for RunningCommand<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = &self.command_id {
+            self.registry.tokens.lock().unwrap().remove(id);
+        }
+    }
+}
+
+/// The connection to `server_id`, or to the only connected server when no id is given
+fn find_connection(
+    connections: &HashMap<String, Arc<ServerConnection>>,
+    server_id: Option<&str>,
+) -> Result<Arc<ServerConnection>, String> {
+    match server_id {
+        Some(id) => connections
+            .get(id)
+            .cloned()
+            .ok_or_else(|| format!("Not connected to server {}", id)),
+        None => match connections.len() {
+            0 => Err("Not connected".to_string()),
+            1 => Ok(connections.values().next().cloned().unwrap()),
+            _ => Err("Connected to several servers, a server_id is required".to_string()),
+        },
+    }
+}
+
+/// Remove `client`'s connection, unless a newer connection to the same server has
+/// replaced it meanwhile. Returns whether it was removed.
+fn remove_if_current(connections: &mut HashMap<String, Arc<ServerConnection>>, client: &Arc<SshClient>) -> bool {
+    let current = connections
+        .get(client.profile_id())
+        .is_some_and(|connection| Arc::ptr_eq(&connection.client, client));
+    if current {
+        connections.remove(client.profile_id());
+    }
+    current
+}
+
+impl AppState {
+    /// The connection to `server_id`, or to the only connected server when no id is given.
+    /// The lock is released straight away so commands run concurrently; each client's
+    /// session pool handles sharing its connection.
+    pub async fn connection(&self, server_id: Option<&str>) -> Result<Arc<ServerConnection>, String> {
+        find_connection(&*self.connections.lock().await, server_id)
+    }
+
+    /// Forget `client`'s connection if it is still the current one for its server
+    pub async fn remove_connection(&self, client: &Arc<SshClient>) -> bool {
+        remove_if_current(&mut *self.connections.lock().await, client)
+    }
+
+    pub async fn client(&self, server_id: Option<&str>) -> Result<Arc<SshClient>, String> {
+        self.connection(server_id).await.map(|c| Arc::clone(&c.client))
+    }
 }
 
 impl Default for AppState {
     fn default() -> Self {
         AppState {
-            ssh_client: Mutex::new(None),
+            connections: Mutex::new(HashMap::new()),
             server_profiles: Mutex::new(HashMap::new()),
-            cpu_history: Mutex::new(Vec::with_capacity(30)),
-            memory_history: Mutex::new(Vec::with_capacity(30)),
-            network_history: Mutex::new(Vec::with_capacity(30)),
-            last_network_stats: Mutex::new(None),
             compose_cache: Arc::new(ComposeDiscoveryCache::new()),
+            running_commands: RunningCommands::default(),
         }
     }
 }
 
 #[tauri::command]
-pub fn test_connection(
+pub async fn test_connection(
     host: String,
     port: u16,
     username: String,
     auth_method: AuthMethod,
+    jump_hosts: Option<Vec<JumpHost>>,
+    app: tauri::AppHandle,
 ) -> Result<ConnectionResult, String> {
-    let profile = ServerProfile {
+    let mut profile = ServerProfile {
         id: "test".to_string(),
         name: "test".to_string(),
         host,
         port,
         username,
         auth_method,
+        jump_hosts: jump_hosts.unwrap_or_default(),
+        escalation: Escalation::default(),
     };
+    app.state::<VaultState>().open(&app, &mut profile)?;
 
-    let client = SshClient::new(profile);
-    match client.connect() {
+    let responder = Arc::new(EventChallengeResponder::new(app, profile.host.clone()));
+    let client = Arc::new(SshClient::new(profile).with_challenge_responder(responder));
+
+    // Connecting may block on the user answering auth prompts, so keep it off the async workers
+    let connecting = Arc::clone(&client);
+    let result = tokio::task::spawn_blocking(move || connecting.connect())
+        .await
+        .map_err(|e| format!("Connection task failed: {}", e))?;
+
+    match result {
         Ok(_) => {
             client.disconnect();
             Ok(ConnectionResult {
                 success: true,
                 message: "Connection successful".to_string(),
+                host_key: client.host_key(),
+                failed_hop: None,
+                warning: None,
+                privileges: None,
             })
         }
         Err(e) => Ok(ConnectionResult {
             success: false,
             message: e.message,
+            host_key: client.host_key(),
+            failed_hop: client.failed_hop(),
+            warning: None,
+            privileges: None,
         }),
     }
 }
 
+/// Trust a server's host key after the user has confirmed its fingerprint.
+/// The key is fetched again (through `jump_hosts` when the host sits behind a bastion)
+/// and must still match the fingerprint that was shown.
+#[tauri::command]
+pub async fn trust_host_key(
+    host: String,
+    port: u16,
+    fingerprint: String,
+    jump_hosts: Option<Vec<JumpHost>>,
+    app: tauri::AppHandle,
+) -> Result<HostKeyInfo, String> {
+    // Only the jump hosts are logged in to; the target is never authenticated here
+    let mut profile = ServerProfile {
+        id: "trust".to_string(),
+        name: "trust".to_string(),
+        host: host.clone(),
+        port,
+        username: String::new(),
+        auth_method: AuthMethod::KeyboardInteractive,
+        jump_hosts: jump_hosts.unwrap_or_default(),
+        escalation: Escalation::default(),
+    };
+    app.state::<VaultState>().open(&app, &mut profile)?;
+    let responder = Arc::new(EventChallengeResponder::new(app, host.clone()));
+    let client = SshClient::new(profile).with_challenge_responder(responder);
+
+    tokio::task::spawn_blocking(move || {
+        let session = client.open_transport().map_err(|e| e.message)?;
+        let mut info = known_hosts::verify_host_key(&session, &host, port).map_err(|e| e.message)?;
+
+        if info.fingerprint != fingerprint {
+            return Err(format!(
+                "Host key for '{}:{}' no longer matches the confirmed fingerprint (server now presents {})",
+                host, port, info.fingerprint
+            ));
+        }
+
+        match info.status {
+            HostKeyStatus::Trusted => Ok(info),
+            HostKeyStatus::Changed => Err(format!(
+                "Host key for '{}:{}' conflicts with a recorded key. Remove the old entry before trusting a new one.",
+                host, port
+            )),
+            HostKeyStatus::Unknown => {
+                known_hosts::add_host_key(&session, &host, port).map_err(|e| e.message)?;
+                info.status = HostKeyStatus::Trusted;
+                Ok(info)
+            }
+        }
+    })
+    .await
+    .map_err(|e| format!("Host key task failed: {}", e))?
+}
+
+/// List the identities offered by the local ssh-agent, for picking one in an Agent profile
+#[tauri::command]
+pub fn list_agent_identities() -> Result<Vec<AgentIdentity>, String> {
+    SshClient::agent_identities().map_err(|e| e.message)
+}
+
+/// Remove a host's entries from DPanel's own known_hosts file
+#[tauri::command]
+pub fn forget_host_key(host: String, port: u16) -> Result<bool, String> {
+    known_hosts::remove_host_key(&host, port)
+}
+
 #[tauri::command]
 pub async fn connect_to_server(
-    profile: ServerProfile,
+    mut profile: ServerProfile,
     state: State<'_, AppState>,
+    vault: State<'_, VaultState>,
     app: tauri::AppHandle,
 ) -> Result<ConnectionResult, String> {
-    let client = Arc::new(SshClient::new(profile.clone()));
-    match client.connect() {
-        Ok(_) => {
-            // Save to in-memory state
-            let mut profiles = state.server_profiles.lock().await;
-            profiles.insert(profile.id.clone(), profile.clone());
+    // Saved profiles carry vault references; only the live client sees the secrets
+    let stored = profile.clone();
+    vault.open(&app, &mut profile)?;
+
+    let responder = Arc::new(EventChallengeResponder::new(app.clone(), profile.host.clone()));
+    let client = Arc::new(SshClient::new(profile.clone()).with_challenge_responder(responder));
 
-            // Save to persistent storage
-            let mut saved_profile: SavedServerProfile = SavedServerProfile::from(profile.clone());
-            if let Ok(store) = app.store(STORE_FILENAME) {
+    let connecting = Arc::clone(&client);
+    let result = tokio::task::spawn_blocking(move || connecting.connect())
+        .await
+        .map_err(|e| format!("Connection task failed: {}", e))?;
+
+    match result {
+        Ok(_) => {
+            // Save to in-memory state; the lock isn't held across the store or the remote probe
+            state.server_profiles.lock().await.insert(profile.id.clone(), stored.clone());
+
+            // Save to persistent storage, unless the credentials can't be put in the vault
+            let mut saved_profile: SavedServerProfile = SavedServerProfile::from(stored);
+            let sealed = vault.seal(&app, &mut saved_profile);
+            let warning = match &sealed {
+                Ok(()) => None,
+                Err(e) => {
+                    log::warn!("Not saving profile {}: {}", profile.id, e);
+                    Some(format!("Connected, but the profile was not saved: {}", e))
+                }
+            };
+            if let (Ok(()), Ok(store)) = (sealed, app.store(STORE_FILENAME)) {
                 let mut profiles_map = profiles_from_json(store.get(PROFILES_KEY));
                 
                 // Preserve existing metadata if profile already exists
@@ -116,6 +429,7 @@ pub async fn connect_to_server(
                     let mut updated = saved_profile;
                     updated.created_at = existing.created_at;
                     updated.connect_on_startup = existing.connect_on_startup;
+                    updated.labels = existing.labels.clone();
                     saved_profile = updated;
                 }
                 
@@ -133,237 +447,100 @@ pub async fn connect_to_server(
                 store.save().map_err(|e| format!("Failed to save profile: {}", e))?;
             }
 
-            let mut ssh_client = state.ssh_client.lock().await;
-            *ssh_client = Some(client);
+            let host_key = client.host_key();
+            // A failed check only means the UI can't warn up front, not a failed connection
+            let privileges = match client.run_blocking(privilege::privilege_status).await {
+                Ok(status) => Some(status),
+                Err(e) => {
+                    log::warn!("Could not check privileges on {}: {}", profile.id, e);
+                    None
+                }
+            };
+            connection_monitor::emit_state(&app, &client, ConnectionState::Connected, 0, None);
+            connection_monitor::spawn(&client, app.clone());
+
+            // Other servers stay connected; only an earlier connection to this one is replaced
+            let mut connections = state.connections.lock().await;
+            let connection = Arc::new(ServerConnection::new(client));
+            metrics_poller::spawn(&connection, app.clone());
+            if let Some(previous) = connections.insert(profile.id.clone(), connection) {
+                previous.client.disconnect();
+                app.state::<TerminalState>().close_server(previous.client.profile_id());
+            }
             Ok(ConnectionResult {
                 success: true,
                 message: "Connected successfully".to_string(),
+                host_key,
+                failed_hop: None,
+                warning,
+                privileges,
             })
         }
         Err(e) => Ok(ConnectionResult {
             success: false,
             message: e.message,
+            host_key: client.host_key(),
+            failed_hop: client.failed_hop(),
+            warning: None,
+            privileges: None,
         }),
     }
 }
 
 #[tauri::command]
-pub async fn disconnect_server(state: State<'_, AppState>) -> Result<(), String> {
-    let mut ssh_client = state.ssh_client.lock().await;
-    if let Some(client) = ssh_client.take() {
-        client.disconnect();
-    }
+pub async fn disconnect_server(
+    server_id: Option<String>,
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+) -> Result<(), String> {
+    // Look up and remove under one lock, so a connection made in between isn't dropped instead
+    let connection = {
+        let mut connections = state.connections.lock().await;
+        let Ok(connection) = find_connection(&connections, server_id.as_deref()) else {
+            return Ok(());
+        };
+        remove_if_current(&mut connections, &connection.client);
+        connection
+    };
+    let client = &connection.client;
+    client.disconnect();
+    app.state::<TerminalState>().close_server(client.profile_id());
+    connection_monitor::emit_state(&app, client, ConnectionState::Disconnected, 0, None);
     Ok(())
 }
 
+/// Profile ids of the servers that are currently connected
 #[tauri::command]
-pub async fn get_system_metrics(state: State<'_, AppState>) -> Result<SystemMetrics, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    // Execute independent commands in parallel using threads
-    let client_clone: Arc<SshClient> = Arc::clone(client);
-    let cpu_handle = std::thread::spawn(move || {
-        client_clone.execute_command("top -bn1 | grep 'Cpu(s)' | awk '{print $2}' | cut -d'%' -f1")
-    });
-    
-    let client_clone: Arc<SshClient> = Arc::clone(client);
-    let mem_handle = std::thread::spawn(move || {
-        client_clone.execute_command("free -b | grep Mem | awk '{print $3,$2}'")
-    });
-    
-    let client_clone: Arc<SshClient> = Arc::clone(client);
-    let disk_handle = std::thread::spawn(move || {
-        client_clone.execute_command("df -B1 | tail -n +2 | awk '{print $6,$3,$2,$5}' | grep -E '^/'")
-    });
-    
-    let client_clone: Arc<SshClient> = Arc::clone(client);
-    let load_handle = std::thread::spawn(move || {
-        client_clone.execute_command("cat /proc/loadavg | awk '{print $1,$2,$3}'")
-    });
-    
-    let client_clone: Arc<SshClient> = Arc::clone(client);
-    let uptime_handle = std::thread::spawn(move || {
-        client_clone.execute_command("cat /proc/uptime | awk '{print int($1)}'")
-    });
-    
-    let client_clone: Arc<SshClient> = Arc::clone(client);
-    let proc_handle = std::thread::spawn(move || {
-        client_clone.execute_command("ps aux | wc -l")
-    });
-
-    let cpu_output = cpu_handle.join().unwrap().map_err(|e| e.message)?;
-    let cpu_percent: f64 = cpu_output.trim().parse().unwrap_or(0.0);
-
-    let mem_output = mem_handle.join().unwrap().map_err(|e| e.message)?;
-    let mem_parts: Vec<&str> = mem_output.trim().split_whitespace().collect();
-    let memory_used: u64 = mem_parts.get(0).and_then(|s: &&str| s.parse().ok()).unwrap_or(0);
-    let memory_total: u64 = mem_parts.get(1).and_then(|s: &&str| s.parse().ok()).unwrap_or(0);
-
-    let disk_output = disk_handle.join().unwrap().map_err(|e| e.message)?;
-    let mut disk_usage = Vec::new();
-    for line in disk_output.lines() {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() >= 4 {
-            let mount_point = parts[0].to_string();
-            let used: u64 = parts[1].parse().unwrap_or(0);
-            let total: u64 = parts[2].parse().unwrap_or(0);
-            let percent_str = parts[3].trim_end_matches('%');
-            let percent: f64 = percent_str.parse().unwrap_or(0.0);
-            disk_usage.push(DiskUsage {
-                mount_point,
-                used,
-                total,
-                percent,
-            });
-        }
-    }
-
-    let load_output = load_handle.join().unwrap().map_err(|e| e.message)?;
-    let load_parts: Vec<f64> = load_output
-        .trim()
-        .split_whitespace()
-        .filter_map(|s: &str| s.parse().ok())
-        .collect();
-    let load_avg = [
-        load_parts.get(0).copied().unwrap_or(0.0),
-        load_parts.get(1).copied().unwrap_or(0.0),
-        load_parts.get(2).copied().unwrap_or(0.0),
-    ];
-
-    let uptime_output = uptime_handle.join().unwrap().map_err(|e| e.message)?;
-    let uptime: u64 = uptime_output.trim().parse().unwrap_or(0);
-
-    let proc_output = proc_handle.join().unwrap().map_err(|e| e.message)?;
-    let process_count: u32 = proc_output.trim().parse().unwrap_or(0);
-
-    // Fetch network stats
-    let client_clone: Arc<SshClient> = Arc::clone(client);
-    let network_handle = std::thread::spawn(move || {
-        client_clone.execute_command("cat /proc/net/dev | tail -n +3 | head -n 1 | awk '{print $2,$3,$10,$11}'")
-    });
-
-    let network_output = network_handle.join().unwrap().map_err(|e| e.message)?;
-    let net_parts: Vec<&str> = network_output.trim().split_whitespace().collect();
-    let bytes_recv: u64 = net_parts.get(0).and_then(|s| s.parse().ok()).unwrap_or(0);
-    let packets_recv: u64 = net_parts.get(1).and_then(|s| s.parse().ok()).unwrap_or(0);
-    let bytes_sent: u64 = net_parts.get(2).and_then(|s| s.parse().ok()).unwrap_or(0);
-    let packets_sent: u64 = net_parts.get(3).and_then(|s| s.parse().ok()).unwrap_or(0);
-
-    // Get primary interface name
-    let client_clone: Arc<SshClient> = Arc::clone(client);
-    let iface_handle = std::thread::spawn(move || {
-        client_clone.execute_command("ip route | grep default | awk '{print $5}' | head -n 1")
-    });
-    let iface_output = iface_handle.join().unwrap().unwrap_or_else(|_| "eth0".to_string());
-    let interface = iface_output.trim().to_string();
-
-    let network = NetworkStats {
-        bytes_sent,
-        bytes_recv,
-        packets_sent,
-        packets_recv,
-        interface,
-    };
-
-    // Update history
-    let timestamp = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_millis() as u64;
-
-    // CPU History
-    {
-        let mut cpu_hist = state.cpu_history.lock().await;
-        cpu_hist.push(cpu_percent);
-        if cpu_hist.len() > MAX_HISTORY_POINTS {
-            cpu_hist.remove(0);
-        }
-    }
-
-    // Memory History
-    {
-        let mem_percent = if memory_total > 0 {
-            (memory_used as f64 / memory_total as f64) * 100.0
-        } else {
-            0.0
-        };
-        let mut mem_hist = state.memory_history.lock().await;
-        mem_hist.push(mem_percent);
-        if mem_hist.len() > MAX_HISTORY_POINTS {
-            mem_hist.remove(0);
-        }
-    }
-
-    // Network History - calculate delta from last reading
-    let network_history_point = {
-        let mut last_net = state.last_network_stats.lock().await;
-        let point = if let Some(ref last) = *last_net {
-            NetworkHistoryPoint {
-                timestamp,
-                bytes_sent: bytes_sent.saturating_sub(last.bytes_sent),
-                bytes_recv: bytes_recv.saturating_sub(last.bytes_recv),
-            }
-        } else {
-            NetworkHistoryPoint {
-                timestamp,
-                bytes_sent: 0,
-                bytes_recv: 0,
-            }
-        };
-        *last_net = Some(network.clone());
-        point
-    };
-
-    {
-        let mut net_hist = state.network_history.lock().await;
-        net_hist.push(network_history_point);
-        if net_hist.len() > MAX_HISTORY_POINTS {
-            net_hist.remove(0);
-        }
-    }
+pub async fn get_connected_servers(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    let mut ids: Vec<String> = state.connections.lock().await.keys().cloned().collect();
+    ids.sort();
+    Ok(ids)
+}
 
-    // Get history snapshots
-    let cpu_history = state.cpu_history.lock().await.clone();
-    let memory_history = state.memory_history.lock().await.clone();
-    let network_history = state.network_history.lock().await.clone();
-
-    Ok(SystemMetrics {
-        cpu_percent,
-        memory_used,
-        memory_total,
-        disk_usage,
-        load_avg,
-        uptime,
-        process_count,
-        network,
-        cpu_history,
-        memory_history,
-        network_history,
-    })
+#[tauri::command]
+pub async fn get_system_metrics(server_id: Option<String>, state: State<'_, AppState>) -> Result<SystemMetrics, String> {
+    state
+        .connection(server_id.as_deref())
+        .await?
+        .sample_metrics(metrics_poller::DEFAULT_INTERVAL)
+        .await
 }
 
 #[tauri::command]
-pub async fn get_docker_containers(state: State<'_, AppState>) -> Result<Vec<DockerContainer>, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    // Execute both commands using threads
-    let client_clone: Arc<SshClient> = Arc::clone(client);
-    let ps_handle = std::thread::spawn(move || {
-        client_clone.execute_command(
-            "docker ps -a --format '{{.ID}}|{{.Names}}|{{.Image}}|{{.Status}}|{{.State}}' --no-trunc",
-        )
-    });
-    
-    let client_clone: Arc<SshClient> = Arc::clone(client);
-    let stats_handle = std::thread::spawn(move || {
-        client_clone.execute_command(
-            "docker stats --no-stream --format '{{.Name}}|{{.CPUPerc}}|{{.MemUsage}}'"
-        )
-    });
-
-    let ps_output = ps_handle.join().unwrap().map_err(|e| e.message)?;
+pub async fn get_docker_containers(server_id: Option<String>, state: State<'_, AppState>) -> Result<Vec<DockerContainer>, String> {
+    let client = state.client(server_id.as_deref()).await?;
+
+    // Run both commands at once, each on its own pooled session
+    let (ps_output, stats_output) = tokio::join!(
+        client.run_blocking(|client| {
+            client.execute_command(
+                "docker ps -a --format '{{.ID}}|{{.Names}}|{{.Image}}|{{.Status}}|{{.State}}' --no-trunc",
+            )
+        }),
+        client.run_blocking(|client| client.execute_command(DOCKER_STATS_COMMAND)),
+    );
+
+    let ps_output = ps_output.map_err(|e| e.message)?;
     let mut containers = Vec::new();
     for line in ps_output.lines() {
         let parts: Vec<&str> = line.split('|').collect();
@@ -382,24 +559,47 @@ pub async fn get_docker_containers(state: State<'_, AppState>) -> Result<Vec<Doc
         }
     }
 
-    let stats_output = stats_handle.join().unwrap().map_err(|e| e.message)?;
-    for line in stats_output.lines() {
-        let parts: Vec<&str> = line.split('|').collect();
-        if parts.len() >= 3 {
-            if let Some(container) = containers.iter_mut().find(|c| c.name == parts[0]) {
-                container.cpu_percent = parts[1].trim_end_matches('%').parse().unwrap_or(0.0);
-                let mem_parts: Vec<&str> = parts[2].split('/').collect();
-                if mem_parts.len() >= 2 {
-                    container.memory_usage = parse_memory(mem_parts[0]);
-                    container.memory_limit = parse_memory(mem_parts[1]);
-                }
-            }
+    let stats_output = stats_output.map_err(|e| e.message)?;
+    for stats in parse_docker_stats(&stats_output) {
+        if let Some(container) = containers.iter_mut().find(|c| c.name == stats.name) {
+            container.cpu_percent = stats.cpu_percent;
+            container.memory_usage = stats.memory_usage;
+            container.memory_limit = stats.memory_limit;
         }
     }
 
     Ok(containers)
 }
 
+pub(crate) const DOCKER_STATS_COMMAND: &str = "docker stats --no-stream --format '{{.Name}}|{{.CPUPerc}}|{{.MemUsage}}'";
+
+/// Resource usage of one running container, from `docker stats`
+pub(crate) struct ContainerStats {
+    pub name: String,
+    pub cpu_percent: f64,
+    pub memory_usage: u64,
+    pub memory_limit: u64,
+}
+
+pub(crate) fn parse_docker_stats(output: &str) -> Vec<ContainerStats> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split('|').collect();
+            if parts.len() < 3 {
+                return None;
+            }
+            let mem_parts: Vec<&str> = parts[2].split('/').collect();
+            Some(ContainerStats {
+                name: parts[0].to_string(),
+                cpu_percent: parts[1].trim_end_matches('%').parse().unwrap_or(0.0),
+                memory_usage: mem_parts.first().map(|m| parse_memory(m)).unwrap_or(0),
+                memory_limit: mem_parts.get(1).map(|m| parse_memory(m)).unwrap_or(0),
+            })
+        })
+        .collect()
+}
+
 fn parse_memory(mem_str: &str) -> u64 {
     let mem_str = mem_str.trim().to_uppercase();
     
@@ -426,149 +626,201 @@ fn parse_memory(mem_str: &str) -> u64 {
 
 #[tauri::command]
 pub async fn docker_container_action(
-    action: String,
+    action: ContainerAction,
     container_name: String,
+    server_id: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let command = format!("docker {} {}", action, container_name);
-    client.execute_command(&command).map_err(|e| e.message)
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let command = Cmd::new("docker").raw(action.as_arg()).arg(shell::docker_name(&container_name)?);
+        client
+            .execute_checked(&command, CommandCategory::Action)
+            .map_err(|e| e.message)
+    })
+    .await
 }
 
 #[tauri::command]
 pub async fn get_container_logs(
     container_name: String,
     lines: Option<u32>,
+    server_id: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let lines = lines.unwrap_or(100);
-    let command = format!("docker logs --tail {} {}", lines, container_name);
-    client.execute_command(&command).map_err(|e| e.message)
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let lines = lines.unwrap_or(100);
+        let command = Cmd::new("docker")
+            .raw("logs --tail")
+            .arg(lines.to_string())
+            .arg(shell::docker_name(&container_name)?);
+        client.execute_command(&command).map_err(|e| e.message)
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn get_services(state: State<'_, AppState>) -> Result<Vec<ServiceInfo>, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let output = client
-        .execute_command("systemctl list-units --type=service --all --no-pager --no-legend --plain")
-        .map_err(|e| e.message)?;
-
-    let mut services = Vec::new();
-    for line in output.lines() {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() >= 4 {
-            services.push(ServiceInfo {
-                name: parts[0].to_string(),
-                state: parts.get(1).map(|s: &&str| s.to_string()).unwrap_or_default(),
-                sub_state: parts.get(2).map(|s: &&str| s.to_string()).unwrap_or_default(),
-                description: parts[3..].join(" "),
-            });
+pub async fn get_services(server_id: Option<String>, state: State<'_, AppState>) -> Result<Vec<ServiceInfo>, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let output = client
+            .execute_command("systemctl list-units --type=service --all --no-pager --no-legend --plain")
+            .map_err(|e| e.message)?;
+
+        let mut services = Vec::new();
+        for line in output.lines() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() >= 4 {
+                services.push(ServiceInfo {
+                    name: parts[0].to_string(),
+                    state: parts.get(1).map(|s: &&str| s.to_string()).unwrap_or_default(),
+                    sub_state: parts.get(2).map(|s: &&str| s.to_string()).unwrap_or_default(),
+                    description: parts[3..].join(" "),
+                });
+            }
         }
-    }
 
-    Ok(services)
+        Ok(services)
+    })
+    .await
 }
 
 #[tauri::command]
 pub async fn service_action(
-    action: String,
+    action: ServiceAction,
     service_name: String,
+    server_id: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let command = format!("sudo systemctl {} {}", action, service_name);
-    client.execute_command(&command).map_err(|e| e.message)
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let command = Cmd::sudo("systemctl").raw(action.as_arg()).arg(shell::unit_name(&service_name)?);
+        client
+            .execute_checked(&command, CommandCategory::Action)
+            .map_err(|e| e.message)
+    })
+    .await
 }
 
 #[tauri::command]
 pub async fn get_service_logs(
     service_name: String,
     lines: Option<u32>,
+    server_id: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let lines = lines.unwrap_or(100);
-    
-    // Try journalctl first (for systemd services with journald)
-    let journalctl_cmd = format!("journalctl -u {} -n {} --no-pager 2>&1", service_name, lines);
-    let journalctl_result = client.execute_command(&journalctl_cmd);
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let lines = lines.unwrap_or(100).to_string();
+        shell::unit_name(&service_name)?;
+
+        // Try journalctl first (for systemd services with journald)
+        let journalctl_cmd = Cmd::new("journalctl")
+            .raw("-u")
+            .arg(&service_name)
+            .raw("-n")
+            .arg(&lines)
+            .raw("--no-pager 2>&1");
+        let journalctl_result = client.execute_command(&journalctl_cmd);
     
-    // If journalctl succeeds and returns content, use it
-    if let Ok(output) = journalctl_result {
-        if !output.is_empty() && !output.contains("No entries") && !output.contains("cannot open") {
-            return Ok(output);
+        // If journalctl succeeds and returns content, use it
+        if let Ok(output) = journalctl_result {
+            if !output.is_empty() && !output.contains("No entries") && !output.contains("cannot open") {
+                return Ok(output);
+            }
         }
-    }
     
-    // Fallback: Try common log file locations
-    let log_paths = vec![
-        format!("/var/log/{}.log", service_name),
-        format!("/var/log/{}.log", service_name.replace('-', "")),
-        format!("/var/log/{}/{}.log", service_name, service_name),
-        format!("/var/log/syslog"),
-        format!("/var/log/messages"),
-    ];
+        // Fallback: Try common log file locations
+        let log_paths = vec![
+            format!("/var/log/{}.log", service_name),
+            format!("/var/log/{}.log", service_name.replace('-', "")),
+            format!("/var/log/{}/{}.log", service_name, service_name),
+            "/var/log/syslog".to_string(),
+            "/var/log/messages".to_string(),
+        ];
     
-    for log_path in log_paths {
-        let tail_cmd = format!("test -f {} && tail -n {} {} 2>&1", log_path, lines, log_path);
-        if let Ok(output) = client.execute_command(&tail_cmd) {
-            if !output.is_empty() && !output.contains("No such file") {
-                return Ok(format!("(From file: {})\n{}", log_path, output));
+        for log_path in log_paths {
+            let tail_cmd = Cmd::new("test")
+                .raw("-f")
+                .arg(&log_path)
+                .and(Cmd::new("tail").raw("-n").arg(&lines).arg(&log_path).raw("2>&1"));
+            if let Ok(output) = client.execute_command(&tail_cmd) {
+                if !output.is_empty() && !output.contains("No such file") {
+                    return Ok(format!("(From file: {})\n{}", log_path, output));
+                }
             }
         }
-    }
     
-    // Try to find service-specific log directory
-    let find_cmd = format!("find /var/log -name '*{}*' -type f 2>/dev/null | head -5", service_name);
-    if let Ok(found_files) = client.execute_command(&find_cmd) {
-        for file in found_files.lines() {
-            if !file.is_empty() {
-                let tail_cmd = format!("tail -n {} {} 2>&1", lines, file);
-                if let Ok(output) = client.execute_command(&tail_cmd) {
-                    if !output.is_empty() {
-                        return Ok(format!("(From file: {})\n{}", file, output));
+        // Try to find service-specific log directory
+        let find_cmd = Cmd::new("find")
+            .raw("/var/log -name")
+            .arg(format!("*{}*", service_name))
+            .raw("-type f 2>/dev/null")
+            .pipe(Cmd::new("head").raw("-5"));
+        if let Ok(found_files) = client.execute_command(&find_cmd) {
+            for file in found_files.lines() {
+                if !file.is_empty() {
+                    let tail_cmd = Cmd::new("tail").raw("-n").arg(&lines).arg(file).raw("2>&1");
+                    if let Ok(output) = client.execute_command(&tail_cmd) {
+                        if !output.is_empty() {
+                            return Ok(format!("(From file: {})\n{}", file, output));
+                        }
                     }
                 }
             }
         }
-    }
     
-    Err(format!("No logs found for service '{}'. Service may not log to journal or standard log locations.", service_name))
+        Err(format!("No logs found for service '{}'. Service may not log to journal or standard log locations.", service_name))
+    })
+    .await
 }
 
+/// Run an ad-hoc command. A non-zero exit still returns its output, so the caller can
+/// show stderr and the exit code; errors are for commands that didn't finish.
 #[tauri::command]
 pub async fn execute_command(
     command: String,
+    timeout_secs: Option<u64>,
+    command_id: Option<String>,
+    server_id: Option<String>,
     state: State<'_, AppState>,
-) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-    client.execute_command(&command).map_err(|e| e.message)
+) -> Result<CommandOutput, CommandError> {
+    let running = state.running_commands.register(command_id);
+    let client = state.client(server_id.as_deref()).await?;
+    // `running` stays registered until the command returns, so it can be cancelled meanwhile
+    let token = running.token.clone();
+    client.run_blocking(move |client| {
+        let timeout = timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or_else(|| CommandCategory::Action.timeout());
+        client.run_command(&command, timeout, Some(&token))
+    })
+    .await
+}
+
+/// Abort a command started with a `command_id`. Returns false if it already finished.
+#[tauri::command]
+pub async fn cancel_command(command_id: String, state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.running_commands.cancel(&command_id))
 }
 
 #[tauri::command]
 pub async fn save_server_profile(
     profile: ServerProfile,
     state: State<'_, AppState>,
+    vault: State<'_, VaultState>,
     app: tauri::AppHandle,
 ) -> Result<(), String> {
+    // Passwords and passphrases go to the vault; the profile keeps references
+    let mut saved_profile: SavedServerProfile = SavedServerProfile::from(profile);
+    vault.seal(&app, &mut saved_profile)?;
+
     // Save to in-memory state
     let mut profiles = state.server_profiles.lock().await;
-    profiles.insert(profile.id.clone(), profile.clone());
+    profiles.insert(saved_profile.id.clone(), ServerProfile::from(saved_profile.clone()));
 
     // Save to persistent storage
-    let mut saved_profile: SavedServerProfile = SavedServerProfile::from(profile);
     if let Ok(store) = app.store(STORE_FILENAME) {
         let mut profiles_map = profiles_from_json(store.get(PROFILES_KEY));
         
@@ -578,6 +830,7 @@ pub async fn save_server_profile(
             updated.created_at = existing.created_at;
             updated.last_connected = existing.last_connected;
             updated.connect_on_startup = existing.connect_on_startup;
+            updated.labels = existing.labels.clone();
             saved_profile = updated;
         }
         
@@ -607,15 +860,28 @@ pub async fn get_server_profiles(
     Ok(profiles.values().map(|p| SavedServerProfile::from(p.clone())).collect())
 }
 
+/// Drop what is kept about a deleted profile outside the profile store: its vault
+/// entries and its metrics history
+pub(crate) fn forget_profile_data(app: &tauri::AppHandle, vault: &VaultState, profile_id: &str) -> Result<(), String> {
+    vault.forget_profile(app, profile_id)?;
+    // Leftover history files shouldn't keep the profile from being deleted
+    if let Err(e) = app.state::<MetricsStore>().forget(profile_id) {
+        log::warn!("Failed to delete metrics history of {}: {}", profile_id, e);
+    }
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn delete_server_profile(
     profile_id: String,
     state: State<'_, AppState>,
+    vault: State<'_, VaultState>,
     app: tauri::AppHandle,
 ) -> Result<(), String> {
     // Remove from in-memory state
     let mut profiles = state.server_profiles.lock().await;
     profiles.remove(&profile_id);
+    forget_profile_data(&app, &vault, &profile_id)?;
 
     // Remove from persistent storage
     if let Ok(store) = app.store(STORE_FILENAME) {
@@ -632,6 +898,7 @@ pub async fn delete_server_profile(
 pub async fn update_server_profile_metadata(
     profile_id: String,
     connect_on_startup: Option<bool>,
+    labels: Option<ProfileLabels>,
     app: tauri::AppHandle,
 ) -> Result<(), String> {
     if let Ok(store) = app.store(STORE_FILENAME) {
@@ -640,6 +907,10 @@ pub async fn update_server_profile_metadata(
             if let Some(value) = connect_on_startup {
                 profile.connect_on_startup = value;
             }
+            // Tags, group, environment, color and notes are replaced as a set
+            if let Some(labels) = labels {
+                profile.labels = labels.normalized();
+            }
             store.set(PROFILES_KEY, profiles_to_json(&profiles_map));
             store.save().map_err(|e| format!("Failed to update profile: {}", e))?;
             return Ok(());
@@ -649,449 +920,469 @@ pub async fn update_server_profile_metadata(
 }
 
 #[tauri::command]
-pub async fn get_ufw_status(state: State<'_, AppState>) -> Result<UfwStatus, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    // Get UFW status verbose
-    let status_output = client
-        .execute_command("sudo ufw status verbose 2>&1")
-        .map_err(|e| e.message)?;
-
-    let mut active = false;
-    let mut logging = "off".to_string();
-    let mut default = "deny (incoming), allow (outgoing)".to_string();
-    let mut rules: Vec<UfwRule> = Vec::new();
-
-    let lines: Vec<&str> = status_output.lines().collect();
-    let mut in_rules = false;
-
-    for line in lines {
-        let line = line.trim();
+pub async fn get_ufw_status(server_id: Option<String>, state: State<'_, AppState>) -> Result<UfwStatus, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        // Get UFW status verbose
+        let status_output = client
+            .execute_command("sudo ufw status verbose 2>&1")
+            .map_err(|e| e.message)?;
+
+        let mut active = false;
+        let mut logging = "off".to_string();
+        let mut default = "deny (incoming), allow (outgoing)".to_string();
+        let mut rules: Vec<UfwRule> = Vec::new();
+
+        let lines: Vec<&str> = status_output.lines().collect();
+        let mut in_rules = false;
+
+        for line in lines {
+            let line = line.trim();
         
-        if line.starts_with("Status:") {
-            active = line.contains("active");
-        } else if line.starts_with("Logging:") {
-            logging = line.replace("Logging:", "").trim().to_string();
-        } else if line.starts_with("Default:") {
-            default = line.replace("Default:", "").trim().to_string();
-        } else if line.starts_with("---") {
-            in_rules = true;
-        } else if in_rules && !line.is_empty() {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 3 {
-                // Parse rule line: "22/tcp                     ALLOW       Anywhere"
-                let rule_str = parts[0].to_string();
-                let action = if parts.len() > 1 { parts[1].to_string() } else { "".to_string() };
-                let from = if parts.len() > 2 { parts[2..].join(" ") } else { "Anywhere".to_string() };
+            if line.starts_with("Status:") {
+                active = line.contains("active");
+            } else if line.starts_with("Logging:") {
+                logging = line.replace("Logging:", "").trim().to_string();
+            } else if line.starts_with("Default:") {
+                default = line.replace("Default:", "").trim().to_string();
+            } else if line.starts_with("---") {
+                in_rules = true;
+            } else if in_rules && !line.is_empty() {
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                if parts.len() >= 3 {
+                    // Parse rule line: "22/tcp                     ALLOW       Anywhere"
+                    let rule_str = parts[0].to_string();
+                    let action = if parts.len() > 1 { parts[1].to_string() } else { "".to_string() };
+                    let from = if parts.len() > 2 { parts[2..].join(" ") } else { "Anywhere".to_string() };
                 
-                // Extract port if present
-                let port = rule_str.split('/').nth(1).map(|s| s.to_string());
+                    // Extract port if present
+                    let port = rule_str.split('/').nth(1).map(|s| s.to_string());
                 
-                rules.push(UfwRule {
-                    rule: rule_str,
-                    to: "Anywhere".to_string(),
-                    action,
-                    from: from.replace(" (v6)", ""),
-                    port,
-                });
+                    rules.push(UfwRule {
+                        rule: rule_str,
+                        to: "Anywhere".to_string(),
+                        action,
+                        from: from.replace(" (v6)", ""),
+                        port,
+                    });
+                }
             }
         }
-    }
 
-    Ok(UfwStatus {
-        active,
-        logging,
-        default,
-        rules,
+        Ok(UfwStatus {
+            active,
+            logging,
+            default,
+            rules,
+        })
     })
+    .await
 }
 
 #[tauri::command]
-pub async fn get_ufw_stats(state: State<'_, AppState>) -> Result<UfwStats, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let status_output = client
-        .execute_command("sudo ufw status numbered 2>&1")
-        .map_err(|e| e.message)?;
-
-    let mut total_rules = 0u32;
-    let mut allow_rules = 0u32;
-    let mut deny_rules = 0u32;
-    let mut limit_rules = 0u32;
-
-    for line in status_output.lines() {
-        let line = line.trim();
-        if line.contains("ALLOW") {
-            total_rules += 1;
-            allow_rules += 1;
-        } else if line.contains("DENY") {
-            total_rules += 1;
-            deny_rules += 1;
-        } else if line.contains("LIMIT") {
-            total_rules += 1;
-            limit_rules += 1;
+pub async fn get_ufw_stats(server_id: Option<String>, state: State<'_, AppState>) -> Result<UfwStats, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let status_output = client
+            .execute_command("sudo ufw status numbered 2>&1")
+            .map_err(|e| e.message)?;
+
+        let mut total_rules = 0u32;
+        let mut allow_rules = 0u32;
+        let mut deny_rules = 0u32;
+        let mut limit_rules = 0u32;
+
+        for line in status_output.lines() {
+            let line = line.trim();
+            if line.contains("ALLOW") {
+                total_rules += 1;
+                allow_rules += 1;
+            } else if line.contains("DENY") {
+                total_rules += 1;
+                deny_rules += 1;
+            } else if line.contains("LIMIT") {
+                total_rules += 1;
+                limit_rules += 1;
+            }
         }
-    }
 
-    Ok(UfwStats {
-        total_rules,
-        allow_rules,
-        deny_rules,
-        limit_rules,
+        Ok(UfwStats {
+            total_rules,
+            allow_rules,
+            deny_rules,
+            limit_rules,
+        })
     })
+    .await
 }
 
 #[tauri::command]
 pub async fn ufw_action(
-    action: String,
+    action: UfwAction,
+    server_id: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let command = match action.as_str() {
-        "enable" => "echo 'y' | sudo ufw enable",
-        "disable" => "sudo ufw disable",
-        "reset" => "echo 'y' | sudo ufw reset",
-        "reload" => "sudo ufw reload",
-        _ => return Err(format!("Unknown action: {}", action)),
-    };
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let command = match action {
+            UfwAction::Enable => "echo 'y' | sudo ufw enable",
+            UfwAction::Disable => "sudo ufw disable",
+            UfwAction::Reset => "echo 'y' | sudo ufw reset",
+            UfwAction::Reload => "sudo ufw reload",
+        };
 
-    client.execute_command(command).map_err(|e| e.message)
+        client
+            .execute_checked(command, CommandCategory::Action)
+            .map_err(|e| e.message)
+    })
+    .await
 }
 
 #[tauri::command]
 pub async fn ufw_add_rule(
-    rule_type: String,
+    rule_type: UfwRuleType,
     port: Option<String>,
     from_ip: Option<String>,
     to_ip: Option<String>,
-    protocol: Option<String>,
+    protocol: Option<UfwProtocol>,
+    server_id: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let mut command = String::from("sudo ufw");
-    
-    // Allow/Deny
-    command.push_str(&format!(" {}", rule_type));
-    
-    // Protocol
-    if let Some(proto) = protocol {
-        if !proto.is_empty() {
-            command.push_str(&format!(" proto {}", proto));
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        // Allow/Deny
+        let mut command = Cmd::sudo("ufw").raw(rule_type.as_arg());
+
+        // Protocol
+        if let Some(proto) = protocol {
+            command = command.raw("proto").raw(proto.as_arg());
         }
-    }
-    
-    // Port
-    if let Some(p) = port {
-        if !p.is_empty() {
-            command.push_str(&format!(" port {}", p));
+
+        // From IP
+        if let Some(from) = from_ip.filter(|f| !f.is_empty() && f != "any") {
+            command = command.raw("from").arg(shell::address(&from)?);
         }
-    }
-    
-    // From IP
-    if let Some(from) = from_ip {
-        if !from.is_empty() && from != "any" {
-            command.push_str(&format!(" from {}", from));
+
+        // To IP; ufw's full syntax needs a destination before the port
+        let port = port.filter(|p| !p.is_empty());
+        match to_ip.filter(|t| !t.is_empty() && t != "any") {
+            Some(to) => command = command.raw("to").arg(shell::address(&to)?),
+            None if port.is_some() => command = command.raw("to any"),
+            None => {}
         }
-    }
-    
-    // To IP
-    if let Some(to) = to_ip {
-        if !to.is_empty() && to != "any" {
-            command.push_str(&format!(" to {}", to));
+
+        // Port
+        if let Some(p) = port {
+            command = command.raw("port").arg(shell::port(&p)?);
         }
-    }
 
-    client.execute_command(&command).map_err(|e| e.message)
+        client
+            .execute_checked(&command, CommandCategory::Action)
+            .map_err(|e| e.message)
+    })
+    .await
 }
 
 #[tauri::command]
 pub async fn ufw_delete_rule(
     rule_number: u32,
+    server_id: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let command = format!("echo 'y' | sudo ufw delete {}", rule_number);
-    client.execute_command(&command).map_err(|e| e.message)
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let command = Cmd::new("echo")
+            .arg("y")
+            .pipe(Cmd::sudo("ufw").raw("delete").arg(rule_number.to_string()));
+        client
+            .execute_checked(&command, CommandCategory::Action)
+            .map_err(|e| e.message)
+    })
+    .await
 }
 
 #[tauri::command]
 pub async fn ufw_set_default(
-    direction: String,
-    policy: String,
+    direction: UfwDirection,
+    policy: UfwPolicy,
+    server_id: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let command = format!("sudo ufw default {} {}", policy, direction);
-    client.execute_command(&command).map_err(|e| e.message)
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let command = Cmd::sudo("ufw").raw("default").raw(policy.as_arg()).raw(direction.as_arg());
+        client
+            .execute_checked(&command, CommandCategory::Action)
+            .map_err(|e| e.message)
+    })
+    .await
 }
 
 #[tauri::command]
 pub async fn ufw_set_logging(
-    level: String,
+    level: UfwLogLevel,
+    server_id: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let command = format!("sudo ufw logging {}", level);
-    client.execute_command(&command).map_err(|e| e.message)
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let command = Cmd::sudo("ufw").raw("logging").raw(level.as_arg());
+        client
+            .execute_checked(&command, CommandCategory::Action)
+            .map_err(|e| e.message)
+    })
+    .await
 }
 
 #[tauri::command]
 pub async fn get_container_details(
     container_name: String,
+    server_id: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<ContainerDetails, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    // Get container inspect data
-    let inspect_output = client
-        .execute_command(&format!("docker inspect {}", container_name))
-        .map_err(|e| e.message)?;
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        // Get container inspect data
+        let inspect_output = client
+            .execute_command(&Cmd::new("docker").raw("inspect").arg(shell::docker_name(&container_name)?))
+            .map_err(|e| e.message)?;
+
+        // Parse JSON manually (simplified parsing)
+        let inspect_value: serde_json::Value = serde_json::from_str(&inspect_output)
+            .map_err(|e| format!("Failed to parse inspect JSON: {}", e))?;
+
+        let container_data = inspect_value.as_array()
+            .and_then(|arr| arr.first())
+            .ok_or("No container data found")?;
+
+        let config = container_data.get("Config").ok_or("No config")?;
+        let host_config = container_data.get("HostConfig").ok_or("No host config")?;
+        let network_settings = container_data.get("NetworkSettings").ok_or("No network settings")?;
+        let state_data = container_data.get("State").ok_or("No state")?;
+
+        // Extract environment variables (filter out sensitive ones)
+        let env_vars: Vec<String> = config.get("Env")
+            .and_then(|v| v.as_array())
+            .map(|arr| arr.iter()
+                .filter_map(|v| v.as_str())
+                .filter(|e| !e.contains("PASSWORD") && !e.contains("SECRET") && !e.contains("KEY") && !e.contains("TOKEN"))
+                .map(String::from)
+                .collect())
+            .unwrap_or_default();
 
-    // Parse JSON manually (simplified parsing)
-    let inspect_value: serde_json::Value = serde_json::from_str(&inspect_output)
-        .map_err(|e| format!("Failed to parse inspect JSON: {}", e))?;
-
-    let container_data = inspect_value.as_array()
-        .and_then(|arr| arr.first())
-        .ok_or("No container data found")?;
-
-    let config = container_data.get("Config").ok_or("No config")?;
-    let host_config = container_data.get("HostConfig").ok_or("No host config")?;
-    let network_settings = container_data.get("NetworkSettings").ok_or("No network settings")?;
-    let state_data = container_data.get("State").ok_or("No state")?;
-
-    // Extract environment variables (filter out sensitive ones)
-    let env_vars: Vec<String> = config.get("Env")
-        .and_then(|v| v.as_array())
-        .map(|arr| arr.iter()
-            .filter_map(|v| v.as_str())
-            .filter(|e| !e.contains("PASSWORD") && !e.contains("SECRET") && !e.contains("KEY") && !e.contains("TOKEN"))
-            .map(String::from)
-            .collect())
-        .unwrap_or_default();
-
-    // Extract ports
-    let mut ports: Vec<PortMapping> = Vec::new();
-    if let Some(port_bindings) = host_config.get("PortBindings").and_then(|v| v.as_object()) {
-        for (container_port, bindings) in port_bindings {
-            if let Some(binding_arr) = bindings.as_array() {
-                for binding in binding_arr {
-                    if let Some(obj) = binding.as_object() {
-                        ports.push(PortMapping {
-                            host_ip: obj.get("HostIp").and_then(|v| v.as_str()).unwrap_or("0.0.0.0").to_string(),
-                            host_port: obj.get("HostPort").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-                            container_port: container_port.split('/').next().unwrap_or("").to_string(),
-                            protocol: container_port.split('/').nth(1).unwrap_or("tcp").to_string(),
-                        });
+        // Extract ports
+        let mut ports: Vec<PortMapping> = Vec::new();
+        if let Some(port_bindings) = host_config.get("PortBindings").and_then(|v| v.as_object()) {
+            for (container_port, bindings) in port_bindings {
+                if let Some(binding_arr) = bindings.as_array() {
+                    for binding in binding_arr {
+                        if let Some(obj) = binding.as_object() {
+                            ports.push(PortMapping {
+                                host_ip: obj.get("HostIp").and_then(|v| v.as_str()).unwrap_or("0.0.0.0").to_string(),
+                                host_port: obj.get("HostPort").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                                container_port: container_port.split('/').next().unwrap_or("").to_string(),
+                                protocol: container_port.split('/').nth(1).unwrap_or("tcp").to_string(),
+                            });
+                        }
                     }
                 }
             }
         }
-    }
 
-    // Extract networks
-    let networks: Vec<String> = network_settings.get("Networks")
-        .and_then(|v| v.as_object())
-        .map(|obj| obj.keys().cloned().collect())
-        .unwrap_or_default();
-
-    // Extract volumes
-    let mut volumes: Vec<VolumeMount> = Vec::new();
-    if let Some(mounts) = container_data.get("Mounts").and_then(|v| v.as_array()) {
-        for mount in mounts {
-            volumes.push(VolumeMount {
-                source: mount.get("Source").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-                destination: mount.get("Destination").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-                mode: mount.get("Mode").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-            });
+        // Extract networks
+        let networks: Vec<String> = network_settings.get("Networks")
+            .and_then(|v| v.as_object())
+            .map(|obj| obj.keys().cloned().collect())
+            .unwrap_or_default();
+
+        // Extract volumes
+        let mut volumes: Vec<VolumeMount> = Vec::new();
+        if let Some(mounts) = container_data.get("Mounts").and_then(|v| v.as_array()) {
+            for mount in mounts {
+                volumes.push(VolumeMount {
+                    source: mount.get("Source").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                    destination: mount.get("Destination").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                    mode: mount.get("Mode").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                });
+            }
         }
-    }
 
-    // Extract labels
-    let mut labels: Vec<Label> = Vec::new();
-    if let Some(labels_obj) = config.get("Labels").and_then(|v| v.as_object()) {
-        for (key, value) in labels_obj {
-            labels.push(Label {
-                key: key.clone(),
-                value: value.as_str().unwrap_or("").to_string(),
-            });
+        // Extract labels
+        let mut labels: Vec<Label> = Vec::new();
+        if let Some(labels_obj) = config.get("Labels").and_then(|v| v.as_object()) {
+            for (key, value) in labels_obj {
+                labels.push(Label {
+                    key: key.clone(),
+                    value: value.as_str().unwrap_or("").to_string(),
+                });
+            }
         }
-    }
 
-    let started_at = state_data.get("StartedAt")
-        .and_then(|v| v.as_str())
-        .map(String::from);
-
-    Ok(ContainerDetails {
-        id: container_data.get("Id").and_then(|v| v.as_str()).unwrap_or("").to_string()[..12].to_string(),
-        name: container_data.get("Name").and_then(|v| v.as_str()).unwrap_or("").trim_start_matches('/').to_string(),
-        image: container_data.get("Image").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-        state: state_data.get("Status").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-        status: container_data.get("State").and_then(|v| v.get("Status")).and_then(|v| v.as_str()).unwrap_or("").to_string(),
-        created: container_data.get("Created").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-        started_at,
-        env_vars,
-        ports,
-        networks,
-        volumes,
-        labels,
-        command: config.get("Cmd")
-            .and_then(|v| v.as_array())
-            .map(|arr| arr.iter().filter_map(|v| v.as_str()).collect::<Vec<_>>().join(" "))
-            .unwrap_or_default(),
-        working_dir: config.get("WorkingDir").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-        user: config.get("User").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-        restart_policy: host_config.get("RestartPolicy")
-            .and_then(|v| v.get("Name"))
+        let started_at = state_data.get("StartedAt")
             .and_then(|v| v.as_str())
-            .unwrap_or("no")
-            .to_string(),
-        memory_limit: host_config.get("Memory").and_then(|v| v.as_u64())
-            .map(|m| if m > 0 { format!("{:.2} GB", m as f64 / 1024.0 / 1024.0 / 1024.0) } else { "Unlimited".to_string() })
-            .unwrap_or("Unlimited".to_string()),
-        cpu_limit: host_config.get("NanoCpus").and_then(|v| v.as_u64())
-            .map(|c| if c > 0 { format!("{:.2} CPUs", c as f64 / 1_000_000_000.0) } else { "Unlimited".to_string() })
-            .unwrap_or("Unlimited".to_string()),
+            .map(String::from);
+
+        Ok(ContainerDetails {
+            id: container_data.get("Id").and_then(|v| v.as_str()).unwrap_or("").to_string()[..12].to_string(),
+            name: container_data.get("Name").and_then(|v| v.as_str()).unwrap_or("").trim_start_matches('/').to_string(),
+            image: container_data.get("Image").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            state: state_data.get("Status").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            status: container_data.get("State").and_then(|v| v.get("Status")).and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            created: container_data.get("Created").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            started_at,
+            env_vars,
+            ports,
+            networks,
+            volumes,
+            labels,
+            command: config.get("Cmd")
+                .and_then(|v| v.as_array())
+                .map(|arr| arr.iter().filter_map(|v| v.as_str()).collect::<Vec<_>>().join(" "))
+                .unwrap_or_default(),
+            working_dir: config.get("WorkingDir").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            user: config.get("User").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            restart_policy: host_config.get("RestartPolicy")
+                .and_then(|v| v.get("Name"))
+                .and_then(|v| v.as_str())
+                .unwrap_or("no")
+                .to_string(),
+            memory_limit: host_config.get("Memory").and_then(|v| v.as_u64())
+                .map(|m| if m > 0 { format!("{:.2} GB", m as f64 / 1024.0 / 1024.0 / 1024.0) } else { "Unlimited".to_string() })
+                .unwrap_or("Unlimited".to_string()),
+            cpu_limit: host_config.get("NanoCpus").and_then(|v| v.as_u64())
+                .map(|c| if c > 0 { format!("{:.2} CPUs", c as f64 / 1_000_000_000.0) } else { "Unlimited".to_string() })
+                .unwrap_or("Unlimited".to_string()),
+        })
     })
+    .await
 }
 
 #[tauri::command]
-pub async fn get_docker_volumes(state: State<'_, AppState>) -> Result<Vec<DockerVolume>, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let output = client
-        .execute_command("docker volume ls --format '{{.Name}}|{{.Driver}}|{{.Mountpoint}}|{{.Scope}}'")
-        .map_err(|e| e.message)?;
-
-    let mut volumes: Vec<DockerVolume> = Vec::new();
-    for line in output.lines() {
-        let parts: Vec<&str> = line.split('|').collect();
-        if parts.len() >= 4 {
-            volumes.push(DockerVolume {
-                name: parts[0].to_string(),
-                driver: parts[1].to_string(),
-                mountpoint: parts[2].to_string(),
-                scope: parts[3].to_string(),
-                labels: Vec::new(),
-            });
+pub async fn get_docker_volumes(server_id: Option<String>, state: State<'_, AppState>) -> Result<Vec<DockerVolume>, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let output = client
+            .execute_command("docker volume ls --format '{{.Name}}|{{.Driver}}|{{.Mountpoint}}|{{.Scope}}'")
+            .map_err(|e| e.message)?;
+
+        let mut volumes: Vec<DockerVolume> = Vec::new();
+        for line in output.lines() {
+            let parts: Vec<&str> = line.split('|').collect();
+            if parts.len() >= 4 {
+                volumes.push(DockerVolume {
+                    name: parts[0].to_string(),
+                    driver: parts[1].to_string(),
+                    mountpoint: parts[2].to_string(),
+                    scope: parts[3].to_string(),
+                    labels: Vec::new(),
+                });
+            }
         }
-    }
-    Ok(volumes)
+        Ok(volumes)
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn get_docker_networks(state: State<'_, AppState>) -> Result<Vec<DockerNetwork>, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let output = client
-        .execute_command("docker network ls --format '{{.ID}}|{{.Name}}|{{.Driver}}|{{.Scope}}'")
-        .map_err(|e| e.message)?;
-
-    let mut networks: Vec<DockerNetwork> = Vec::new();
-    for line in output.lines() {
-        let parts: Vec<&str> = line.split('|').collect();
-        if parts.len() >= 4 {
-            networks.push(DockerNetwork {
-                id: parts[0].to_string(),
-                name: parts[1].to_string(),
-                driver: parts[2].to_string(),
-                scope: parts[3].to_string(),
-                subnet: None,
-                gateway: None,
-                containers: Vec::new(),
-            });
+pub async fn get_docker_networks(server_id: Option<String>, state: State<'_, AppState>) -> Result<Vec<DockerNetwork>, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let output = client
+            .execute_command("docker network ls --format '{{.ID}}|{{.Name}}|{{.Driver}}|{{.Scope}}'")
+            .map_err(|e| e.message)?;
+
+        let mut networks: Vec<DockerNetwork> = Vec::new();
+        for line in output.lines() {
+            let parts: Vec<&str> = line.split('|').collect();
+            if parts.len() >= 4 {
+                networks.push(DockerNetwork {
+                    id: parts[0].to_string(),
+                    name: parts[1].to_string(),
+                    driver: parts[2].to_string(),
+                    scope: parts[3].to_string(),
+                    subnet: None,
+                    gateway: None,
+                    containers: Vec::new(),
+                });
+            }
         }
-    }
-    Ok(networks)
+        Ok(networks)
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn get_docker_images(state: State<'_, AppState>) -> Result<Vec<DockerImage>, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let output = client
-        .execute_command("docker images --format '{{.ID}}|{{.Repository}}|{{.Tag}}|{{.Size}}|{{.CreatedAt}}' --no-trunc")
-        .map_err(|e| e.message)?;
-
-    let mut images: Vec<DockerImage> = Vec::new();
-    for line in output.lines() {
-        let parts: Vec<&str> = line.split('|').collect();
-        if parts.len() >= 5 {
-            images.push(DockerImage {
-                id: parts[0].to_string(),
-                repository: parts[1].to_string(),
-                tag: parts[2].to_string(),
-                size: 0, // Would need additional parsing
-                created: parts[4].to_string(),
-                architecture: "amd64".to_string(),
-            });
+pub async fn get_docker_images(server_id: Option<String>, state: State<'_, AppState>) -> Result<Vec<DockerImage>, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let output = client
+            .execute_command("docker images --format '{{.ID}}|{{.Repository}}|{{.Tag}}|{{.Size}}|{{.CreatedAt}}' --no-trunc")
+            .map_err(|e| e.message)?;
+
+        let mut images: Vec<DockerImage> = Vec::new();
+        for line in output.lines() {
+            let parts: Vec<&str> = line.split('|').collect();
+            if parts.len() >= 5 {
+                images.push(DockerImage {
+                    id: parts[0].to_string(),
+                    repository: parts[1].to_string(),
+                    tag: parts[2].to_string(),
+                    size: 0, // Would need additional parsing
+                    created: parts[4].to_string(),
+                    architecture: "amd64".to_string(),
+                });
+            }
         }
-    }
-    Ok(images)
+        Ok(images)
+    })
+    .await
 }
 
 #[tauri::command]
 pub async fn get_container_env(
     container_name: String,
     show_secrets: bool,
+    server_id: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<Vec<String>, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let output = client
-        .execute_command(&format!("docker inspect --format '{{{{json .Config.Env}}}}' {}", container_name))
-        .map_err(|e| e.message)?;
-
-    let env_vars: Vec<String> = serde_json::from_str(&output)
-        .unwrap_or_default();
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let output = client
+            .execute_command(
+                &Cmd::new("docker")
+                    .raw("inspect --format")
+                    .arg("{{json .Config.Env}}")
+                    .arg(shell::docker_name(&container_name)?),
+            )
+            .map_err(|e| e.message)?;
+
+        let env_vars: Vec<String> = serde_json::from_str(&output)
+            .unwrap_or_default();
 
-    if show_secrets {
-        Ok(env_vars)
-    } else {
-        // Filter out sensitive variables
-        Ok(env_vars.into_iter()
-            .filter(|e| !e.contains("PASSWORD") && !e.contains("SECRET") && !e.contains("KEY") && !e.contains("TOKEN"))
-            .collect())
-    }
+        if show_secrets {
+            Ok(env_vars)
+        } else {
+            // Filter out sensitive variables
+            Ok(env_vars.into_iter()
+                .filter(|e| !e.contains("PASSWORD") && !e.contains("SECRET") && !e.contains("KEY") && !e.contains("TOKEN"))
+                .collect())
+        }
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn find_compose_files(state: State<'_, AppState>) -> Result<Vec<ComposeProject>, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
+pub async fn find_compose_files(server_id: Option<String>, state: State<'_, AppState>) -> Result<Vec<ComposeProject>, String> {
+    let client = state.client(server_id.as_deref()).await?;
 
-    // Get current server profile to get server ID
-    // For now, use host as identifier
-    let server_id = client.get_host();
-
-    scan_compose_files(client, &state.compose_cache, &server_id).await
+    scan_compose_files(&client, &state.compose_cache, client.profile_id()).await
 }
 
 #[tauri::command]
-pub async fn refresh_compose_files(state: State<'_, AppState>) -> Result<Vec<ComposeProject>, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let server_id = client.get_host();
+pub async fn refresh_compose_files(server_id: Option<String>, state: State<'_, AppState>) -> Result<Vec<ComposeProject>, String> {
+    let client = state.client(server_id.as_deref()).await?;
 
-    refresh_compose_scan(client, &state.compose_cache, &server_id).await
+    refresh_compose_scan(&client, &state.compose_cache, client.profile_id()).await
 }
 
 #[tauri::command]
@@ -1099,663 +1390,752 @@ pub async fn get_container_logs_stream(
     container_name: String,
     lines: u32,
     follow: bool,
+    timeout_secs: Option<u64>,
+    command_id: Option<String>,
+    server_id: Option<String>,
     state: State<'_, AppState>,
-) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let command = if follow {
-        format!("docker logs --tail {} --follow {} 2>&1", lines, container_name)
-    } else {
-        format!("docker logs --tail {} {} 2>&1", lines, container_name)
-    };
+) -> Result<String, CommandError> {
+    let running = state.running_commands.register(command_id);
+    let client = state.client(server_id.as_deref()).await?;
+    let token = running.token.clone();
+    client.run_blocking(move |client| {
+        shell::docker_name(&container_name)?;
+
+        if !follow {
+            let command = Cmd::new("docker")
+                .raw("logs --tail")
+                .arg(lines.to_string())
+                .arg(&container_name)
+                .raw("2>&1");
+            return client.execute_command_with(&command, CommandCategory::Query.timeout(), Some(&token));
+        }
 
-    client.execute_command(&command).map_err(|e| e.message)
+        // `--follow` never exits on its own, so let the remote side stop it and return what was collected
+        let follow_for = timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or_else(|| CommandCategory::Stream.timeout());
+        let command = Cmd::new("timeout")
+            .arg(follow_for.as_secs().to_string())
+            .raw("docker logs --tail")
+            .arg(lines.to_string())
+            .raw("--follow")
+            .arg(&container_name)
+            .raw("2>&1");
+        client.execute_command_with(&command, follow_for + Duration::from_secs(15), Some(&token))
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn get_ufw_overview(state: State<'_, AppState>) -> Result<UfwOverview, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    // Get UFW status verbose
-    let status_output = client
-        .execute_command("sudo ufw status verbose 2>&1")
-        .map_err(|e| e.message)?;
-
-    // Get UFW numbered for rule numbers
-    let _numbered_output = client
-        .execute_command("sudo ufw status numbered 2>&1")
-        .map_err(|e| e.message)?;
+pub async fn get_ufw_overview(server_id: Option<String>, state: State<'_, AppState>) -> Result<UfwOverview, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        // Get UFW status verbose
+        let status_output = client
+            .execute_command("sudo ufw status verbose 2>&1")
+            .map_err(|e| e.message)?;
+
+        // Get UFW numbered for rule numbers
+        let _numbered_output = client
+            .execute_command("sudo ufw status numbered 2>&1")
+            .map_err(|e| e.message)?;
+
+        // Get listening ports
+        let listening_output = client
+            .execute_command("ss -tlnp 2>&1 | tail -n +2 | awk '{print $4, $6}'")
+            .unwrap_or_default();
 
-    // Get listening ports
-    let listening_output = client
-        .execute_command("ss -tlnp 2>&1 | tail -n +2 | awk '{print $4, $6}'")
-        .unwrap_or_default();
-
-    let mut active = false;
-    let mut open_ports: Vec<PortInfo> = Vec::new();
-    let mut blocked_ports: Vec<PortInfo> = Vec::new();
-    let mut all_rules: Vec<UfwRule> = Vec::new();
-    let mut total_rules = 0u32;
-    let mut allow_rules = 0u32;
-    let mut deny_rules = 0u32;
-    let mut limit_rules = 0u32;
-
-    // Parse listening ports
-    let mut listening_ports_map: std::collections::HashMap<String, String> = std::collections::HashMap::new();
-    for line in listening_output.lines() {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() >= 2 {
-            let addr = parts[0];
-            let process = parts[1].trim_matches('"').to_string();
-            if let Some(port) = addr.rsplit(':').next() {
-                if port.chars().all(|c| c.is_numeric()) {
-                    listening_ports_map.insert(port.to_string(), process);
+        let mut active = false;
+        let mut open_ports: Vec<PortInfo> = Vec::new();
+        let mut blocked_ports: Vec<PortInfo> = Vec::new();
+        let mut all_rules: Vec<UfwRule> = Vec::new();
+        let mut total_rules = 0u32;
+        let mut allow_rules = 0u32;
+        let mut deny_rules = 0u32;
+        let mut limit_rules = 0u32;
+
+        // Parse listening ports
+        let mut listening_ports_map: std::collections::HashMap<String, String> = std::collections::HashMap::new();
+        for line in listening_output.lines() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() >= 2 {
+                let addr = parts[0];
+                let process = parts[1].trim_matches('"').to_string();
+                if let Some(port) = addr.rsplit(':').next() {
+                    if port.chars().all(|c| c.is_numeric()) {
+                        listening_ports_map.insert(port.to_string(), process);
+                    }
                 }
             }
         }
-    }
 
-    // Parse rules from verbose output
-    let lines: Vec<&str> = status_output.lines().collect();
-    let mut in_rules = false;
+        // Parse rules from verbose output
+        let lines: Vec<&str> = status_output.lines().collect();
+        let mut in_rules = false;
 
-    for line in lines {
-        let line = line.trim();
+        for line in lines {
+            let line = line.trim();
         
-        if line.starts_with("Status:") {
-            active = line.contains("active");
-        } else if line.starts_with("---") {
-            in_rules = true;
-        } else if in_rules && !line.is_empty() {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 3 {
-                let rule_str = parts[0].to_string();
-                let action = if parts.len() > 1 { parts[1].to_string() } else { "".to_string() };
-                let from = if parts.len() > 2 { parts[2..].join(" ") } else { "Anywhere".to_string() };
+            if line.starts_with("Status:") {
+                active = line.contains("active");
+            } else if line.starts_with("---") {
+                in_rules = true;
+            } else if in_rules && !line.is_empty() {
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                if parts.len() >= 3 {
+                    let rule_str = parts[0].to_string();
+                    let action = if parts.len() > 1 { parts[1].to_string() } else { "".to_string() };
+                    let from = if parts.len() > 2 { parts[2..].join(" ") } else { "Anywhere".to_string() };
                 
-                // Extract port and protocol
-                let port_protocol: Vec<&str> = rule_str.split('/').collect();
-                let port = port_protocol.get(0).map(|s| s.to_string()).unwrap_or_default();
-                let protocol = port_protocol.get(1).map(|s| s.to_string()).unwrap_or_else(|| "any".to_string());
+                    // Extract port and protocol
+                    let port_protocol: Vec<&str> = rule_str.split('/').collect();
+                    let port = port_protocol.get(0).map(|s| s.to_string()).unwrap_or_default();
+                    let protocol = port_protocol.get(1).map(|s| s.to_string()).unwrap_or_else(|| "any".to_string());
                 
-                // Get service name if port is listening
-                let service_name = listening_ports_map.get(&port).cloned();
+                    // Get service name if port is listening
+                    let service_name = listening_ports_map.get(&port).cloned();
                 
-                let port_info = PortInfo {
-                    port: port.clone(),
-                    protocol: protocol.clone(),
-                    action: action.clone(),
-                    source: from.replace(" (v6)", ""),
-                    service_name,
-                };
-
-                if action.to_uppercase().contains("ALLOW") || action.to_uppercase() == "ALLOW" {
-                    open_ports.push(port_info.clone());
-                    allow_rules += 1;
-                } else if action.to_uppercase().contains("DENY") || action.to_uppercase() == "DENY" {
-                    blocked_ports.push(port_info.clone());
-                    deny_rules += 1;
-                } else if action.to_uppercase().contains("LIMIT") {
-                    open_ports.push(port_info.clone()); // LIMIT is also a form of allowing
-                    limit_rules += 1;
-                }
+                    let port_info = PortInfo {
+                        port: port.clone(),
+                        protocol: protocol.clone(),
+                        action: action.clone(),
+                        source: from.replace(" (v6)", ""),
+                        service_name,
+                    };
 
-                all_rules.push(UfwRule {
-                    rule: rule_str,
-                    to: "Anywhere".to_string(),
-                    action,
-                    from: from.replace(" (v6)", ""),
-                    port: if port.is_empty() { None } else { Some(port) },
-                });
+                    if action.to_uppercase().contains("ALLOW") || action.to_uppercase() == "ALLOW" {
+                        open_ports.push(port_info.clone());
+                        allow_rules += 1;
+                    } else if action.to_uppercase().contains("DENY") || action.to_uppercase() == "DENY" {
+                        blocked_ports.push(port_info.clone());
+                        deny_rules += 1;
+                    } else if action.to_uppercase().contains("LIMIT") {
+                        open_ports.push(port_info.clone()); // LIMIT is also a form of allowing
+                        limit_rules += 1;
+                    }
 
-                total_rules += 1;
+                    all_rules.push(UfwRule {
+                        rule: rule_str,
+                        to: "Anywhere".to_string(),
+                        action,
+                        from: from.replace(" (v6)", ""),
+                        port: if port.is_empty() { None } else { Some(port) },
+                    });
+
+                    total_rules += 1;
+                }
             }
         }
-    }
 
-    Ok(UfwOverview {
-        active,
-        open_ports,
-        blocked_ports,
-        all_rules,
-        stats: UfwStats {
-            total_rules,
-            allow_rules,
-            deny_rules,
-            limit_rules,
-        },
+        Ok(UfwOverview {
+            active,
+            open_ports,
+            blocked_ports,
+            all_rules,
+            stats: UfwStats {
+                total_rules,
+                allow_rules,
+                deny_rules,
+                limit_rules,
+            },
+        })
     })
+    .await
 }
 
 #[tauri::command]
-pub async fn get_listening_ports(state: State<'_, AppState>) -> Result<Vec<PortInfo>, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
+pub async fn get_listening_ports(server_id: Option<String>, state: State<'_, AppState>) -> Result<Vec<PortInfo>, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        // Get listening TCP ports
+        let output = client
+            .execute_command("ss -tlnp 2>&1 | tail -n +2")
+            .map_err(|e| e.message)?;
 
-    // Get listening TCP ports
-    let output = client
-        .execute_command("ss -tlnp 2>&1 | tail -n +2")
-        .map_err(|e| e.message)?;
+        let mut ports: Vec<PortInfo> = Vec::new();
 
-    let mut ports: Vec<PortInfo> = Vec::new();
-
-    for line in output.lines() {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() >= 6 {
-            let local_addr = parts[3];
-            let process_info = parts[5].trim_matches('"').to_string();
+        for line in output.lines() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() >= 6 {
+                let local_addr = parts[3];
+                let process_info = parts[5].trim_matches('"').to_string();
             
-            if let Some(port) = local_addr.rsplit(':').next() {
-                if port.chars().all(|c| c.is_numeric()) {
-                    // Extract process name
-                    let process_name = if process_info.contains("users:") {
-                        process_info.split("users:").nth(1)
-                            .and_then(|s| s.split('"').nth(1))
-                            .unwrap_or("unknown")
-                            .to_string()
-                    } else {
-                        "unknown".to_string()
-                    };
-
-                    ports.push(PortInfo {
-                        port: port.to_string(),
-                        protocol: "tcp".to_string(),
-                        action: "listening".to_string(),
-                        source: "0.0.0.0".to_string(),
-                        service_name: Some(process_name),
-                    });
+                if let Some(port) = local_addr.rsplit(':').next() {
+                    if port.chars().all(|c| c.is_numeric()) {
+                        // Extract process name
+                        let process_name = if process_info.contains("users:") {
+                            process_info.split("users:").nth(1)
+                                .and_then(|s| s.split('"').nth(1))
+                                .unwrap_or("unknown")
+                                .to_string()
+                        } else {
+                            "unknown".to_string()
+                        };
+
+                        ports.push(PortInfo {
+                            port: port.to_string(),
+                            protocol: "tcp".to_string(),
+                            action: "listening".to_string(),
+                            source: "0.0.0.0".to_string(),
+                            service_name: Some(process_name),
+                        });
+                    }
                 }
             }
         }
-    }
 
-    Ok(ports)
+        Ok(ports)
+    })
+    .await
 }
 
 // ==================== NGINX COMMANDS ====================
 
 #[tauri::command]
-pub async fn nginx_status(state: State<'_, AppState>) -> Result<NginxStatus, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    // Check if nginx is running with multiple fallback methods
-    let is_running = {
-        // Method 1: systemctl is-active
-        let systemctl_check = client
-            .execute_command("systemctl is-active nginx 2>/dev/null || echo 'inactive'")
-            .unwrap_or_default()
-            .trim()
-            .to_string();
-        
-        if systemctl_check == "active" {
-            true
-        } else {
-            // Method 2: Check if nginx process exists
-            let process_check = client
-                .execute_command("pgrep -x nginx 2>/dev/null | head -1")
+pub async fn nginx_status(server_id: Option<String>, state: State<'_, AppState>) -> Result<NginxStatus, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        // Check if nginx is running with multiple fallback methods
+        let is_running = {
+            // Method 1: systemctl is-active
+            let systemctl_check = client
+                .execute_command("systemctl is-active nginx 2>/dev/null || echo 'inactive'")
                 .unwrap_or_default()
                 .trim()
                 .to_string();
-            
-            if !process_check.is_empty() {
+        
+            if systemctl_check == "active" {
                 true
             } else {
-                // Method 3: Check pid file
-                client
-                    .execute_command("test -f /run/nginx.pid && echo 'running' || echo 'stopped'")
+                // Method 2: Check if nginx process exists
+                let process_check = client
+                    .execute_command("pgrep -x nginx 2>/dev/null | head -1")
                     .unwrap_or_default()
                     .trim()
-                    .eq("running")
+                    .to_string();
+            
+                if !process_check.is_empty() {
+                    true
+                } else {
+                    // Method 3: Check pid file
+                    client
+                        .execute_command("test -f /run/nginx.pid && echo 'running' || echo 'stopped'")
+                        .unwrap_or_default()
+                        .trim()
+                        .eq("running")
+                }
             }
-        }
-    };
+        };
 
-    // Get nginx version
-    let version = client
-        .execute_command("nginx -v 2>&1 | cut -d'/' -f2")
-        .unwrap_or_default()
-        .trim()
-        .to_string();
-
-    // Get worker processes
-    let worker_processes = client
-        .execute_command("grep -E '^\\s*worker_processes' /etc/nginx/nginx.conf 2>/dev/null | awk '{print $2}' | tr -d ';'")
-        .unwrap_or_else(|_| "auto".to_string())
-        .trim()
-        .to_string();
-
-    // Test config
-    let config_test = client
-        .execute_command("nginx -t 2>&1")
-        .unwrap_or_else(|e| format!("Config test failed: {}", e.message));
-
-    Ok(NginxStatus {
-        running: is_running,
-        version,
-        worker_processes,
-        config_test,
+        // Get nginx version
+        let version = client
+            .execute_command("nginx -v 2>&1 | cut -d'/' -f2")
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+
+        // Get worker processes
+        let worker_processes = client
+            .execute_command("grep -E '^\\s*worker_processes' /etc/nginx/nginx.conf 2>/dev/null | awk '{print $2}' | tr -d ';'")
+            .unwrap_or_else(|_| "auto".to_string())
+            .trim()
+            .to_string();
+
+        // Test config
+        let config_test = client
+            .execute_command("nginx -t 2>&1")
+            .unwrap_or_else(|e| format!("Config test failed: {}", e.message));
+
+        Ok(NginxStatus {
+            running: is_running,
+            version,
+            worker_processes,
+            config_test,
+        })
     })
+    .await
 }
 
 #[tauri::command]
-pub async fn nginx_action(action: String, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let command = format!("sudo systemctl {} nginx", action);
-    client.execute_command(&command).map_err(|e| e.message)
+pub async fn nginx_action(action: ServiceAction, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let command = Cmd::sudo("systemctl").raw(action.as_arg()).raw("nginx");
+        client
+            .execute_checked(&command, CommandCategory::Action)
+            .map_err(|e| e.message)
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn nginx_test_config(state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    client.execute_command("sudo nginx -t 2>&1").map_err(|e| e.message)
+pub async fn nginx_test_config(server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        client.execute_command("sudo nginx -t 2>&1").map_err(|e| e.message)
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn get_nginx_config(state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    client.execute_command("cat /etc/nginx/nginx.conf 2>&1").map_err(|e| e.message)
+pub async fn get_nginx_config(server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        client.execute_command("cat /etc/nginx/nginx.conf 2>&1").map_err(|e| e.message)
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn save_nginx_config(content: String, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    // Backup first
-    client.execute_command("sudo cp /etc/nginx/nginx.conf /etc/nginx/nginx.conf.bak 2>&1")
-        .map_err(|e| e.message)?;
-
-    // Write new config using tee
-    let write_cmd = format!("echo '{}' | sudo tee /etc/nginx/nginx.conf > /dev/null", content);
-    client.execute_command(&write_cmd).map_err(|e| e.message)?;
-
-    // Test config
-    let test_result = client.execute_command("sudo nginx -t 2>&1").map_err(|e| e.message)?;
-
-    if test_result.contains("syntax is ok") && test_result.contains("test is successful") {
-        Ok("Config saved and validated. Reload nginx to apply changes.".to_string())
-    } else {
-        Err(format!("Config saved but test failed: {}", test_result))
-    }
+pub async fn save_nginx_config(content: String, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        // Backup first
+        client
+            .execute_checked("sudo cp /etc/nginx/nginx.conf /etc/nginx/nginx.conf.bak", CommandCategory::Action)
+            .map_err(|e| e.message)?;
+
+        remote_file::write_file(client, "/etc/nginx/nginx.conf", content.as_bytes(), WriteOptions::root(0o644))
+            .map_err(|e| e.message)?;
+
+        // Test config; nginx reports on stderr and signals failure through the exit code
+        let test = client
+            .run_command("sudo nginx -t", CommandCategory::Query.timeout(), None)
+            .map_err(|e| e.message)?;
+
+        if test.success() {
+            Ok("Config saved and validated. Reload nginx to apply changes.".to_string())
+        } else {
+            Err(format!("Config saved but test failed: {}", test.stderr.trim()))
+        }
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn get_nginx_vhosts(state: State<'_, AppState>) -> Result<Vec<NginxVhost>, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let mut vhosts = Vec::new();
-
-    // Get sites from sites-available
-    let available_output = client
-        .execute_command("ls -1 /etc/nginx/sites-available/ 2>/dev/null | grep -v '^default$'")
-        .unwrap_or_default();
+pub async fn get_nginx_vhosts(server_id: Option<String>, state: State<'_, AppState>) -> Result<Vec<NginxVhost>, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let mut vhosts = Vec::new();
+
+        // Get sites from sites-available
+        let available_output = client
+            .execute_command("ls -1 /etc/nginx/sites-available/ 2>/dev/null | grep -v '^default$'")
+            .unwrap_or_default();
 
-    // Get enabled sites (symlinks in sites-enabled)
-    let enabled_output = client
-        .execute_command("ls -1 /etc/nginx/sites-enabled/ 2>/dev/null")
-        .unwrap_or_default();
+        // Get enabled sites (symlinks in sites-enabled)
+        let enabled_output = client
+            .execute_command("ls -1 /etc/nginx/sites-enabled/ 2>/dev/null")
+            .unwrap_or_default();
 
-    let enabled_sites: Vec<&str> = enabled_output.lines().collect();
+        let enabled_sites: Vec<&str> = enabled_output.lines().collect();
 
-    for name in available_output.lines() {
-        if name.is_empty() {
-            continue;
-        }
+        for name in available_output.lines() {
+            if name.is_empty() {
+                continue;
+            }
 
-        let enabled = enabled_sites.contains(&name);
+            let enabled = enabled_sites.contains(&name);
 
-        // Read config to extract details
-        let config = client
-            .execute_command(&format!("cat /etc/nginx/sites-available/{}", name))
-            .unwrap_or_default();
+            // Read config to extract details
+            let config = client
+                .execute_command(&Cmd::new("cat").arg(format!("/etc/nginx/sites-available/{}", name)))
+                .unwrap_or_default();
 
-        // Extract server_name
-        let server_name = config
-            .lines()
-            .find(|l| l.trim().starts_with("server_name"))
-            .map(|l| l.split_whitespace().nth(1).unwrap_or("*").trim_end_matches(';'))
-            .unwrap_or("*")
-            .to_string();
+            // Extract server_name
+            let server_name = config
+                .lines()
+                .find(|l| l.trim().starts_with("server_name"))
+                .map(|l| l.split_whitespace().nth(1).unwrap_or("*").trim_end_matches(';'))
+                .unwrap_or("*")
+                .to_string();
 
-        // Extract listen port
-        let listen_port = config
-            .lines()
-            .find(|l| l.trim().starts_with("listen"))
-            .and_then(|l| {
-                l.split_whitespace()
-                    .nth(1)
-                    .map(|s| s.trim_end_matches(';').to_string())
-            })
-            .unwrap_or_else(|| "80".to_string());
+            // Extract listen port
+            let listen_port = config
+                .lines()
+                .find(|l| l.trim().starts_with("listen"))
+                .and_then(|l| {
+                    l.split_whitespace()
+                        .nth(1)
+                        .map(|s| s.trim_end_matches(';').to_string())
+                })
+                .unwrap_or_else(|| "80".to_string());
 
-        // Check SSL
-        let ssl_enabled = config.contains("ssl_certificate");
+            // Check SSL
+            let ssl_enabled = config.contains("ssl_certificate");
 
-        // Extract root path
-        let root_path = config
-            .lines()
-            .find(|l| l.trim().starts_with("root"))
-            .map(|l| l.split_whitespace().nth(1).unwrap_or("").trim_end_matches(';'))
-            .unwrap_or("")
-            .to_string();
+            // Extract root path
+            let root_path = config
+                .lines()
+                .find(|l| l.trim().starts_with("root"))
+                .map(|l| l.split_whitespace().nth(1).unwrap_or("").trim_end_matches(';'))
+                .unwrap_or("")
+                .to_string();
 
-        vhosts.push(NginxVhost {
-            name: name.to_string(),
-            enabled,
-            server_name,
-            listen_port,
-            ssl_enabled,
-            root_path,
-        });
-    }
+            vhosts.push(NginxVhost {
+                name: name.to_string(),
+                enabled,
+                server_name,
+                listen_port,
+                ssl_enabled,
+                root_path,
+            });
+        }
 
-    Ok(vhosts)
+        Ok(vhosts)
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn get_vhost_config(name: String, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    client.execute_command(&format!("cat /etc/nginx/sites-available/{}", name)).map_err(|e| e.message)
+pub async fn get_vhost_config(name: String, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let path = format!("/etc/nginx/sites-available/{}", shell::file_name(&name)?);
+        client.execute_command(&Cmd::new("cat").arg(path)).map_err(|e| e.message)
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn save_vhost_config(name: String, content: String, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
+pub async fn save_vhost_config(name: String, content: String, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let path = format!("/etc/nginx/sites-available/{}", shell::file_name(&name)?);
 
-    // Backup first
-    let backup_cmd = format!("sudo cp /etc/nginx/sites-available/{} /etc/nginx/sites-available/{}.bak 2>&1", name, name);
-    client.execute_command(&backup_cmd).map_err(|e| e.message)?;
+        // Backup first, unless this is a new vhost
+        let backup_cmd = Cmd::sudo("test")
+            .raw("! -e")
+            .arg(&path)
+            .or(Cmd::sudo("cp").arg(&path).arg(format!("{}.bak", path)));
+        client.execute_checked(&backup_cmd, CommandCategory::Action).map_err(|e| e.message)?;
 
-    // Write new config
-    let write_cmd = format!("echo '{}' | sudo tee /etc/nginx/sites-available/{} > /dev/null", content, name);
-    client.execute_command(&write_cmd).map_err(|e| e.message)?;
+        remote_file::write_file(client, &path, content.as_bytes(), WriteOptions::root(0o644)).map_err(|e| e.message)?;
 
-    Ok(format!("Vhost '{}' saved. Reload nginx to apply changes.", name))
+        Ok(format!("Vhost '{}' saved. Reload nginx to apply changes.", name))
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn enable_vhost(name: String, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let cmd = format!(
-        "sudo ln -sf /etc/nginx/sites-available/{} /etc/nginx/sites-enabled/{} 2>&1",
-        name, name
-    );
-    client.execute_command(&cmd).map_err(|e| e.message)?;
-
-    // Test and reload
-    let test = client.execute_command("sudo nginx -t 2>&1").map_err(|e| e.message)?;
-    if test.contains("syntax is ok") {
-        client.execute_command("sudo systemctl reload nginx 2>&1").map_err(|e| e.message)?;
-        Ok(format!("Vhost '{}' enabled and nginx reloaded.", name))
-    } else {
-        Err(format!("Vhost enabled but config test failed: {}", test))
-    }
+pub async fn enable_vhost(name: String, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let name = shell::file_name(&name)?;
+        let cmd = Cmd::sudo("ln")
+            .raw("-sf")
+            .arg(format!("/etc/nginx/sites-available/{}", name))
+            .arg(format!("/etc/nginx/sites-enabled/{}", name));
+        client.execute_checked(&cmd, CommandCategory::Action).map_err(|e| e.message)?;
+
+        // Test and reload
+        let test = client
+            .run_command("sudo nginx -t", CommandCategory::Query.timeout(), None)
+            .map_err(|e| e.message)?;
+        if test.success() {
+            client
+                .execute_checked("sudo systemctl reload nginx", CommandCategory::Action)
+                .map_err(|e| e.message)?;
+            Ok(format!("Vhost '{}' enabled and nginx reloaded.", name))
+        } else {
+            Err(format!("Vhost enabled but config test failed: {}", test.stderr.trim()))
+        }
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn disable_vhost(name: String, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let cmd = format!("sudo rm -f /etc/nginx/sites-enabled/{} 2>&1", name);
-    client.execute_command(&cmd).map_err(|e| e.message)?;
-    client.execute_command("sudo systemctl reload nginx 2>&1").map_err(|e| e.message)?;
-
-    Ok(format!("Vhost '{}' disabled and nginx reloaded.", name))
+pub async fn disable_vhost(name: String, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let cmd = Cmd::sudo("rm")
+            .raw("-f")
+            .arg(format!("/etc/nginx/sites-enabled/{}", shell::file_name(&name)?));
+        client.execute_checked(&cmd, CommandCategory::Action).map_err(|e| e.message)?;
+        client
+            .execute_checked("sudo systemctl reload nginx", CommandCategory::Action)
+            .map_err(|e| e.message)?;
+
+        Ok(format!("Vhost '{}' disabled and nginx reloaded.", name))
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn delete_vhost(name: String, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    // Remove from both available and enabled
-    client.execute_command(&format!("sudo rm -f /etc/nginx/sites-available/{} 2>&1", name)).map_err(|e| e.message)?;
-    client.execute_command(&format!("sudo rm -f /etc/nginx/sites-enabled/{} 2>&1", name)).map_err(|e| e.message)?;
+pub async fn delete_vhost(name: String, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        // Remove from both available and enabled
+        let file = shell::file_name(&name)?;
+        for dir in ["/etc/nginx/sites-available", "/etc/nginx/sites-enabled"] {
+            let cmd = Cmd::sudo("rm").raw("-f").arg(format!("{}/{}", dir, file));
+            client.execute_checked(&cmd, CommandCategory::Action).map_err(|e| e.message)?;
+        }
 
-    Ok(format!("Vhost '{}' deleted.", name))
+        Ok(format!("Vhost '{}' deleted.", name))
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn get_nginx_logs(log_type: String, lines: u32, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let log_path = match log_type.as_str() {
-        "error" => "/var/log/nginx/error.log",
-        "access" => "/var/log/nginx/access.log",
-        _ => "/var/log/nginx/error.log",
-    };
+pub async fn get_nginx_logs(log_type: String, lines: u32, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let log_path = match log_type.as_str() {
+            "error" => "/var/log/nginx/error.log",
+            "access" => "/var/log/nginx/access.log",
+            _ => "/var/log/nginx/error.log",
+        };
 
-    client.execute_command(&format!("tail -n {} {} 2>&1", lines, log_path)).map_err(|e| e.message)
+        let command = Cmd::new("tail").raw("-n").arg(lines.to_string()).raw(log_path).raw("2>&1");
+        client.execute_command(&command).map_err(|e| e.message)
+    })
+    .await
 }
 
 // ==================== CRON COMMANDS ====================
 
-#[tauri::command]
-pub async fn get_user_crontab(state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
+/// Current user's crontab; a user without one gets an empty string rather than an error
+fn read_user_crontab(client: &SshClient) -> Result<String, String> {
+    let output = client
+        .run_command("crontab -l", CommandCategory::Query.timeout(), None)
+        .map_err(|e| e.message)?;
 
-    client.execute_command("crontab -l 2>&1").map_err(|e| e.message)
+    if output.success() || output.stderr.contains("no crontab for") {
+        Ok(output.stdout)
+    } else {
+        output.into_result().map_err(|e| e.message)
+    }
 }
 
 #[tauri::command]
-pub async fn save_user_crontab(content: String, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    // Write to temp file and install
-    let write_cmd = format!("echo '{}' | crontab - 2>&1", content);
-    client.execute_command(&write_cmd).map_err(|e| e.message)
+pub async fn get_user_crontab(server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        read_user_crontab(client)
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn get_system_crontab(state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    client.execute_command("cat /etc/crontab 2>&1").map_err(|e| e.message)
+pub async fn save_user_crontab(content: String, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        remote_file::install_crontab(client, &content).map_err(|e| e.message)
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn get_cron_d_jobs(state: State<'_, AppState>) -> Result<Vec<CronJob>, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let mut jobs = Vec::new();
-
-    // List files in /etc/cron.d/
-    let files = client
-        .execute_command("ls -1 /etc/cron.d/ 2>/dev/null")
-        .unwrap_or_default();
-
-    for file in files.lines() {
-        if file.is_empty() || file == "README" || file == ".placeholder" {
-            continue;
-        }
+pub async fn get_system_crontab(server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        client.execute_command("cat /etc/crontab 2>&1").map_err(|e| e.message)
+    })
+    .await
+}
 
-        let content = client
-            .execute_command(&format!("cat /etc/cron.d/{}", file))
+#[tauri::command]
+pub async fn get_cron_d_jobs(server_id: Option<String>, state: State<'_, AppState>) -> Result<Vec<CronJob>, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let mut jobs = Vec::new();
+
+        // List files in /etc/cron.d/
+        let files = client
+            .execute_command("ls -1 /etc/cron.d/ 2>/dev/null")
             .unwrap_or_default();
 
-        for (idx, line) in content.lines().enumerate() {
-            let trimmed = line.trim();
-            if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with("SHELL=") || trimmed.starts_with("PATH=") {
+        for file in files.lines() {
+            if file.is_empty() || file == "README" || file == ".placeholder" {
                 continue;
             }
 
-            // Parse cron line: minute hour day month weekday user command
-            let parts: Vec<&str> = trimmed.split_whitespace().collect();
-            if parts.len() >= 7 {
-                jobs.push(CronJob {
-                    id: idx,
-                    schedule: parts[0..5].join(" "),
-                    command: parts[6..].join(" "),
-                    user: parts[5].to_string(),
-                    enabled: !trimmed.starts_with('#'),
-                    source: format!("/etc/cron.d/{}", file),
-                });
+            let content = client
+                .execute_command(&Cmd::new("cat").arg(format!("/etc/cron.d/{}", file)))
+                .unwrap_or_default();
+
+            for (idx, line) in content.lines().enumerate() {
+                let trimmed = line.trim();
+                if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with("SHELL=") || trimmed.starts_with("PATH=") {
+                    continue;
+                }
+
+                // Parse cron line: minute hour day month weekday user command
+                let parts: Vec<&str> = trimmed.split_whitespace().collect();
+                if parts.len() >= 7 {
+                    jobs.push(CronJob {
+                        id: idx,
+                        schedule: parts[0..5].join(" "),
+                        command: parts[6..].join(" "),
+                        user: parts[5].to_string(),
+                        enabled: !trimmed.starts_with('#'),
+                        source: format!("/etc/cron.d/{}", file),
+                    });
+                }
             }
         }
-    }
 
-    Ok(jobs)
+        Ok(jobs)
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn get_cron_folders(state: State<'_, AppState>) -> Result<Vec<CronFolder>, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let mut folders = Vec::new();
-
-    for folder_name in &["cron.daily", "cron.weekly", "cron.monthly", "cron.hourly"] {
-        let path = format!("/etc/{}", folder_name);
-        let scripts_output = client
-            .execute_command(&format!("ls -1 {} 2>/dev/null", path))
-            .unwrap_or_default();
-
-        let scripts: Vec<String> = scripts_output
-            .lines()
-            .filter(|s| !s.is_empty())
-            .map(|s| s.to_string())
-            .collect();
-
-        if !scripts.is_empty() {
-            folders.push(CronFolder {
-                name: folder_name.to_string(),
-                path,
-                scripts,
-            });
+pub async fn get_cron_folders(server_id: Option<String>, state: State<'_, AppState>) -> Result<Vec<CronFolder>, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let mut folders = Vec::new();
+
+        for folder_name in &["cron.daily", "cron.weekly", "cron.monthly", "cron.hourly"] {
+            let path = format!("/etc/{}", folder_name);
+            let scripts_output = client
+                .execute_command(&Cmd::new("ls").raw("-1").arg(&path).raw("2>/dev/null"))
+                .unwrap_or_default();
+
+            let scripts: Vec<String> = scripts_output
+                .lines()
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string())
+                .collect();
+
+            if !scripts.is_empty() {
+                folders.push(CronFolder {
+                    name: folder_name.to_string(),
+                    path,
+                    scripts,
+                });
+            }
         }
-    }
 
-    Ok(folders)
+        Ok(folders)
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn get_cron_logs(lines: u32, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    // Try to find cron logs in various locations
-    let log_paths = ["/var/log/cron", "/var/log/syslog", "/var/log/messages"];
-
-    for log_path in &log_paths {
-        let test_cmd = format!("test -f {} && echo 'exists'", log_path);
-        if let Ok(result) = client.execute_command(&test_cmd) {
-            if result.trim() == "exists" {
-                let grep_cmd = format!("grep -i cron {} | tail -n {} 2>&1", log_path, lines);
-                if let Ok(logs) = client.execute_command(&grep_cmd) {
-                    if !logs.is_empty() {
-                        return Ok(logs);
+pub async fn get_cron_logs(lines: u32, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        // Try to find cron logs in various locations
+        let log_paths = ["/var/log/cron", "/var/log/syslog", "/var/log/messages"];
+
+        for log_path in &log_paths {
+            let test_cmd = Cmd::new("test").raw("-f").raw(log_path).and(Cmd::new("echo").arg("exists"));
+            if let Ok(result) = client.execute_command(&test_cmd) {
+                if result.trim() == "exists" {
+                    let grep_cmd = Cmd::new("grep")
+                        .raw("-i cron")
+                        .raw(log_path)
+                        .pipe(Cmd::new("tail").raw("-n").arg(lines.to_string()).raw("2>&1"));
+                    if let Ok(logs) = client.execute_command(&grep_cmd) {
+                        if !logs.is_empty() {
+                            return Ok(logs);
+                        }
                     }
                 }
             }
         }
-    }
 
-    // Fallback: try journalctl
-    client.execute_command(&format!("journalctl -u cron -n {} --no-pager 2>&1", lines)).map_err(|e| e.message)
+        // Fallback: try journalctl
+        let command = Cmd::new("journalctl").raw("-u cron -n").arg(lines.to_string()).raw("--no-pager 2>&1");
+        client.execute_command(&command).map_err(|e| e.message)
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn add_cron_job(schedule: String, command: String, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    // Get current crontab
-    let current = client.execute_command("crontab -l 2>&1").unwrap_or_default();
-
-    // Add new job
-    let new_entry = format!("{} {}", schedule, command);
-    let new_crontab = if current.contains("command not found") {
-        new_entry
-    } else {
-        format!("{}\n{}", current.trim_end(), new_entry)
-    };
+pub async fn add_cron_job(schedule: String, command: String, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        // A line break would smuggle extra entries into the crontab
+        shell::single_line("Schedule", &schedule)?;
+        shell::single_line("Command", &command)?;
+
+        // Get current crontab
+        let current = read_user_crontab(client)?;
+
+        // Add new job
+        let new_entry = format!("{} {}", schedule, command);
+        let new_crontab = if current.trim().is_empty() {
+            new_entry
+        } else {
+            format!("{}\n{}", current.trim_end(), new_entry)
+        };
 
-    // Install new crontab
-    let install_cmd = format!("echo '{}' | crontab - 2>&1", new_crontab);
-    client.execute_command(&install_cmd).map_err(|e| e.message)
+        remote_file::install_crontab(client, &new_crontab).map_err(|e| e.message)
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn delete_cron_job(line_number: usize, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
+pub async fn delete_cron_job(line_number: usize, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        if line_number == 0 {
+            return Err("Line numbers start at 1".to_string());
+        }
 
-    // Get current crontab and remove line
-    let current = client.execute_command("crontab -l 2>&1").unwrap_or_default();
+        // Get current crontab and remove line
+        let current = read_user_crontab(client)?;
 
-    if current.contains("command not found") {
-        return Err("No crontab found".to_string());
-    }
+        if current.trim().is_empty() {
+            return Err("No crontab found".to_string());
+        }
 
-    let new_crontab: String = current
-        .lines()
-        .enumerate()
-        .filter(|(i, _)| *i != line_number - 1) // 1-indexed
-        .map(|(_, line)| line.to_string())
-        .collect::<Vec<_>>()
-        .join("\n");
+        let new_crontab: String = current
+            .lines()
+            .enumerate()
+            .filter(|(i, _)| *i != line_number - 1) // 1-indexed
+            .map(|(_, line)| line.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
 
-    let install_cmd = format!("echo '{}' | crontab - 2>&1", new_crontab);
-    client.execute_command(&install_cmd).map_err(|e| e.message)
+        remote_file::install_crontab(client, &new_crontab).map_err(|e| e.message)
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn toggle_cron_job(line_number: usize, enabled: bool, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
+pub async fn toggle_cron_job(line_number: usize, enabled: bool, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        if line_number == 0 {
+            return Err("Line numbers start at 1".to_string());
+        }
 
-    let current = client.execute_command("crontab -l 2>&1").unwrap_or_default();
+        let current = read_user_crontab(client)?;
 
-    if current.contains("command not found") {
-        return Err("No crontab found".to_string());
-    }
+        if current.trim().is_empty() {
+            return Err("No crontab found".to_string());
+        }
 
-    let new_crontab: String = current
-        .lines()
-        .enumerate()
-        .map(|(i, line)| {
-            if i == line_number - 1 {
-                // Toggle comment
-                let trimmed = line.trim();
-                if enabled {
-                    // Remove leading #
-                    trimmed.trim_start_matches('#').trim_start().to_string()
-                } else {
-                    // Add # if not already commented
-                    if !trimmed.starts_with('#') {
-                        format!("# {}", line)
+        let new_crontab: String = current
+            .lines()
+            .enumerate()
+            .map(|(i, line)| {
+                if i == line_number - 1 {
+                    // Toggle comment
+                    let trimmed = line.trim();
+                    if enabled {
+                        // Remove leading #
+                        trimmed.trim_start_matches('#').trim_start().to_string()
                     } else {
-                        line.to_string()
+                        // Add # if not already commented
+                        if !trimmed.starts_with('#') {
+                            format!("# {}", line)
+                        } else {
+                            line.to_string()
+                        }
                     }
+                } else {
+                    line.to_string()
                 }
-            } else {
-                line.to_string()
-            }
-        })
-        .collect::<Vec<_>>()
-        .join("\n");
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
 
-    let install_cmd = format!("echo '{}' | crontab - 2>&1", new_crontab);
-    client.execute_command(&install_cmd).map_err(|e| e.message)
+        remote_file::install_crontab(client, &new_crontab).map_err(|e| e.message)
+    })
+    .await
 }
@@ -1,5 +1,6 @@
-use crate::ssh::SshClient;
-use crate::types::ComposeProject;
+use crate::shell::Cmd;
+use crate::ssh::{CommandCategory, SshClient};
+use crate::types::{profile_file_id, ComposeProject};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
@@ -46,7 +47,7 @@ impl ComposeDiscoveryCache {
     }
 
     fn cache_file_path(&self, server_id: &str) -> PathBuf {
-        self.cache_dir.join(format!("compose_cache_{}.json", server_id))
+        self.cache_dir.join(format!("compose_cache_{}.json", profile_file_id(server_id)))
     }
 
     pub async fn get(&self, server_id: &str) -> Option<ComposeCacheEntry> {
@@ -107,9 +108,12 @@ impl Default for ComposeDiscoveryCache {
     }
 }
 
+/// Common locations scanned for compose files, with limited depth
+const SCAN_PATHS: [&str; 3] = ["/home/*/", "/opt/", "/srv/"];
+
 /// Scan for Docker Compose files on the remote server
 pub async fn scan_compose_files(
-    client: &SshClient,
+    client: &Arc<SshClient>,
     cache: &ComposeDiscoveryCache,
     server_id: &str,
 ) -> Result<Vec<ComposeProject>, String> {
@@ -133,32 +137,36 @@ pub async fn scan_compose_files(
 }
 
 async fn compose_projects_from_cache(
-    client: &SshClient,
+    client: &Arc<SshClient>,
     entry: &ComposeCacheEntry,
 ) -> Result<Vec<ComposeProject>, String> {
-    let mut projects = Vec::new();
-
-    for cached in &entry.projects {
-        let content = client
-            .execute_command(&format!("cat '{}'", cached.path))
-            .unwrap_or_else(|_| "Unable to read file".to_string());
-
-        // Extract services from compose file
-        let services = extract_services_from_content(&content);
-
-        projects.push(ComposeProject {
-            name: cached.name.clone(),
-            path: cached.path.clone(),
-            services,
-            content,
-        });
-    }
+    let cached_projects = entry.projects.clone();
+    client.run_blocking(move |client| {
+        let mut projects = Vec::new();
+
+        for cached in cached_projects {
+            let content = client
+                .execute_command(&Cmd::new("cat").arg(&cached.path))
+                .unwrap_or_else(|_| "Unable to read file".to_string());
+
+            // Extract services from compose file
+            let services = extract_services_from_content(&content);
+
+            projects.push(ComposeProject {
+                name: cached.name,
+                path: cached.path,
+                services,
+                content,
+            });
+        }
 
-    Ok(projects)
+        Ok(projects)
+    })
+    .await
 }
 
 async fn scan_and_cache(
-    client: &SshClient,
+    client: &Arc<SshClient>,
     cache: &ComposeDiscoveryCache,
     server_id: &str,
 ) -> Result<Vec<ComposeProject>, String> {
@@ -167,68 +175,68 @@ async fn scan_and_cache(
         .unwrap()
         .as_secs();
 
-    // Scan common locations with limited depth
-    let scan_paths = vec![
-        "/home/*/",
-        "/opt/",
-        "/srv/",
-    ];
+    let (all_projects, cached_projects) = client.run_blocking(|client| -> Result<_, String> {
+        let mut all_projects = Vec::new();
+        let mut cached_projects = Vec::new();
 
-    let mut all_projects = Vec::new();
-    let mut cached_projects = Vec::new();
+        for base_path in SCAN_PATHS {
+            // Find compose files with limited depth (3 levels)
+            // The base paths are fixed globs the shell has to expand, so they go in unquoted
+            let find_command = Cmd::new("find").raw(base_path).raw(
+                "-maxdepth 3 -type f \\( -name 'docker-compose.yml' -o -name 'docker-compose.yaml' -o -name 'compose.yml' -o -name 'compose.yaml' \\) 2>/dev/null",
+            );
 
-    for base_path in &scan_paths {
-        // Find compose files with limited depth (3 levels)
-        let find_command = format!(
-            "find {} -maxdepth 3 -type f \\( -name 'docker-compose.yml' -o -name 'docker-compose.yaml' -o -name 'compose.yml' -o -name 'compose.yaml' \\) 2>/dev/null",
-            base_path
-        );
+            let output = client
+                .execute_command_with(&find_command, CommandCategory::Scan.timeout(), None)
+                .unwrap_or_default();
 
-        let output = client.execute_command(&find_command).unwrap_or_default();
+            for path in output.lines() {
+                if path.is_empty() {
+                    continue;
+                }
 
-        for path in output.lines() {
-            if path.is_empty() {
-                continue;
+                let path = path.trim();
+
+                // Extract project name from parent directory
+                let name = std::path::Path::new(path)
+                    .parent()
+                    .and_then(|p| p.file_name())
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("unknown")
+                    .to_string();
+
+                // Read compose file content
+                let content = client
+                    .execute_command(&Cmd::new("cat").arg(path))
+                    .unwrap_or_else(|_| "Unable to read file".to_string());
+
+                // Extract services from compose file
+                let services = extract_services_from_content(&content);
+
+                all_projects.push(ComposeProject {
+                    name: name.clone(),
+                    path: path.to_string(),
+                    services: services.clone(),
+                    content: content.clone(),
+                });
+
+                cached_projects.push(CachedComposeProject {
+                    name,
+                    path: path.to_string(),
+                    compose_file: path.to_string(),
+                });
             }
-
-            let path = path.trim();
-
-            // Extract project name from parent directory
-            let name = std::path::Path::new(path)
-                .parent()
-                .and_then(|p| p.file_name())
-                .and_then(|n| n.to_str())
-                .unwrap_or("unknown")
-                .to_string();
-
-            // Read compose file content
-            let content = client
-                .execute_command(&format!("cat '{}'", path))
-                .unwrap_or_else(|_| "Unable to read file".to_string());
-
-            // Extract services from compose file
-            let services = extract_services_from_content(&content);
-
-            all_projects.push(ComposeProject {
-                name: name.clone(),
-                path: path.to_string(),
-                services: services.clone(),
-                content: content.clone(),
-            });
-
-            cached_projects.push(CachedComposeProject {
-                name,
-                path: path.to_string(),
-                compose_file: path.to_string(),
-            });
         }
-    }
+
+        Ok((all_projects, cached_projects))
+    })
+    .await?;
 
     // Update cache
     let cache_entry = ComposeCacheEntry {
         projects: cached_projects,
         last_scan: now,
-        scan_paths: scan_paths.into_iter().map(|s| s.to_string()).collect(),
+        scan_paths: SCAN_PATHS.iter().map(|s| s.to_string()).collect(),
     };
 
     if let Err(e) = cache.set(server_id, cache_entry).await {
@@ -288,7 +296,7 @@ fn extract_services_from_content(content: &str) -> Vec<String> {
 
 /// Force refresh the compose file scan
 pub async fn refresh_compose_scan(
-    client: &SshClient,
+    client: &Arc<SshClient>,
     cache: &ComposeDiscoveryCache,
     server_id: &str,
 ) -> Result<Vec<ComposeProject>, String> {
@@ -0,0 +1,117 @@
+use crate::commands::AppState;
+use crate::ssh::SshClient;
+use crate::terminal::TerminalState;
+use crate::types::*;
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+pub const CONNECTION_STATE_EVENT: &str = "ssh://connection-state";
+
+const TICK: Duration = Duration::from_secs(1);
+/// How often idle sessions get a keepalive and a round-trip probe
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(20);
+const MAX_RECONNECT_ATTEMPTS: u32 = 8;
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+pub fn emit_state(app: &AppHandle, client: &SshClient, state: ConnectionState, attempt: u32, message: Option<String>) {
+    let event = ConnectionStateEvent {
+        server_id: client.profile_id().to_string(),
+        host: client.get_host(),
+        state,
+        attempt,
+        message,
+    };
+    if let Err(e) = app.emit(CONNECTION_STATE_EVENT, event) {
+        log::warn!("Failed to emit connection state: {}", e);
+    }
+}
+
+/// Watch a connected client until it is disconnected or dropped, reconnecting with
+/// exponential backoff whenever the connection dies. Once it gives up, the connection
+/// is removed so commands report "Not connected" instead of using a dead client.
+pub fn spawn(client: &Arc<SshClient>, app: AppHandle) {
+    let client = Arc::downgrade(client);
+    thread::spawn(move || run(client, app));
+}
+
+fn run(client: Weak<SshClient>, app: AppHandle) {
+    let mut since_keepalive = Duration::ZERO;
+
+    loop {
+        thread::sleep(TICK);
+        let Some(client) = client.upgrade() else {
+            return;
+        };
+        if client.is_closed() {
+            return;
+        }
+
+        since_keepalive += TICK;
+        let alive = if since_keepalive >= KEEPALIVE_INTERVAL {
+            since_keepalive = Duration::ZERO;
+            client.check_alive()
+        } else {
+            client.is_connected()
+        };
+
+        if !alive && !reconnect(&client, &app) {
+            if !client.is_closed() {
+                give_up(&client, &app);
+            }
+            return;
+        }
+    }
+}
+
+/// Drop a connection that couldn't be brought back, unless it was replaced meanwhile
+fn give_up(client: &Arc<SshClient>, app: &AppHandle) {
+    let removed = tauri::async_runtime::block_on(app.state::<AppState>().remove_connection(client));
+    client.disconnect();
+    if removed {
+        app.state::<TerminalState>().close_server(client.profile_id());
+    }
+}
+
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_secs(1u64 << (attempt - 1).min(5)).min(MAX_BACKOFF)
+}
+
+/// Try to bring the connection back; returns false once the monitor should stop
+fn reconnect(client: &SshClient, app: &AppHandle) -> bool {
+    let mut last_error = None;
+
+    for attempt in 1..=MAX_RECONNECT_ATTEMPTS {
+        emit_state(app, client, ConnectionState::Reconnecting, attempt, last_error.clone());
+
+        let mut waited = Duration::ZERO;
+        while waited < backoff(attempt) {
+            thread::sleep(TICK);
+            waited += TICK;
+            if client.is_closed() {
+                return false;
+            }
+        }
+
+        match client.connect() {
+            Ok(()) => {
+                log::info!("Reconnected to {} after {} attempt(s)", client.get_host(), attempt);
+                emit_state(app, client, ConnectionState::Connected, attempt, None);
+                return true;
+            }
+            // A changed or unknown host key needs the user, retrying won't help
+            Err(e) if e.code == CommandError::HOST_KEY_CHANGED || e.code == CommandError::HOST_KEY_UNKNOWN => {
+                emit_state(app, client, ConnectionState::Failed, attempt, Some(e.message));
+                return false;
+            }
+            Err(e) => {
+                log::warn!("Reconnect attempt {} to {} failed: {}", attempt, client.get_host(), e.message);
+                last_error = Some(e.message);
+            }
+        }
+    }
+
+    emit_state(app, client, ConnectionState::Failed, MAX_RECONNECT_ATTEMPTS, last_error);
+    false
+}
@@ -0,0 +1,315 @@
+use crate::commands::{profiles_from_json, AppState, PROFILES_KEY, STORE_FILENAME};
+use crate::profile_query::{select_profile_ids, ProfileFilter};
+use crate::ssh::{CancelToken, CommandCategory, SshClient};
+use crate::types::*;
+use crate::vault::VaultState;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, State};
+use tauri_plugin_store::StoreExt;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+pub const BROADCAST_RESULT_EVENT: &str = "broadcast://result";
+
+pub const DEFAULT_CONCURRENCY: usize = 5;
+pub const MAX_CONCURRENCY: usize = 32;
+
+static NEXT_BROADCAST_ID: AtomicU64 = AtomicU64::new(1);
+
+/// How to reach one server of the fleet
+pub enum HostConnection {
+    /// Already connected; the connection is shared and left open
+    Live(Arc<SshClient>),
+    /// Not connected; a connection is opened for the job and closed after it
+    Fresh(ServerProfile),
+}
+
+/// A saved profile selected for a fleet operation
+pub struct FleetHost {
+    pub server_id: String,
+    pub name: String,
+    pub host: String,
+    pub labels: ProfileLabels,
+    /// Err when the host can't be reached at all, e.g. its credentials are in a locked vault
+    pub connection: Result<HostConnection, String>,
+}
+
+impl FleetHost {
+    /// Run `f` on this host. Blocks, so call it from a blocking task.
+    pub fn with_client<T>(&self, f: impl FnOnce(&SshClient) -> Result<T, String>) -> Result<T, String> {
+        match &self.connection {
+            Ok(HostConnection::Live(client)) => f(client),
+            Ok(HostConnection::Fresh(profile)) => {
+                let client = SshClient::new(profile.clone());
+                client.connect().map_err(|e| e.message)?;
+                let result = f(&client);
+                client.disconnect();
+                result
+            }
+            Err(e) => Err(e.clone()),
+        }
+    }
+}
+
+/// The servers a fleet operation runs on: listed profiles, a tag or group filter, or both
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FleetTarget {
+    #[serde(default)]
+    pub profile_ids: Vec<String>,
+    #[serde(default)]
+    pub filter: Option<ProfileFilter>,
+}
+
+/// Look up the saved profiles `target` selects, preferring live connections
+pub async fn resolve_hosts(
+    target: &FleetTarget,
+    state: &AppState,
+    vault: &VaultState,
+    app: &AppHandle,
+) -> Result<Vec<FleetHost>, String> {
+    let store = app.store(STORE_FILENAME).map_err(|e| format!("Failed to open profile store: {}", e))?;
+    let saved = profiles_from_json(store.get(PROFILES_KEY));
+    let profile_ids = select_profile_ids(&saved, &target.profile_ids, target.filter.as_ref());
+    if profile_ids.is_empty() {
+        return Err("No servers match the selection".to_string());
+    }
+    let connections = state.connections.lock().await;
+
+    let hosts = profile_ids
+        .iter()
+        .map(|id| {
+            let Some(profile) = saved.get(id) else {
+                return FleetHost {
+                    server_id: id.clone(),
+                    name: id.clone(),
+                    host: String::new(),
+                    labels: ProfileLabels::default(),
+                    connection: Err("Profile not found".to_string()),
+                };
+            };
+            let connection = match connections.get(id) {
+                Some(live) => Ok(HostConnection::Live(Arc::clone(&live.client))),
+                None => {
+                    let mut profile = ServerProfile::from(profile.clone());
+                    vault.open(app, &mut profile).map(|_| HostConnection::Fresh(profile))
+                }
+            };
+            FleetHost {
+                server_id: id.clone(),
+                name: profile.name.clone(),
+                host: profile.host.clone(),
+                labels: profile.labels.clone(),
+                connection,
+            }
+        })
+        .collect();
+    Ok(hosts)
+}
+
+/// Run the blocking `job` on every host, at most `concurrency` at a time, calling
+/// `on_done` as each one finishes. Results are returned in host order, not finishing order.
+pub async fn for_each_host<T, J, D>(hosts: Vec<FleetHost>, concurrency: usize, job: J, on_done: D) -> Result<Vec<T>, String>
+where
+    T: Send + 'static,
+    J: Fn(&FleetHost) -> T + Send + Sync + 'static,
+    D: Fn(&T) + Send + Sync + 'static,
+{
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+    let job = Arc::new(job);
+    let on_done = Arc::new(on_done);
+    let mut tasks = JoinSet::new();
+
+    for (index, host) in hosts.into_iter().enumerate() {
+        let permits = Arc::clone(&permits);
+        let job = Arc::clone(&job);
+        let on_done = Arc::clone(&on_done);
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            let result = tokio::task::spawn_blocking(move || job(&host))
+                .await
+                .map_err(|e| format!("Fleet task failed: {}", e));
+            if let Ok(result) = &result {
+                on_done(result);
+            }
+            (index, result)
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        let (index, result) = joined.map_err(|e| format!("Fleet task failed: {}", e))?;
+        results.push((index, result?));
+    }
+    results.sort_by_key(|(index, _)| *index);
+    Ok(results.into_iter().map(|(_, result)| result).collect())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BroadcastRequest {
+    #[serde(flatten)]
+    pub target: FleetTarget,
+    pub command: String,
+    /// The QuickCommands entry the command came from, echoed back in the summary
+    pub label: Option<String>,
+    /// Hosts worked on at once; defaults to 5
+    pub concurrency: Option<usize>,
+    pub timeout_secs: Option<u64>,
+    /// Id for `cancel_command`; hosts not started yet are skipped once cancelled
+    pub broadcast_id: Option<String>,
+}
+
+/// One host's outcome, emitted as a `broadcast://result` event as soon as it is known
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BroadcastHostResult {
+    pub broadcast_id: String,
+    pub server_id: String,
+    pub name: String,
+    pub host: String,
+    pub success: bool,
+    /// None when the command never ran or didn't finish
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub duration_ms: u64,
+    /// Why the command couldn't be run: connection, auth, timeout or cancellation
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BroadcastFailure {
+    pub server_id: String,
+    pub name: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BroadcastSummary {
+    pub broadcast_id: String,
+    pub label: Option<String>,
+    pub command: String,
+    pub total: usize,
+    pub succeeded: Vec<String>,
+    pub failed: Vec<BroadcastFailure>,
+    pub cancelled: bool,
+    pub duration_ms: u64,
+}
+
+fn run_on_host(
+    broadcast_id: String,
+    host: &FleetHost,
+    command: &str,
+    timeout: Duration,
+    cancel: &CancelToken,
+) -> BroadcastHostResult {
+    let started = Instant::now();
+    let output = if cancel.is_cancelled() {
+        Err("Cancelled".to_string())
+    } else {
+        host.with_client(|client| client.run_command(command, timeout, Some(cancel)).map_err(|e| e.message))
+    };
+
+    let mut result = BroadcastHostResult {
+        broadcast_id,
+        server_id: host.server_id.clone(),
+        name: host.name.clone(),
+        host: host.host.clone(),
+        success: false,
+        exit_code: None,
+        stdout: String::new(),
+        stderr: String::new(),
+        duration_ms: started.elapsed().as_millis() as u64,
+        error: None,
+    };
+    match output {
+        Ok(output) => {
+            result.success = output.success();
+            result.exit_code = Some(output.exit_code);
+            result.stdout = output.stdout;
+            result.stderr = output.stderr;
+        }
+        Err(e) => result.error = Some(e),
+    }
+    result
+}
+
+fn failure_reason(result: &BroadcastHostResult) -> String {
+    if let Some(error) = &result.error {
+        return error.clone();
+    }
+    match result.stderr.trim().lines().last() {
+        Some(line) => line.to_string(),
+        None => format!("Exit code {}", result.exit_code.unwrap_or(-1)),
+    }
+}
+
+/// Run one command on several saved servers, a few at a time. Each host's result is
+/// emitted as `broadcast://result` when it finishes; the returned summary lists failures.
+/// Servers that aren't connected get a connection just for the run.
+#[tauri::command]
+pub async fn broadcast_command(
+    request: BroadcastRequest,
+    state: State<'_, AppState>,
+    vault: State<'_, VaultState>,
+    app: AppHandle,
+) -> Result<BroadcastSummary, String> {
+    if request.command.trim().is_empty() {
+        return Err("Command must not be empty".to_string());
+    }
+    let broadcast_id = request
+        .broadcast_id
+        .clone()
+        .unwrap_or_else(|| format!("broadcast-{}", NEXT_BROADCAST_ID.fetch_add(1, Ordering::Relaxed)));
+    let running = state.running_commands.register(Some(broadcast_id.clone()));
+    let timeout = request
+        .timeout_secs
+        .map(Duration::from_secs)
+        .unwrap_or_else(|| CommandCategory::Action.timeout());
+    let concurrency = request.concurrency.unwrap_or(DEFAULT_CONCURRENCY).clamp(1, MAX_CONCURRENCY);
+
+    let started = Instant::now();
+    let hosts = resolve_hosts(&request.target, &state, &vault, &app).await?;
+    let total = hosts.len();
+    let cancel = running.token.clone();
+    let command = request.command.clone();
+    let job_id = broadcast_id.clone();
+    let emitter = app.clone();
+    let results = for_each_host(
+        hosts,
+        concurrency,
+        move |host| run_on_host(job_id.clone(), host, &command, timeout, &cancel),
+        move |result| {
+            if let Err(e) = emitter.emit(BROADCAST_RESULT_EVENT, result) {
+                log::warn!("Failed to emit broadcast result: {}", e);
+            }
+        },
+    )
+    .await?;
+
+    let mut succeeded = Vec::new();
+    let mut failed = Vec::new();
+    for result in &results {
+        if result.success {
+            succeeded.push(result.server_id.clone());
+        } else {
+            failed.push(BroadcastFailure {
+                server_id: result.server_id.clone(),
+                name: result.name.clone(),
+                reason: failure_reason(result),
+            });
+        }
+    }
+
+    Ok(BroadcastSummary {
+        broadcast_id,
+        label: request.label,
+        command: request.command,
+        total,
+        succeeded,
+        failed,
+        cancelled: running.token.is_cancelled(),
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
@@ -0,0 +1,407 @@
+use crate::commands::AppState;
+use crate::fleet::{for_each_host, resolve_hosts, FleetHost, FleetTarget, DEFAULT_CONCURRENCY, MAX_CONCURRENCY};
+use crate::metrics;
+use crate::profile_query::ProfileFilter;
+use crate::ssh::CommandCategory;
+use crate::types::*;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use tauri::{AppHandle, State};
+
+const CPU_WARNING: f64 = 80.0;
+const CPU_CRITICAL: f64 = 95.0;
+const MEMORY_WARNING: f64 = 85.0;
+const MEMORY_CRITICAL: f64 = 95.0;
+const DISK_WARNING: f64 = 85.0;
+const DISK_CRITICAL: f64 = 95.0;
+/// 1-minute load per core
+const LOAD_WARNING: f64 = 1.5;
+const LOAD_CRITICAL: f64 = 3.0;
+
+const CORES_COMMAND: &str = "nproc";
+const FAILED_UNITS_COMMAND: &str = "systemctl list-units --state=failed --no-legend --plain | awk '{print $1}'";
+const CONTAINERS_COMMAND: &str = "docker ps -a --format '{{.Names}}|{{.State}}|{{.Status}}'";
+
+/// Worst first, so sorting by status puts trouble at the top
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Unreachable,
+    /// Reachable, but the snapshot came back without CPU or memory readings
+    Unknown,
+    Critical,
+    Warning,
+    Healthy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthSortKey {
+    #[default]
+    Status,
+    Name,
+    Cpu,
+    Memory,
+    Disk,
+    Load,
+}
+
+/// One row of the fleet overview. Readings are None when the host couldn't be reached.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostHealth {
+    pub server_id: String,
+    pub name: String,
+    pub host: String,
+    pub environment: Option<Environment>,
+    pub group: Option<String>,
+    pub status: HealthStatus,
+    /// What pushed the status above healthy, e.g. "Disk /var at 93%"
+    pub issues: Vec<String>,
+    pub error: Option<String>,
+    pub cpu_percent: Option<f64>,
+    pub memory_percent: Option<f64>,
+    pub fullest_disk: Option<DiskUsage>,
+    pub load_avg: Option<[f64; 3]>,
+    pub cpu_count: Option<u32>,
+    pub failed_units: Vec<String>,
+    pub unhealthy_containers: Vec<String>,
+    pub duration_ms: u64,
+}
+
+impl HostHealth {
+    fn load_per_core(&self) -> Option<f64> {
+        self.load_avg.map(|load| load[0] / self.cpu_count.unwrap_or(1).max(1) as f64)
+    }
+}
+
+/// The host that does worst on one measure
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Offender {
+    pub metric: String,
+    pub server_id: String,
+    pub name: String,
+    pub value: f64,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatusCounts {
+    pub healthy: usize,
+    pub warning: usize,
+    pub critical: usize,
+    pub unknown: usize,
+    pub unreachable: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FleetOverview {
+    pub hosts: Vec<HostHealth>,
+    pub counts: StatusCounts,
+    pub worst_offenders: Vec<Offender>,
+    pub generated_at: u64,
+    pub duration_ms: u64,
+}
+
+/// The /proc snapshot the dashboard uses plus the fleet-only checks. There's no
+/// previous poll to diff CPU time against, so it always takes the two samples.
+fn snapshot_script() -> String {
+    let mut sections = metrics::proc_sections(true);
+    sections.extend([
+        ("cores", CORES_COMMAND),
+        ("units", FAILED_UNITS_COMMAND),
+        ("containers", CONTAINERS_COMMAND),
+    ]);
+    metrics::sectioned_script(&sections)
+}
+
+/// Restarting, dead, or failing their health check
+fn unhealthy_containers(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '|');
+            let (name, state, status) = (parts.next()?, parts.next()?, parts.next().unwrap_or(""));
+            let unhealthy = matches!(state, "restarting" | "dead") || status.contains("(unhealthy)");
+            unhealthy.then(|| name.to_string())
+        })
+        .collect()
+}
+
+/// A row with no readings yet
+fn empty_row(host: &FleetHost, duration_ms: u64) -> HostHealth {
+    HostHealth {
+        server_id: host.server_id.clone(),
+        name: host.name.clone(),
+        host: host.host.clone(),
+        environment: host.labels.environment,
+        group: host.labels.group.clone(),
+        status: HealthStatus::Healthy,
+        issues: Vec::new(),
+        error: None,
+        cpu_percent: None,
+        memory_percent: None,
+        fullest_disk: None,
+        load_avg: None,
+        cpu_count: None,
+        failed_units: Vec::new(),
+        unhealthy_containers: Vec::new(),
+        duration_ms,
+    }
+}
+
+fn unreachable(host: &FleetHost, error: String, duration_ms: u64) -> HostHealth {
+    HostHealth {
+        status: HealthStatus::Unreachable,
+        issues: vec![error.clone()],
+        error: Some(error),
+        ..empty_row(host, duration_ms)
+    }
+}
+
+/// Turn the snapshot script's output into a row, with status and issues filled in
+fn parse_snapshot(host: &FleetHost, output: &str, duration_ms: u64) -> HostHealth {
+    let sections = metrics::split_sections(output);
+    let section = |name: &str| sections.get(name).map(String::as_str).unwrap_or("");
+    let snapshot = metrics::ProcSnapshot::from_sections(&sections);
+    let fullest_disk = snapshot.disks.iter().max_by(|a, b| a.percent.total_cmp(&b.percent)).cloned();
+
+    let mut health = HostHealth {
+        cpu_percent: snapshot.cpu.map(|_| snapshot.cpu_percent(None)),
+        memory_percent: (snapshot.memory_total > 0).then(|| snapshot.memory_percent()),
+        fullest_disk,
+        load_avg: Some(snapshot.load_avg),
+        cpu_count: section("cores").trim().parse().ok(),
+        failed_units: section("units").lines().map(str::trim).filter(|u| !u.is_empty()).map(str::to_string).collect(),
+        unhealthy_containers: unhealthy_containers(section("containers")),
+        ..empty_row(host, duration_ms)
+    };
+    assess(&mut health);
+    if health.cpu_percent.is_none() && health.memory_percent.is_none() {
+        // Not reading a host isn't the same as it being fine
+        let error = "No CPU or memory readings in the host's reply".to_string();
+        health.status = HealthStatus::Unknown;
+        health.issues.insert(0, error.clone());
+        health.error = Some(error);
+    }
+    health
+}
+
+/// Work out the status from the readings and say why it isn't healthy
+fn assess(health: &mut HostHealth) {
+    let mut status = HealthStatus::Healthy;
+    let mut issues = Vec::new();
+    let mut check = |value: Option<f64>, warning: f64, critical: f64, describe: &dyn Fn(f64) -> String| {
+        let Some(value) = value else { return };
+        if value >= critical {
+            status = status.min(HealthStatus::Critical);
+        } else if value >= warning {
+            status = status.min(HealthStatus::Warning);
+        } else {
+            return;
+        }
+        issues.push(describe(value));
+    };
+
+    check(health.cpu_percent, CPU_WARNING, CPU_CRITICAL, &|v| format!("CPU at {:.0}%", v));
+    check(health.memory_percent, MEMORY_WARNING, MEMORY_CRITICAL, &|v| format!("Memory at {:.0}%", v));
+    if let Some(disk) = &health.fullest_disk {
+        let mount = disk.mount_point.clone();
+        check(Some(disk.percent), DISK_WARNING, DISK_CRITICAL, &|v| format!("Disk {} at {:.0}%", mount, v));
+    }
+    check(health.load_per_core(), LOAD_WARNING, LOAD_CRITICAL, &|v| format!("Load {:.2} per core", v));
+
+    if !health.failed_units.is_empty() {
+        status = status.min(HealthStatus::Warning);
+        issues.push(format!("Failed units: {}", health.failed_units.join(", ")));
+    }
+    if !health.unhealthy_containers.is_empty() {
+        status = status.min(HealthStatus::Warning);
+        issues.push(format!("Unhealthy containers: {}", health.unhealthy_containers.join(", ")));
+    }
+    health.status = status;
+    health.issues = issues;
+}
+
+/// Metric columns sort highest first, name alphabetically, status worst first
+pub fn sort_hosts(hosts: &mut [HostHealth], key: HealthSortKey) {
+    let by_metric = |value: fn(&HostHealth) -> Option<f64>| {
+        move |a: &HostHealth, b: &HostHealth| {
+            let (a, b) = (value(a).unwrap_or(-1.0), value(b).unwrap_or(-1.0));
+            b.total_cmp(&a)
+        }
+    };
+    let name = |a: &HostHealth, b: &HostHealth| a.name.to_lowercase().cmp(&b.name.to_lowercase());
+    match key {
+        HealthSortKey::Status => hosts.sort_by(|a, b| a.status.cmp(&b.status).then_with(|| name(a, b))),
+        HealthSortKey::Name => hosts.sort_by(name),
+        HealthSortKey::Cpu => hosts.sort_by(by_metric(|h| h.cpu_percent)),
+        HealthSortKey::Memory => hosts.sort_by(by_metric(|h| h.memory_percent)),
+        HealthSortKey::Disk => hosts.sort_by(by_metric(|h| h.fullest_disk.as_ref().map(|d| d.percent))),
+        HealthSortKey::Load => hosts.sort_by(by_metric(|h| h.load_per_core())),
+    }
+}
+
+/// The worst host for each measure; counts that are zero everywhere are left out
+fn worst_offenders(hosts: &[HostHealth]) -> Vec<Offender> {
+    let mut offenders = Vec::new();
+    let mut worst = |metric: &str, value: &dyn Fn(&HostHealth) -> Option<f64>, detail: &dyn Fn(&HostHealth) -> String| {
+        let top = hosts
+            .iter()
+            .filter_map(|h| value(h).map(|v| (h, v)))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b));
+        if let Some((host, value)) = top.filter(|(_, v)| *v > 0.0) {
+            offenders.push(Offender {
+                metric: metric.to_string(),
+                server_id: host.server_id.clone(),
+                name: host.name.clone(),
+                value,
+                detail: detail(host),
+            });
+        }
+    };
+
+    worst("cpu", &|h| h.cpu_percent, &|h| format!("{:.0}% CPU", h.cpu_percent.unwrap_or(0.0)));
+    worst("memory", &|h| h.memory_percent, &|h| format!("{:.0}% memory", h.memory_percent.unwrap_or(0.0)));
+    worst("disk", &|h| h.fullest_disk.as_ref().map(|d| d.percent), &|h| match &h.fullest_disk {
+        Some(disk) => format!("{} at {:.0}%", disk.mount_point, disk.percent),
+        None => String::new(),
+    });
+    worst("load", &|h| h.load_per_core(), &|h| format!("{:.2} per core", h.load_per_core().unwrap_or(0.0)));
+    worst("failed_units", &|h| Some(h.failed_units.len() as f64), &|h| h.failed_units.join(", "));
+    worst("unhealthy_containers", &|h| Some(h.unhealthy_containers.len() as f64), &|h| h.unhealthy_containers.join(", "));
+    offenders
+}
+
+fn count_statuses(hosts: &[HostHealth]) -> StatusCounts {
+    let mut counts = StatusCounts::default();
+    for host in hosts {
+        match host.status {
+            HealthStatus::Healthy => counts.healthy += 1,
+            HealthStatus::Warning => counts.warning += 1,
+            HealthStatus::Critical => counts.critical += 1,
+            HealthStatus::Unknown => counts.unknown += 1,
+            HealthStatus::Unreachable => counts.unreachable += 1,
+        }
+    }
+    counts
+}
+
+fn check_host(host: &FleetHost, script: &str) -> HostHealth {
+    let started = Instant::now();
+    let output = host.with_client(|client| {
+        client
+            .run_command(script, CommandCategory::Query.timeout(), None)
+            .map(|output| output.stdout)
+            .map_err(|e| e.message)
+    });
+    let duration_ms = started.elapsed().as_millis() as u64;
+    match output {
+        Ok(output) => parse_snapshot(host, &output, duration_ms),
+        Err(e) => unreachable(host, e, duration_ms),
+    }
+}
+
+/// Take a health snapshot of every server `target` selects (all saved profiles by default)
+/// in one round trip each, and rank them. Connected servers reuse their connection.
+#[tauri::command]
+pub async fn get_fleet_overview(
+    target: Option<FleetTarget>,
+    sort_by: Option<HealthSortKey>,
+    concurrency: Option<usize>,
+    state: State<'_, AppState>,
+    vault: State<'_, crate::vault::VaultState>,
+    app: AppHandle,
+) -> Result<FleetOverview, String> {
+    let target = target.unwrap_or_else(|| FleetTarget {
+        profile_ids: Vec::new(),
+        filter: Some(ProfileFilter::default()),
+    });
+    let started = Instant::now();
+    let hosts = resolve_hosts(&target, &state, &vault, &app).await?;
+    let script = snapshot_script();
+    let concurrency = concurrency.unwrap_or(DEFAULT_CONCURRENCY).clamp(1, MAX_CONCURRENCY);
+
+    let mut hosts = for_each_host(hosts, concurrency, move |host| check_host(host, &script), |_| {}).await?;
+    sort_hosts(&mut hosts, sort_by.unwrap_or_default());
+
+    Ok(FleetOverview {
+        counts: count_statuses(&hosts),
+        worst_offenders: worst_offenders(&hosts),
+        hosts,
+        generated_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host(name: &str) -> FleetHost {
+        FleetHost {
+            server_id: name.to_string(),
+            name: name.to_string(),
+            host: format!("{}.example.com", name),
+            labels: ProfileLabels::default(),
+            connection: Err("not used".to_string()),
+        }
+    }
+
+    const BUSY: &str = "@@dpanel:cpu_baseline\ncpu 100 0 0 900\n@@dpanel:cpu\ncpu 1070 0 0 930\n\
+@@dpanel:meminfo\nMemTotal: 8000 kB\nMemAvailable: 4000 kB\n\
+@@dpanel:disk\nFilesystem 1-blocks Used Available Capacity Mounted on\n/dev/sda1 100 50 50 50% /\n/dev/sdb1 100 93 7 93% /var\n/dev/loop0 100 100 0 100% /snap/core/1\n\
+@@dpanel:loadavg\n6.00 4.00 2.00 3/210 4567\n@@dpanel:cores\n4\n@@dpanel:units\nbackup.service\n\
+@@dpanel:containers\nweb|running|Up 2 hours (unhealthy)\ndb|running|Up 2 hours\nworker|restarting|Restarting (1) 5 seconds ago\n";
+
+    const QUIET: &str = "@@dpanel:cpu_baseline\ncpu 0 0 0 100\n@@dpanel:cpu\ncpu 3 0 0 197\n\
+@@dpanel:meminfo\nMemTotal: 8 kB\nMemAvailable: 7 kB\n@@dpanel:cores\n2\n";
+
+    #[test]
+    fn test_snapshot_is_parsed_and_assessed() {
+        let health = parse_snapshot(&host("busy"), BUSY, 10);
+        assert_eq!(health.status, HealthStatus::Critical);
+        assert_eq!(health.fullest_disk.as_ref().map(|d| d.mount_point.as_str()), Some("/var"));
+        assert_eq!(health.load_per_core(), Some(1.5));
+        assert_eq!(health.failed_units, vec!["backup.service"]);
+        assert_eq!(health.unhealthy_containers, vec!["web", "worker"]);
+        assert!(health.issues.iter().any(|i| i == "CPU at 97%"));
+        assert!(health.issues.iter().any(|i| i == "Disk /var at 93%"));
+
+        let quiet = parse_snapshot(&host("quiet"), QUIET, 10);
+        assert_eq!(quiet.status, HealthStatus::Healthy);
+        assert!(quiet.issues.is_empty());
+
+        // A reply without the /proc sections isn't a healthy host at 0%
+        let blank = parse_snapshot(&host("blank"), "@@dpanel:cores\n2\n", 10);
+        assert_eq!(blank.status, HealthStatus::Unknown);
+        assert_eq!((blank.cpu_percent, blank.memory_percent), (None, None));
+        assert!(blank.error.is_some());
+    }
+
+    #[test]
+    fn test_sorting_and_worst_offenders() {
+        let mut hosts = vec![
+            parse_snapshot(&host("quiet"), QUIET, 10),
+            unreachable(&host("gone"), "Connection refused".to_string(), 10),
+            parse_snapshot(&host("busy"), BUSY, 10),
+        ];
+        sort_hosts(&mut hosts, HealthSortKey::Status);
+        let order: Vec<&str> = hosts.iter().map(|h| h.name.as_str()).collect();
+        assert_eq!(order, vec!["gone", "busy", "quiet"]);
+
+        sort_hosts(&mut hosts, HealthSortKey::Cpu);
+        assert_eq!(hosts[0].name, "busy");
+        assert_eq!(hosts[2].name, "gone");
+
+        let offenders = worst_offenders(&hosts);
+        let cpu = offenders.iter().find(|o| o.metric == "cpu").unwrap();
+        assert_eq!((cpu.name.as_str(), cpu.value), ("busy", 97.0));
+        assert!(offenders.iter().any(|o| o.metric == "unhealthy_containers" && o.detail == "web, worker"));
+
+        let counts = count_statuses(&hosts);
+        assert_eq!((counts.healthy, counts.critical, counts.unreachable), (1, 1, 1));
+    }
+}
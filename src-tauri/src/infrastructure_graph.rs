@@ -1,3 +1,4 @@
+use crate::shell::Cmd;
 use crate::types::*;
 use serde_json::json;
 use std::collections::HashMap;
@@ -12,251 +13,252 @@ impl Default for InfraGraphState {
 }
 
 #[tauri::command]
-pub async fn get_infrastructure_graph(state: State<'_, crate::commands::AppState>) -> Result<InfrastructureGraph, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected to server")?;
-
-    let mut nodes = Vec::new();
-    let mut edges = Vec::new();
-
-    // ============== LAYER 1: INTERNET ==============
-    nodes.push(InfraGraphNode {
-        id: "internet".to_string(),
-        label: "Internet".to_string(),
-        node_type: InfraGraphNodeType::Internet,
-        status: NodeStatus::Healthy,
-        metadata: json!({
-            "description": "External network"
-        }),
-    });
-
-    // ============== LAYER 2: NGINX & HOST PORTS ==============
-    // Get Nginx status
-    let nginx_status_output = client
-        .execute_command("systemctl is-active nginx 2>/dev/null || echo 'inactive'")
-        .unwrap_or_else(|_| "inactive".to_string());
-    
-    let nginx_running = nginx_status_output.trim() == "active";
-    let nginx_version = client
-        .execute_command("nginx -v 2>&1 | cut -d'/' -f2")
-        .unwrap_or_default();
+pub async fn get_infrastructure_graph(server_id: Option<String>, state: State<'_, crate::commands::AppState>) -> Result<InfrastructureGraph, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let mut nodes = Vec::new();
+        let mut edges = Vec::new();
 
-    nodes.push(InfraGraphNode {
-        id: "nginx".to_string(),
-        label: format!("Nginx {}", nginx_version.trim()),
-        node_type: InfraGraphNodeType::Nginx,
-        status: if nginx_running { NodeStatus::Running } else { NodeStatus::Stopped },
-        metadata: json!({
-            "version": nginx_version.trim(),
-            "running": nginx_running
-        }),
-    });
-
-    // Edge: Internet -> Nginx
-    edges.push(InfraGraphEdge {
-        source: "internet".to_string(),
-        target: "nginx".to_string(),
-        edge_type: "routes_to".to_string(),
-        label: Some("80/443".to_string()),
-        metadata: None,
-    });
-
-    // Get host network interface
-    let host_interface = client
-        .execute_command("ip route | grep default | awk '{print $5}' | head -1")
-        .unwrap_or_else(|_| "eth0".to_string())
-        .trim()
-        .to_string();
-
-    nodes.push(InfraGraphNode {
-        id: "host_network".to_string(),
-        label: format!("Host ({})", host_interface),
-        node_type: InfraGraphNodeType::HostNetwork,
-        status: NodeStatus::Running,
-        metadata: json!({
-            "interface": host_interface,
-            "type": "host"
-        }),
-    });
-
-    // Edge: Host Network -> Internet (outbound NAT)
-    edges.push(InfraGraphEdge {
-        source: "host_network".to_string(),
-        target: "internet".to_string(),
-        edge_type: "outbound".to_string(),
-        label: Some("NAT".to_string()),
-        metadata: None,
-    });
-
-    // ============== LAYER 3: VHOSTS & DIRECT PORTS ==============
-    let vhosts = get_vhosts_for_graph(client)?;
-    let mut vhost_to_backend: HashMap<String, String> = HashMap::new();
-
-    for vhost in &vhosts {
-        let vhost_id = format!("vhost:{}", vhost.name);
+        // ============== LAYER 1: INTERNET ==============
         nodes.push(InfraGraphNode {
-            id: vhost_id.clone(),
-            label: vhost.server_name.clone(),
-            node_type: InfraGraphNodeType::Vhost,
-            status: if vhost.enabled { NodeStatus::Healthy } else { NodeStatus::Stopped },
+            id: "internet".to_string(),
+            label: "Internet".to_string(),
+            node_type: InfraGraphNodeType::Internet,
+            status: NodeStatus::Healthy,
             metadata: json!({
-                "name": vhost.name,
-                "server_name": vhost.server_name,
-                "enabled": vhost.enabled,
-                "ssl": vhost.ssl_enabled
+                "description": "External network"
             }),
         });
 
-        // Edge: Nginx -> Vhost
-        edges.push(InfraGraphEdge {
-            source: "nginx".to_string(),
-            target: vhost_id.clone(),
-            edge_type: "serves".to_string(),
-            label: Some(vhost.listen_port.clone()),
-            metadata: None,
-        });
-
-        // Parse proxy_pass
-        if let Ok(backend) = extract_proxy_target(client, &vhost.name).await {
-            vhost_to_backend.insert(vhost_id.clone(), backend.clone());
-        }
-    }
-
-    // ============== LAYER 4: DOCKER CONTAINERS ==============
-    let containers = get_containers_for_graph(client)?;
+        // ============== LAYER 2: NGINX & HOST PORTS ==============
+        // Get Nginx status
+        let nginx_status_output = client
+            .execute_command("systemctl is-active nginx 2>/dev/null || echo 'inactive'")
+            .unwrap_or_else(|_| "inactive".to_string());
     
-    for container in &containers {
-        let container_id = format!("container:{}", container.name);
+        let nginx_running = nginx_status_output.trim() == "active";
+        let nginx_version = client
+            .execute_command("nginx -v 2>&1 | cut -d'/' -f2")
+            .unwrap_or_default();
+
         nodes.push(InfraGraphNode {
-            id: container_id.clone(),
-            label: container.name.clone(),
-            node_type: InfraGraphNodeType::Container,
-            status: if container.state == "running" { NodeStatus::Running } else { NodeStatus::Stopped },
+            id: "nginx".to_string(),
+            label: format!("Nginx {}", nginx_version.trim()),
+            node_type: InfraGraphNodeType::Nginx,
+            status: if nginx_running { NodeStatus::Running } else { NodeStatus::Stopped },
             metadata: json!({
-                "id": container.id,
-                "image": container.image,
-                "state": container.state
+                "version": nginx_version.trim(),
+                "running": nginx_running
             }),
         });
 
-        // Edge: Vhost -> Container (proxy_pass)
-        for (vhost_id, backend) in &vhost_to_backend {
-            if backend.contains(&container.name) || backend.contains(&container.id[..12.min(container.id.len())]) {
-                edges.push(InfraGraphEdge {
-                    source: vhost_id.clone(),
-                    target: container_id.clone(),
-                    edge_type: "proxies_to".to_string(),
-                    label: Some(backend.clone()),
-                    metadata: None,
-                });
-            }
-        }
-    }
+        // Edge: Internet -> Nginx
+        edges.push(InfraGraphEdge {
+            source: "internet".to_string(),
+            target: "nginx".to_string(),
+            edge_type: "routes_to".to_string(),
+            label: Some("80/443".to_string()),
+            metadata: None,
+        });
+
+        // Get host network interface
+        let host_interface = client
+            .execute_command("ip route | grep default | awk '{print $5}' | head -1")
+            .unwrap_or_else(|_| "eth0".to_string())
+            .trim()
+            .to_string();
 
-    // ============== LAYER 5: DOCKER NETWORKS ==============
-    let networks = get_docker_networks_for_graph(client)?;
-    
-    for network in &networks {
-        let network_id = format!("network:{}", network.name);
         nodes.push(InfraGraphNode {
-            id: network_id.clone(),
-            label: format!("{} ({})", network.name, network.driver),
-            node_type: InfraGraphNodeType::DockerNetwork,
-            status: NodeStatus::Healthy,
+            id: "host_network".to_string(),
+            label: format!("Host ({})", host_interface),
+            node_type: InfraGraphNodeType::HostNetwork,
+            status: NodeStatus::Running,
             metadata: json!({
-                "driver": network.driver,
-                "scope": network.scope,
-                "subnet": network.subnet,
-                "containers": network.containers.len()
+                "interface": host_interface,
+                "type": "host"
             }),
         });
 
-        // Edge: Docker Network -> Host Network (NAT)
+        // Edge: Host Network -> Internet (outbound NAT)
         edges.push(InfraGraphEdge {
-            source: network_id.clone(),
-            target: "host_network".to_string(),
-            edge_type: "nat".to_string(),
-            label: Some("masquerade".to_string()),
+            source: "host_network".to_string(),
+            target: "internet".to_string(),
+            edge_type: "outbound".to_string(),
+            label: Some("NAT".to_string()),
             metadata: None,
         });
 
-        // Edge: Container -> Docker Network
-        for container in &containers {
-            let container_short_id = &container.id[..12.min(container.id.len())];
-            if network.containers.contains(&container.name) || 
-               network.containers.iter().any(|c| c.starts_with(container_short_id)) {
-                edges.push(InfraGraphEdge {
-                    source: format!("container:{}", container.name),
-                    target: network_id.clone(),
-                    edge_type: "connected_to".to_string(),
-                    label: None,
-                    metadata: None,
-                });
-            }
-        }
-    }
+        // ============== LAYER 3: VHOSTS & DIRECT PORTS ==============
+        let vhosts = get_vhosts_for_graph(client)?;
+        let mut vhost_to_backend: HashMap<String, String> = HashMap::new();
 
-    // ============== DIRECT PORT MAPPINGS ==============
-    for container in &containers {
-        let ports = get_container_ports(client, &container.name).await;
-        
-        for port_mapping in ports {
-            let host_port = &port_mapping.host_port;
-            let container_port = &port_mapping.container_port;
-            
-            // Create HostPort node
-            let host_port_id = format!("hostport:{}", host_port);
+        for vhost in &vhosts {
+            let vhost_id = format!("vhost:{}", vhost.name);
             nodes.push(InfraGraphNode {
-                id: host_port_id.clone(),
-                label: format!("Port :{}", host_port),
-                node_type: InfraGraphNodeType::HostPort,
-                status: NodeStatus::Running,
+                id: vhost_id.clone(),
+                label: vhost.server_name.clone(),
+                node_type: InfraGraphNodeType::Vhost,
+                status: if vhost.enabled { NodeStatus::Healthy } else { NodeStatus::Stopped },
                 metadata: json!({
-                    "host_port": host_port,
-                    "container_port": container_port,
-                    "protocol": port_mapping.protocol
+                    "name": vhost.name,
+                    "server_name": vhost.server_name,
+                    "enabled": vhost.enabled,
+                    "ssl": vhost.ssl_enabled
                 }),
             });
 
-            // Edge: Internet -> HostPort (direct access)
+            // Edge: Nginx -> Vhost
             edges.push(InfraGraphEdge {
-                source: "internet".to_string(),
-                target: host_port_id.clone(),
-                edge_type: "direct_access".to_string(),
-                label: Some(format!(":{}", host_port)),
+                source: "nginx".to_string(),
+                target: vhost_id.clone(),
+                edge_type: "serves".to_string(),
+                label: Some(vhost.listen_port.clone()),
                 metadata: None,
             });
 
-            // Edge: HostPort -> Container
+            // Parse proxy_pass
+            if let Ok(backend) = extract_proxy_target(client, &vhost.name) {
+                vhost_to_backend.insert(vhost_id.clone(), backend.clone());
+            }
+        }
+
+        // ============== LAYER 4: DOCKER CONTAINERS ==============
+        let containers = get_containers_for_graph(client)?;
+    
+        for container in &containers {
+            let container_id = format!("container:{}", container.name);
+            nodes.push(InfraGraphNode {
+                id: container_id.clone(),
+                label: container.name.clone(),
+                node_type: InfraGraphNodeType::Container,
+                status: if container.state == "running" { NodeStatus::Running } else { NodeStatus::Stopped },
+                metadata: json!({
+                    "id": container.id,
+                    "image": container.image,
+                    "state": container.state
+                }),
+            });
+
+            // Edge: Vhost -> Container (proxy_pass)
+            for (vhost_id, backend) in &vhost_to_backend {
+                if backend.contains(&container.name) || backend.contains(&container.id[..12.min(container.id.len())]) {
+                    edges.push(InfraGraphEdge {
+                        source: vhost_id.clone(),
+                        target: container_id.clone(),
+                        edge_type: "proxies_to".to_string(),
+                        label: Some(backend.clone()),
+                        metadata: None,
+                    });
+                }
+            }
+        }
+
+        // ============== LAYER 5: DOCKER NETWORKS ==============
+        let networks = get_docker_networks_for_graph(client)?;
+    
+        for network in &networks {
+            let network_id = format!("network:{}", network.name);
+            nodes.push(InfraGraphNode {
+                id: network_id.clone(),
+                label: format!("{} ({})", network.name, network.driver),
+                node_type: InfraGraphNodeType::DockerNetwork,
+                status: NodeStatus::Healthy,
+                metadata: json!({
+                    "driver": network.driver,
+                    "scope": network.scope,
+                    "subnet": network.subnet,
+                    "containers": network.containers.len()
+                }),
+            });
+
+            // Edge: Docker Network -> Host Network (NAT)
             edges.push(InfraGraphEdge {
-                source: host_port_id.clone(),
-                target: format!("container:{}", container.name),
-                edge_type: "port_mapping".to_string(),
-                label: Some(format!("→ :{}", container_port)),
+                source: network_id.clone(),
+                target: "host_network".to_string(),
+                edge_type: "nat".to_string(),
+                label: Some("masquerade".to_string()),
                 metadata: None,
             });
+
+            // Edge: Container -> Docker Network
+            for container in &containers {
+                let container_short_id = &container.id[..12.min(container.id.len())];
+                if network.containers.contains(&container.name) || 
+                   network.containers.iter().any(|c| c.starts_with(container_short_id)) {
+                    edges.push(InfraGraphEdge {
+                        source: format!("container:{}", container.name),
+                        target: network_id.clone(),
+                        edge_type: "connected_to".to_string(),
+                        label: None,
+                        metadata: None,
+                    });
+                }
+            }
+        }
+
+        // ============== DIRECT PORT MAPPINGS ==============
+        for container in &containers {
+            let ports = get_container_ports(client, &container.name);
+        
+            for port_mapping in ports {
+                let host_port = &port_mapping.host_port;
+                let container_port = &port_mapping.container_port;
+            
+                // Create HostPort node
+                let host_port_id = format!("hostport:{}", host_port);
+                nodes.push(InfraGraphNode {
+                    id: host_port_id.clone(),
+                    label: format!("Port :{}", host_port),
+                    node_type: InfraGraphNodeType::HostPort,
+                    status: NodeStatus::Running,
+                    metadata: json!({
+                        "host_port": host_port,
+                        "container_port": container_port,
+                        "protocol": port_mapping.protocol
+                    }),
+                });
+
+                // Edge: Internet -> HostPort (direct access)
+                edges.push(InfraGraphEdge {
+                    source: "internet".to_string(),
+                    target: host_port_id.clone(),
+                    edge_type: "direct_access".to_string(),
+                    label: Some(format!(":{}", host_port)),
+                    metadata: None,
+                });
+
+                // Edge: HostPort -> Container
+                edges.push(InfraGraphEdge {
+                    source: host_port_id.clone(),
+                    target: format!("container:{}", container.name),
+                    edge_type: "port_mapping".to_string(),
+                    label: Some(format!("→ :{}", container_port)),
+                    metadata: None,
+                });
+            }
         }
-    }
 
-    // Calculate summary
-    let summary = InfraSummary {
-        total_containers: containers.len(),
-        running_containers: containers.iter().filter(|c| c.state == "running").count(),
-        total_vhosts: vhosts.len(),
-        enabled_vhosts: vhosts.iter().filter(|v| v.enabled).count(),
-        nginx_status: if nginx_running { "running".to_string() } else { "stopped".to_string() },
-        total_volumes: 0,
-        total_networks: networks.len(),
-    };
-
-    Ok(InfrastructureGraph { nodes, edges, summary })
+        // Calculate summary
+        let summary = InfraSummary {
+            total_containers: containers.len(),
+            running_containers: containers.iter().filter(|c| c.state == "running").count(),
+            total_vhosts: vhosts.len(),
+            enabled_vhosts: vhosts.iter().filter(|v| v.enabled).count(),
+            nginx_status: if nginx_running { "running".to_string() } else { "stopped".to_string() },
+            total_volumes: 0,
+            total_networks: networks.len(),
+        };
+
+        Ok(InfrastructureGraph { nodes, edges, summary })
+    })
+    .await
 }
 
-async fn get_container_ports(client: &std::sync::Arc<crate::ssh::SshClient>, container_name: &str) -> Vec<PortMapping> {
+fn get_container_ports(client: &crate::ssh::SshClient, container_name: &str) -> Vec<PortMapping> {
     let mut ports = Vec::new();
     
     let output = client
-        .execute_command(&format!("docker port {}", container_name))
+        .execute_command(&Cmd::new("docker").raw("port").arg(container_name))
         .unwrap_or_default();
     
     for line in output.lines() {
@@ -286,7 +288,7 @@ async fn get_container_ports(client: &std::sync::Arc<crate::ssh::SshClient>, con
     ports
 }
 
-fn get_vhosts_for_graph(client: &std::sync::Arc<crate::ssh::SshClient>) -> Result<Vec<NginxVhost>, String> {
+fn get_vhosts_for_graph(client: &crate::ssh::SshClient) -> Result<Vec<NginxVhost>, String> {
     let mut vhosts = Vec::new();
 
     let available = client
@@ -304,7 +306,7 @@ fn get_vhosts_for_graph(client: &std::sync::Arc<crate::ssh::SshClient>) -> Resul
         }
 
         let content = client
-            .execute_command(&format!("cat /etc/nginx/sites-available/{}", name))
+            .execute_command(&Cmd::new("cat").arg(format!("/etc/nginx/sites-available/{}", name)))
             .unwrap_or_default();
 
         let server_name = extract_server_name(&content).unwrap_or_else(|| name.to_string());
@@ -325,7 +327,7 @@ fn get_vhosts_for_graph(client: &std::sync::Arc<crate::ssh::SshClient>) -> Resul
     Ok(vhosts)
 }
 
-fn get_containers_for_graph(client: &std::sync::Arc<crate::ssh::SshClient>) -> Result<Vec<DockerContainer>, String> {
+fn get_containers_for_graph(client: &crate::ssh::SshClient) -> Result<Vec<DockerContainer>, String> {
     let ps_output = client
         .execute_command("docker ps --format '{{.ID}}|{{.Names}}|{{.Image}}|{{.State}}' --no-trunc")
         .map_err(|e| e.message)?;
@@ -351,7 +353,7 @@ fn get_containers_for_graph(client: &std::sync::Arc<crate::ssh::SshClient>) -> R
     Ok(containers)
 }
 
-fn get_docker_networks_for_graph(client: &std::sync::Arc<crate::ssh::SshClient>) -> Result<Vec<DockerNetwork>, String> {
+fn get_docker_networks_for_graph(client: &crate::ssh::SshClient) -> Result<Vec<DockerNetwork>, String> {
     let output = client
         .execute_command("docker network ls --format '{{.ID}}|{{.Name}}|{{.Driver}}|{{.Scope}}'")
         .map_err(|e| e.message)?;
@@ -370,7 +372,13 @@ fn get_docker_networks_for_graph(client: &std::sync::Arc<crate::ssh::SshClient>)
             
             // Get containers in this network
             let containers_output = client
-                .execute_command(&format!("docker network inspect {} --format '{{{{range .Containers}}}}{{.Name}},{{end}}'", network_id))
+                .execute_command(
+                    &Cmd::new("docker")
+                        .raw("network inspect")
+                        .arg(&network_id)
+                        .raw("--format")
+                        .arg("{{range .Containers}}{{.Name}},{{end}}"),
+                )
                 .unwrap_or_default();
             
             let containers: Vec<String> = containers_output
@@ -382,7 +390,13 @@ fn get_docker_networks_for_graph(client: &std::sync::Arc<crate::ssh::SshClient>)
 
             // Get subnet
             let subnet_output = client
-                .execute_command(&format!("docker network inspect {} --format '{{{{(index .IPAM.Config 0).Subnet}}}}'", network_id))
+                .execute_command(
+                    &Cmd::new("docker")
+                        .raw("network inspect")
+                        .arg(&network_id)
+                        .raw("--format")
+                        .arg("{{(index .IPAM.Config 0).Subnet}}"),
+                )
                 .unwrap_or_default();
             
             let subnet = if subnet_output.trim().is_empty() { None } else { Some(subnet_output.trim().to_string()) };
@@ -402,9 +416,9 @@ fn get_docker_networks_for_graph(client: &std::sync::Arc<crate::ssh::SshClient>)
     Ok(networks)
 }
 
-async fn extract_proxy_target(client: &std::sync::Arc<crate::ssh::SshClient>, vhost_name: &str) -> Result<String, String> {
+fn extract_proxy_target(client: &crate::ssh::SshClient, vhost_name: &str) -> Result<String, String> {
     let content = client
-        .execute_command(&format!("cat /etc/nginx/sites-available/{}", vhost_name))
+        .execute_command(&Cmd::new("cat").arg(format!("/etc/nginx/sites-available/{}", vhost_name)))
         .map_err(|e| e.message)?;
 
     for line in content.lines() {
@@ -0,0 +1,343 @@
+use crate::commands::{profiles_from_json, profiles_to_json, AppState, PROFILES_KEY, STORE_FILENAME};
+use crate::remote_file::{self, WriteOptions};
+use crate::shell::{self, Cmd};
+use crate::ssh::{CommandCategory, SshClient};
+use crate::types::*;
+use crate::user_commands::install_authorized_key;
+use crate::vault::VaultState;
+use chacha20poly1305::aead::OsRng;
+use serde::{Deserialize, Serialize};
+use ssh_key::{Algorithm, HashAlg, LineEnding, PrivateKey};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::State;
+use tauri_plugin_store::StoreExt;
+use zeroize::Zeroizing;
+
+const SSHD_CONFIG: &str = "/etc/ssh/sshd_config";
+const SSHD_CONFIG_BACKUP: &str = "/etc/ssh/sshd_config.dpanel-bak";
+const DISABLED_MARKER: &str = "# Password logins disabled by DPanel after verifying key login";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeneratedKey {
+    pub private_key_path: String,
+    pub public_key: String,
+    pub fingerprint: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KeyDeployResult {
+    /// The public key was added to authorized_keys (false if it was already there)
+    pub installed: bool,
+    /// A fresh login with the key succeeded
+    pub verified: bool,
+    /// The saved profile now logs in with the key
+    pub profile_updated: bool,
+    pub password_auth_disabled: bool,
+    /// sshd_config was restored because key login stopped working after the change
+    pub rolled_back: bool,
+    pub message: String,
+}
+
+fn ssh_dir() -> Result<PathBuf, String> {
+    dirs::home_dir()
+        .map(|home| home.join(".ssh"))
+        .ok_or_else(|| "Could not determine the home directory".to_string())
+}
+
+/// Generate an ed25519 keypair in ~/.ssh as `file_name` and `file_name.pub`.
+/// An existing key is never overwritten.
+#[tauri::command]
+pub async fn generate_ssh_key(
+    file_name: String,
+    comment: Option<String>,
+    passphrase: Option<String>,
+) -> Result<GeneratedKey, String> {
+    let passphrase = Zeroizing::new(passphrase.unwrap_or_default());
+    let dir = ssh_dir()?;
+    let private_path = dir.join(shell::file_name(&file_name)?);
+    let public_path = dir.join(format!("{}.pub", file_name));
+    if private_path.exists() || public_path.exists() {
+        return Err(format!("{} already exists", private_path.display()));
+    }
+
+    // Encrypting with a passphrase runs bcrypt-pbkdf, which takes a moment
+    tokio::task::spawn_blocking(move || {
+        let mut key = PrivateKey::random(&mut OsRng, Algorithm::Ed25519)
+            .map_err(|e| format!("Key generation failed: {}", e))?;
+        key.set_comment(comment.unwrap_or_else(|| format!("dpanel-{}", file_name)));
+        let public_key = key.public_key().to_openssh().map_err(|e| format!("Failed to encode public key: {}", e))?;
+        let fingerprint = key.fingerprint(HashAlg::Sha256).to_string();
+        if !passphrase.is_empty() {
+            key = key
+                .encrypt(&mut OsRng, passphrase.as_bytes())
+                .map_err(|e| format!("Failed to encrypt key: {}", e))?;
+        }
+
+        create_ssh_dir(&dir)?;
+        key.write_openssh_file(&private_path, LineEnding::LF)
+            .map_err(|e| format!("Failed to write {}: {}", private_path.display(), e))?;
+        fs::write(&public_path, format!("{}\n", public_key))
+            .map_err(|e| format!("Failed to write {}: {}", public_path.display(), e))?;
+
+        Ok(GeneratedKey {
+            private_key_path: private_path.to_string_lossy().into_owned(),
+            public_key,
+            fingerprint,
+        })
+    })
+    .await
+    .map_err(|e| format!("Key generation task failed: {}", e))?
+}
+
+fn create_ssh_dir(dir: &Path) -> Result<(), String> {
+    if dir.exists() {
+        return Ok(());
+    }
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))
+            .map_err(|e| format!("Failed to set permissions on {}: {}", dir.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Log in again from scratch with only the new key, the way the next connect will
+fn verify_key_login(profile: &ServerProfile, key_auth: &AuthMethod) -> Result<(), String> {
+    let mut profile = profile.clone();
+    profile.auth_method = key_auth.clone();
+    let client = SshClient::new(profile);
+    let result = client.connect().map_err(|e| e.message);
+    client.disconnect();
+    result
+}
+
+/// Put `PasswordAuthentication no` first in the file. sshd keeps the first value it
+/// reads, so this wins over later lines and over Include'd drop-ins; global lines that
+/// said otherwise are commented out so the file doesn't contradict itself.
+fn without_password_auth(config: &str) -> String {
+    let header = format!("{}\nPasswordAuthentication no\n", DISABLED_MARKER);
+    let mut out = header.clone();
+    let mut in_match = false;
+    for line in config.strip_prefix(&header).unwrap_or(config).lines() {
+        let keyword = line.split_whitespace().next().unwrap_or("").to_ascii_lowercase();
+        in_match |= keyword == "match";
+        if !in_match && keyword == "passwordauthentication" {
+            out.push_str(&format!("#{}\n", line));
+            continue;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+fn reload_sshd(client: &SshClient) -> Result<(), String> {
+    // Debian names the unit ssh, most others sshd
+    let reload = Cmd::sudo("systemctl").raw("reload sshd 2>/dev/null").or(Cmd::sudo("systemctl").raw("reload ssh"));
+    client.execute_checked(&reload, CommandCategory::Action).map(|_| ()).map_err(|e| e.message)
+}
+
+fn restore_sshd_config(client: &SshClient) -> Result<(), String> {
+    let restore = Cmd::sudo("cp").raw("-p").arg(SSHD_CONFIG_BACKUP).arg(SSHD_CONFIG);
+    client.execute_checked(&restore, CommandCategory::Action).map_err(|e| e.message)?;
+    reload_sshd(client)
+}
+
+/// Turn off password logins, then make sure the key still gets in; if it doesn't, the
+/// previous sshd_config is put back. Returns whether it had to roll back.
+fn disable_password_auth_with_rollback(client: &SshClient, key_auth: &AuthMethod) -> Result<bool, String> {
+    let backup = Cmd::sudo("cp").raw("-p").arg(SSHD_CONFIG).arg(SSHD_CONFIG_BACKUP);
+    client.execute_checked(&backup, CommandCategory::Action).map_err(|e| e.message)?;
+    let current = client.execute_checked(&Cmd::sudo("cat").arg(SSHD_CONFIG), CommandCategory::Query).map_err(|e| e.message)?;
+
+    let updated = without_password_auth(&current);
+    // Once the file is touched, any failure puts the backup back
+    let applied = remote_file::write_file(client, SSHD_CONFIG, updated.as_bytes(), WriteOptions::root(0o644))
+        .map_err(|e| format!("Failed to write {}: {}", SSHD_CONFIG, e.message))
+        .and_then(|_| {
+            client
+                .execute_checked(&Cmd::sudo("sshd").raw("-t"), CommandCategory::Query)
+                .map_err(|e| format!("sshd rejected the new configuration: {}", e.message))
+        })
+        .and_then(|_| reload_sshd(client));
+    if let Err(e) = applied {
+        return Err(match restore_sshd_config(client) {
+            Ok(()) => format!("{}; the previous configuration was restored", e),
+            Err(restore) => format!("{}; restoring the previous configuration also failed: {}", e, restore),
+        });
+    }
+
+    if let Err(e) = verify_key_login(client.profile(), key_auth) {
+        log::warn!("Key login failed after disabling passwords, rolling back: {}", e);
+        restore_sshd_config(client).map_err(|restore| {
+            format!(
+                "The key stopped working once password logins were disabled ({}), and restoring the previous configuration failed: {}. The backup is at {}",
+                e, restore, SSHD_CONFIG_BACKUP
+            )
+        })?;
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// Install a key generated by `generate_ssh_key` for the connected user, check that it
+/// logs in, and switch the saved profile over to it. With `disable_password_auth`,
+/// password logins are then turned off in sshd_config, rolling back if the key stops working.
+#[tauri::command]
+pub async fn deploy_ssh_key(
+    private_key_path: String,
+    passphrase: Option<String>,
+    disable_password_auth: bool,
+    server_id: Option<String>,
+    state: State<'_, AppState>,
+    vault: State<'_, VaultState>,
+    app: tauri::AppHandle,
+) -> Result<KeyDeployResult, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    let passphrase = passphrase.filter(|p| !p.is_empty());
+    // The passphrase will be sealed into the vault; find out now rather than after sshd was changed
+    if passphrase.is_some() {
+        vault.ensure_unlocked(&app)?;
+    }
+    let public_path = format!("{}.pub", private_key_path);
+    let public_key = fs::read_to_string(&public_path).map_err(|e| format!("Failed to read {}: {}", public_path, e))?;
+    let key_auth = AuthMethod::PrivateKey { key_path: private_key_path, passphrase };
+
+    let deploying = Arc::clone(&client);
+    let auth = key_auth.clone();
+    let mut result = tokio::task::spawn_blocking(move || -> Result<KeyDeployResult, String> {
+        let mut result = KeyDeployResult {
+            installed: install_authorized_key(&deploying, &deploying.profile().username, &public_key)?,
+            ..Default::default()
+        };
+        if let Err(e) = verify_key_login(deploying.profile(), &auth) {
+            result.message = format!("The key was installed but logging in with it failed: {}", e);
+            return Ok(result);
+        }
+        result.verified = true;
+        // The key works by now, so a failure here shouldn't keep the profile from using it
+        if disable_password_auth {
+            match disable_password_auth_with_rollback(&deploying, &auth) {
+                Ok(rolled_back) => {
+                    result.rolled_back = rolled_back;
+                    result.password_auth_disabled = !rolled_back;
+                }
+                Err(e) => result.message = format!("password logins could not be disabled: {}", e),
+            }
+        }
+        Ok(result)
+    })
+    .await
+    .map_err(|e| format!("Key deployment task failed: {}", e))??;
+
+    if !result.verified {
+        return Ok(result);
+    }
+
+    let sshd_error = std::mem::take(&mut result.message);
+    let store = app.store(STORE_FILENAME).map_err(|e| format!("Failed to open profile store: {}", e))?;
+    let mut profiles_map = profiles_from_json(store.get(PROFILES_KEY));
+    let mut kept_password = Ok(());
+    if let Some(saved) = profiles_map.get_mut(client.profile_id()) {
+        kept_password = keep_sudo_password(saved);
+        if kept_password.is_ok() {
+            saved.auth_method = key_auth;
+            vault.seal(&app, saved)?;
+            state
+                .server_profiles
+                .lock()
+                .await
+                .insert(saved.id.clone(), ServerProfile::from(saved.clone()));
+            store.set(PROFILES_KEY, profiles_to_json(&profiles_map));
+            store.save().map_err(|e| format!("Failed to save profiles: {}", e))?;
+            result.profile_updated = true;
+        }
+    }
+
+    result.message = if result.rolled_back {
+        "Key login works, but it failed once passwords were disabled, so sshd_config was restored".to_string()
+    } else if result.password_auth_disabled {
+        "Key login verified and password logins disabled".to_string()
+    } else if !sshd_error.is_empty() {
+        format!("Key login verified, but {}", sshd_error)
+    } else {
+        "Key login verified".to_string()
+    };
+    if let Err(e) = kept_password {
+        result.message = format!("{}. The profile still logs in with its password: {}", result.message, e);
+    }
+    Ok(result)
+}
+
+/// sudo falls back to the login password when the profile has none of its own, so
+/// keep that password for sudo before the profile stops logging in with it
+fn keep_sudo_password(saved: &mut SavedServerProfile) -> Result<(), String> {
+    let Escalation::SudoPassword { password: sudo_password @ None } = &mut saved.escalation else {
+        return Ok(());
+    };
+    match &saved.auth_method {
+        // A vault reference works as well as the password itself; the entry stays while referenced
+        AuthMethod::Password { password } if !password.is_empty() => {
+            *sudo_password = Some(password.clone());
+            Ok(())
+        }
+        AuthMethod::Password { .. } => {
+            Err("sudo uses the login password, which isn't saved, so switching to the key would leave sudo without one".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_password_auth_goes_first_and_match_blocks_are_kept() {
+        let config = "Include /etc/ssh/sshd_config.d/*.conf\nPasswordAuthentication yes\nUsePAM yes\nMatch User backup\n    PasswordAuthentication yes\n";
+        let updated = without_password_auth(config);
+        assert_eq!(
+            updated,
+            format!(
+                "{}\nPasswordAuthentication no\nInclude /etc/ssh/sshd_config.d/*.conf\n#PasswordAuthentication yes\nUsePAM yes\nMatch User backup\n    PasswordAuthentication yes\n",
+                DISABLED_MARKER
+            )
+        );
+        // Running it twice doesn't stack markers
+        assert_eq!(without_password_auth(&updated), updated);
+    }
+
+    #[test]
+    fn test_keep_sudo_password() {
+        let mut saved = SavedServerProfile {
+            id: "web".to_string(),
+            name: "web".to_string(),
+            host: "web.example.com".to_string(),
+            port: 22,
+            username: "deploy".to_string(),
+            auth_method: AuthMethod::Password { password: "vault:web/auth-0001".to_string() },
+            jump_hosts: Vec::new(),
+            escalation: Escalation::SudoPassword { password: None },
+            created_at: 0,
+            last_connected: None,
+            connect_on_startup: false,
+            labels: ProfileLabels::default(),
+        };
+        keep_sudo_password(&mut saved).unwrap();
+        assert_eq!(saved.escalation, Escalation::SudoPassword { password: Some("vault:web/auth-0001".to_string()) });
+
+        // A sudo password of its own is left alone
+        saved.auth_method = AuthMethod::Password { password: "other".to_string() };
+        keep_sudo_password(&mut saved).unwrap();
+        assert_eq!(saved.escalation, Escalation::SudoPassword { password: Some("vault:web/auth-0001".to_string()) });
+
+        saved.escalation = Escalation::SudoPassword { password: None };
+        saved.auth_method = AuthMethod::Password { password: String::new() };
+        assert!(keep_sudo_password(&mut saved).is_err());
+        assert_eq!(saved.escalation, Escalation::SudoPassword { password: None });
+    }
+}
@@ -0,0 +1,267 @@
+use crate::types::*;
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use ssh2::{CheckResult, HashType, HostKeyType, KnownHostFileKind, KnownHosts, Session};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Known-hosts file managed by DPanel, used for keys trusted from the UI
+pub fn app_known_hosts_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("dpanel")
+        .join("known_hosts")
+}
+
+/// The user's OpenSSH known_hosts file
+fn user_known_hosts_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".ssh").join("known_hosts"))
+}
+
+/// Host pattern as written in known_hosts files (`[host]:port` for non-default ports)
+fn host_pattern(host: &str, port: u16) -> String {
+    if port == 22 {
+        host.to_string()
+    } else {
+        format!("[{}]:{}", host, port)
+    }
+}
+
+fn key_type_name(key_type: HostKeyType) -> &'static str {
+    match key_type {
+        HostKeyType::Rsa => "ssh-rsa",
+        HostKeyType::Dss => "ssh-dss",
+        HostKeyType::Ecdsa256 => "ecdsa-sha2-nistp256",
+        HostKeyType::Ecdsa384 => "ecdsa-sha2-nistp384",
+        HostKeyType::Ecdsa521 => "ecdsa-sha2-nistp521",
+        HostKeyType::Ed25519 => "ssh-ed25519",
+        HostKeyType::Unknown => "unknown",
+    }
+}
+
+/// Describe the host key presented during the handshake, with an OpenSSH-style SHA256 fingerprint
+pub fn host_key_info(session: &Session, host: &str, port: u16) -> Result<HostKeyInfo, CommandError> {
+    let (_, key_type) = session.host_key().ok_or_else(|| CommandError {
+        message: "Server did not present a host key".to_string(),
+        code: -1,
+    })?;
+    let hash = session.host_key_hash(HashType::Sha256).ok_or_else(|| CommandError {
+        message: "Failed to compute host key fingerprint".to_string(),
+        code: -1,
+    })?;
+
+    Ok(HostKeyInfo {
+        host: host.to_string(),
+        port,
+        key_type: key_type_name(key_type).to_string(),
+        fingerprint: format!("SHA256:{}", STANDARD_NO_PAD.encode(hash)),
+        status: HostKeyStatus::Unknown,
+    })
+}
+
+fn load_known_hosts(session: &Session, path: &Path) -> Result<Option<KnownHosts>, CommandError> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let mut known_hosts = session.known_hosts().map_err(|e| CommandError {
+        message: format!("Failed to initialise known hosts: {}", e),
+        code: -1,
+    })?;
+
+    match known_hosts.read_file(path, KnownHostFileKind::OpenSSH) {
+        Ok(_) => Ok(Some(known_hosts)),
+        Err(e) => {
+            log::warn!("Failed to read known hosts file {}: {}", path.display(), e);
+            Ok(None)
+        }
+    }
+}
+
+/// Check the session's host key against ~/.ssh/known_hosts and the app-managed known_hosts file.
+///
+/// A key recorded in either file is `Trusted`; a key that differs from a recorded one is
+/// `Changed`. Anything else is `Unknown` until the user trusts it.
+pub fn verify_host_key(session: &Session, host: &str, port: u16) -> Result<HostKeyInfo, CommandError> {
+    let mut info = host_key_info(session, host, port)?;
+    let (key, _) = session.host_key().ok_or_else(|| CommandError {
+        message: "Server did not present a host key".to_string(),
+        code: -1,
+    })?;
+
+    let files = user_known_hosts_path()
+        .into_iter()
+        .chain(std::iter::once(app_known_hosts_path()));
+    info.status = check_known_hosts(session, files, host, port, key)?;
+    Ok(info)
+}
+
+fn check_known_hosts(
+    session: &Session,
+    files: impl IntoIterator<Item = PathBuf>,
+    host: &str,
+    port: u16,
+    key: &[u8],
+) -> Result<HostKeyStatus, CommandError> {
+    let mut changed = false;
+    for path in files {
+        let Some(known_hosts) = load_known_hosts(session, &path)? else {
+            continue;
+        };
+
+        match known_hosts.check_port(host, port, key) {
+            CheckResult::Match => return Ok(HostKeyStatus::Trusted),
+            CheckResult::Mismatch => changed = true,
+            CheckResult::NotFound | CheckResult::Failure => {}
+        }
+    }
+
+    Ok(if changed { HostKeyStatus::Changed } else { HostKeyStatus::Unknown })
+}
+
+/// Record the session's host key in the app-managed known_hosts file
+pub fn add_host_key(session: &Session, host: &str, port: u16) -> Result<(), CommandError> {
+    let (key, key_type) = session.host_key().ok_or_else(|| CommandError {
+        message: "Server did not present a host key".to_string(),
+        code: -1,
+    })?;
+
+    let path = app_known_hosts_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| CommandError {
+            message: format!("Failed to create {}: {}", parent.display(), e),
+            code: -1,
+        })?;
+    }
+
+    let mut known_hosts = match load_known_hosts(session, &path)? {
+        Some(known_hosts) => known_hosts,
+        None => session.known_hosts().map_err(|e| CommandError {
+            message: format!("Failed to initialise known hosts: {}", e),
+            code: -1,
+        })?,
+    };
+
+    known_hosts
+        .add(&host_pattern(host, port), key, "added by DPanel", key_type.into())
+        .map_err(|e| CommandError {
+            message: format!("Failed to add host key: {}", e),
+            code: -1,
+        })?;
+
+    known_hosts
+        .write_file(&path, KnownHostFileKind::OpenSSH)
+        .map_err(|e| CommandError {
+            message: format!("Failed to write {}: {}", path.display(), e),
+            code: -1,
+        })
+}
+
+/// Drop every entry for `host:port` from the app-managed known_hosts file.
+/// Entries in ~/.ssh/known_hosts are left for the user to manage.
+///
+/// Only plain entries are matched. Hashed ones (`|1|salt|hash`, as written with
+/// `HashKnownHosts`) would need HMAC-SHA1 of the host and are left in place; DPanel
+/// writes plain entries itself, so these only appear when the file is edited by hand.
+pub fn remove_host_key(host: &str, port: u16) -> Result<bool, String> {
+    remove_host_key_from(&app_known_hosts_path(), host, port)
+}
+
+fn remove_host_key_from(path: &Path, host: &str, port: u16) -> Result<bool, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(_) => return Ok(false),
+    };
+
+    let pattern = host_pattern(host, port);
+    let mut removed = false;
+    let kept: Vec<&str> = content
+        .lines()
+        .filter(|line| {
+            let matches = line
+                .split_whitespace()
+                .next()
+                .is_some_and(|hosts| hosts.split(',').any(|h| h == pattern));
+            removed |= matches;
+            !matches
+        })
+        .collect();
+
+    if removed {
+        let mut new_content = kept.join("\n");
+        new_content.push('\n');
+        fs::write(path, new_content)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An OpenSSH ed25519 public key blob with the given key bytes
+    fn ed25519_blob(fill: u8) -> Vec<u8> {
+        let mut blob = Vec::new();
+        for part in [&b"ssh-ed25519"[..], &[fill; 32][..]] {
+            blob.extend_from_slice(&(part.len() as u32).to_be_bytes());
+            blob.extend_from_slice(part);
+        }
+        blob
+    }
+
+    fn temp_file(name: &str, content: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("dpanel-known-hosts-{}-{}", std::process::id(), name));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_host_pattern() {
+        assert_eq!(host_pattern("example.com", 22), "example.com");
+        assert_eq!(host_pattern("example.com", 2222), "[example.com]:2222");
+        assert_eq!(host_pattern("10.0.0.5", 2200), "[10.0.0.5]:2200");
+    }
+
+    #[test]
+    fn test_remove_host_key() {
+        let path = temp_file(
+            "remove",
+            "example.com ssh-ed25519 AAAA\n\
+             [example.com]:2222,10.0.0.5 ssh-ed25519 BBBB\n\
+             |1|c2FsdA==|aGFzaA== ssh-ed25519 CCCC\n\
+             other.com ssh-ed25519 DDDD\n",
+        );
+
+        assert!(remove_host_key_from(&path, "example.com", 2222).unwrap());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "example.com ssh-ed25519 AAAA\n|1|c2FsdA==|aGFzaA== ssh-ed25519 CCCC\nother.com ssh-ed25519 DDDD\n"
+        );
+        // The default port entry is its own pattern, and nothing is left for 2222
+        assert!(!remove_host_key_from(&path, "example.com", 2222).unwrap());
+        assert!(remove_host_key_from(&path, "example.com", 22).unwrap());
+        assert!(!remove_host_key_from(&path.with_extension("missing"), "example.com", 22).unwrap());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_check_known_hosts_reports_changed_key() {
+        let session = Session::new().unwrap();
+        let trusted = ed25519_blob(1);
+        let line = |host: &str| format!("{} ssh-ed25519 {}\n", host, base64::engine::general_purpose::STANDARD.encode(&trusted));
+        let user = temp_file("user", &line("[example.com]:2222"));
+        let app = temp_file("app", &line("other.com"));
+        let files = || [user.with_extension("missing"), user.clone(), app.clone()];
+
+        let status = |host, port, key: &[u8]| check_known_hosts(&session, files(), host, port, key).unwrap();
+        assert_eq!(status("example.com", 2222, &trusted), HostKeyStatus::Trusted);
+        assert_eq!(status("example.com", 2222, &ed25519_blob(2)), HostKeyStatus::Changed);
+        // Port 22 has no entry of its own
+        assert_eq!(status("example.com", 22, &trusted), HostKeyStatus::Unknown);
+        assert_eq!(status("other.com", 22, &ed25519_blob(2)), HostKeyStatus::Changed);
+
+        fs::remove_file(user).unwrap();
+        fs::remove_file(app).unwrap();
+    }
+}
@@ -1,28 +1,86 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod auth_prompt;
 mod commands;
 mod compose_discovery;
+mod connection_monitor;
+mod fleet;
+mod fleet_overview;
 mod infrastructure_graph;
+mod key_deploy;
+mod known_hosts;
+mod metrics;
+mod metrics_poller;
+mod metrics_store;
+mod privilege;
+mod profile_bundle;
+mod profile_query;
+mod remote_file;
+mod session_pool;
+mod sftp;
+mod shell;
 mod ssh;
+mod ssh_config;
+mod terminal;
+mod transfer;
+mod tunnel;
 mod types;
 mod user_commands;
+mod vault;
 
+use auth_prompt::*;
 use commands::*;
+use fleet::*;
+use fleet_overview::*;
 use infrastructure_graph::*;
+use key_deploy::*;
+use metrics_poller::*;
+use metrics_store::*;
+use privilege::*;
+use profile_bundle::*;
+use profile_query::*;
+use sftp::*;
+use ssh_config::*;
+use terminal::*;
+use transfer::*;
 use user_commands::*;
+use vault::*;
 
 fn main() {
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_store::Builder::default().build())
         .manage(commands::AppState::default())
+        .manage(auth_prompt::AuthPromptState::default())
+        .manage(terminal::TerminalState::default())
         .manage(infrastructure_graph::InfraGraphState::default())
+        .manage(vault::VaultState::default())
+        .manage(metrics_poller::MetricsSubscribers::default())
+        .manage(metrics_store::MetricsStore::new())
         .invoke_handler(tauri::generate_handler![
             test_connection,
             connect_to_server,
             disconnect_server,
+            get_connected_servers,
+            trust_host_key,
+            forget_host_key,
+            list_agent_identities,
+            answer_auth_prompt,
+            preview_ssh_config,
+            import_ssh_config,
+            check_privileges,
+            get_vault_status,
+            unlock_vault,
+            lock_vault,
+            set_vault_auto_lock,
             get_system_metrics,
+            subscribe_metrics,
+            get_latest_metrics,
+            unsubscribe_metrics,
+            get_background_metrics_interval,
+            set_background_metrics_interval,
+            get_metrics_history,
             get_docker_containers,
             docker_container_action,
             get_container_logs,
@@ -30,10 +88,29 @@ fn main() {
             service_action,
             get_service_logs,
             execute_command,
+            cancel_command,
+            broadcast_command,
+            get_fleet_overview,
+            open_terminal,
+            write_terminal,
+            resize_terminal,
+            close_terminal,
+            list_remote_directory,
+            stat_remote_path,
+            create_remote_directory,
+            rename_remote_path,
+            delete_remote_path,
+            upload_file,
+            download_file,
             save_server_profile,
             get_server_profiles,
             delete_server_profile,
             update_server_profile_metadata,
+            query_server_profiles,
+            get_profile_tree,
+            get_profile_tags,
+            export_profiles,
+            import_profiles,
             get_ufw_status,
             get_ufw_stats,
             get_ufw_overview,
@@ -85,6 +162,8 @@ fn main() {
             add_user_to_group,
             remove_user_from_group,
             add_ssh_key,
+            generate_ssh_key,
+            deploy_ssh_key,
             delete_ssh_key,
             create_group,
             delete_group,
@@ -0,0 +1,348 @@
+//! System metrics read straight from /proc in one round trip, and the parsers for it.
+//! Shared by the per-server dashboard and the fleet health overview so both read
+//! numbers the same way. Nothing here depends on procps, so the output doesn't
+//! change between distros the way `top` and `free` do.
+
+use crate::types::{DiskUsage, NetworkStats};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Prefix of the marker lines that separate the parts of a combined script's output
+const SECTION_MARKER: &str = "@@dpanel:";
+
+/// Two /proc/stat samples a moment apart, for when there is no earlier poll to compare with
+const CPU_BASELINE_COMMAND: &str = "head -n 1 /proc/stat; sleep 0.25";
+
+/// Build one shell script that runs each `(name, command)` and labels its output,
+/// so several readings cost a single round trip. Split the result with `split_sections`.
+pub fn sectioned_script(sections: &[(&str, &str)]) -> String {
+    sections
+        .iter()
+        .map(|(name, command)| format!("echo '{}{}'; {{ {}; }} 2>/dev/null", SECTION_MARKER, name, command))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+pub fn split_sections(output: &str) -> HashMap<String, String> {
+    let mut sections: HashMap<String, String> = HashMap::new();
+    let mut current: Option<String> = None;
+    for line in output.lines() {
+        if let Some(name) = line.strip_prefix(SECTION_MARKER) {
+            current = Some(name.trim().to_string());
+            sections.entry(name.trim().to_string()).or_default();
+        } else if let Some(name) = &current {
+            let section = sections.entry(name.clone()).or_default();
+            section.push_str(line);
+            section.push('\n');
+        }
+    }
+    sections
+}
+
+/// df reads statfs for every mount; -P keeps each one on a single line. Snap images,
+/// tmpfs and container layers are always full or say nothing about the real disks, so
+/// they're left out; busybox df has no -x, so there `parse_disks` drops them instead.
+const DISK_COMMAND: &str = "df -P -B1 -x squashfs -x tmpfs -x devtmpfs -x overlay 2>/dev/null || df -P -B1";
+
+/// Filesystems `DISK_COMMAND` excludes by type, by the name df shows for them
+const PSEUDO_FILESYSTEMS: &[&str] = &["squashfs", "tmpfs", "devtmpfs", "overlay"];
+
+/// The sections of the /proc snapshot. With `sample_cpu` the script takes an extra
+/// /proc/stat reading a moment before the real one, for when there's no previous poll.
+pub fn proc_sections(sample_cpu: bool) -> Vec<(&'static str, &'static str)> {
+    let mut sections = Vec::new();
+    if sample_cpu {
+        sections.push(("cpu_baseline", CPU_BASELINE_COMMAND));
+    }
+    sections.extend([
+        ("cpu", "head -n 1 /proc/stat"),
+        ("meminfo", "cat /proc/meminfo"),
+        ("loadavg", "cat /proc/loadavg"),
+        ("uptime", "cat /proc/uptime"),
+        ("net", "cat /proc/net/dev"),
+        ("route", "cat /proc/net/route"),
+        ("pids", "ls -d /proc/[0-9]* | wc -l"),
+        ("disk", DISK_COMMAND),
+    ]);
+    sections
+}
+
+/// The whole snapshot as one script
+pub fn proc_script(sample_cpu: bool) -> String {
+    sectioned_script(&proc_sections(sample_cpu))
+}
+
+/// Cumulative CPU time from the first line of /proc/stat, in clock ticks
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct CpuTimes {
+    pub idle: u64,
+    pub total: u64,
+}
+
+impl CpuTimes {
+    pub fn parse(output: &str) -> Option<CpuTimes> {
+        let line = output.lines().find(|l| l.starts_with("cpu "))?;
+        let values: Vec<u64> = line.split_whitespace().skip(1).filter_map(|v| v.parse().ok()).collect();
+        if values.len() < 4 {
+            return None;
+        }
+        // user nice system idle iowait irq softirq steal; guest time is already in user
+        let total = values.iter().take(8).sum();
+        let idle = values[3] + values.get(4).copied().unwrap_or(0);
+        Some(CpuTimes { idle, total })
+    }
+
+    /// Busy share of the time since `earlier`. Without a usable earlier sample (first
+    /// poll, or the counters went back after a reboot) it's the average since boot.
+    pub fn percent_since(&self, earlier: Option<&CpuTimes>) -> f64 {
+        let (idle, total) = match earlier {
+            Some(earlier) if self.total > earlier.total && self.idle >= earlier.idle => {
+                (self.idle - earlier.idle, self.total - earlier.total)
+            }
+            _ => (self.idle, self.total),
+        };
+        if total == 0 {
+            return 0.0;
+        }
+        (total.saturating_sub(idle) as f64 / total as f64 * 100.0).clamp(0.0, 100.0)
+    }
+}
+
+/// Everything one run of `proc_script` reports
+#[derive(Debug, Clone)]
+pub struct ProcSnapshot {
+    pub cpu: Option<CpuTimes>,
+    /// The extra sample taken when `sample_cpu` was asked for
+    pub cpu_baseline: Option<CpuTimes>,
+    pub memory_used: u64,
+    pub memory_total: u64,
+    pub disks: Vec<DiskUsage>,
+    pub load_avg: [f64; 3],
+    pub uptime: u64,
+    pub process_count: u32,
+    pub network: NetworkStats,
+}
+
+impl ProcSnapshot {
+    pub fn parse(output: &str) -> ProcSnapshot {
+        Self::from_sections(&split_sections(output))
+    }
+
+    pub fn from_sections(sections: &HashMap<String, String>) -> ProcSnapshot {
+        let section = |name: &str| sections.get(name).map(String::as_str).unwrap_or("");
+        let (memory_used, memory_total) = parse_meminfo(section("meminfo"));
+        let interface = default_interface(section("route"));
+        ProcSnapshot {
+            cpu: CpuTimes::parse(section("cpu")),
+            cpu_baseline: CpuTimes::parse(section("cpu_baseline")),
+            memory_used,
+            memory_total,
+            disks: parse_disks(section("disk")),
+            load_avg: parse_load(section("loadavg")),
+            uptime: section("uptime")
+                .split_whitespace()
+                .next()
+                .and_then(|s| s.parse::<f64>().ok())
+                .map(|s| s as u64)
+                .unwrap_or(0),
+            process_count: section("pids").trim().parse().unwrap_or(0),
+            network: parse_net_dev(section("net"), interface.as_deref()),
+        }
+    }
+
+    /// CPU usage against the in-script baseline if there is one, else `previous`
+    pub fn cpu_percent(&self, previous: Option<&CpuTimes>) -> f64 {
+        match &self.cpu {
+            Some(cpu) => cpu.percent_since(self.cpu_baseline.as_ref().or(previous)),
+            None => 0.0,
+        }
+    }
+
+    pub fn memory_percent(&self) -> f64 {
+        memory_percent(self.memory_used, self.memory_total)
+    }
+}
+
+pub fn memory_percent(used: u64, total: u64) -> f64 {
+    if total > 0 {
+        (used as f64 / total as f64) * 100.0
+    } else {
+        0.0
+    }
+}
+
+/// `(used, total)` in bytes. Used is what isn't available, the way `free` counts it;
+/// kernels older than 3.14 have no MemAvailable, so free + buffers + cache stands in.
+pub fn parse_meminfo(output: &str) -> (u64, u64) {
+    let fields: HashMap<&str, u64> = output
+        .lines()
+        .filter_map(|line| {
+            let (key, rest) = line.split_once(':')?;
+            let kib: u64 = rest.split_whitespace().next()?.parse().ok()?;
+            Some((key.trim(), kib * 1024))
+        })
+        .collect();
+    let field = |key: &str| fields.get(key).copied().unwrap_or(0);
+    let total = field("MemTotal");
+    let available = fields
+        .get("MemAvailable")
+        .copied()
+        .unwrap_or_else(|| field("MemFree") + field("Buffers") + field("Cached"));
+    (total.saturating_sub(available), total)
+}
+
+/// `df -P -B1` output, header, loop devices and pseudo filesystems skipped
+pub fn parse_disks(output: &str) -> Vec<DiskUsage> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() < 6 || parts[0] == "Filesystem" {
+                return None;
+            }
+            if parts[0].starts_with("/dev/loop") || PSEUDO_FILESYSTEMS.contains(&parts[0]) {
+                return None;
+            }
+            Some(DiskUsage {
+                // Mount points may contain spaces; they're always the last column
+                mount_point: parts[5..].join(" "),
+                used: parts[2].parse().unwrap_or(0),
+                total: parts[1].parse().unwrap_or(0),
+                percent: parts[4].trim_end_matches('%').parse().unwrap_or(0.0),
+            })
+        })
+        .collect()
+}
+
+pub fn parse_load(output: &str) -> [f64; 3] {
+    let parts: Vec<f64> = output.split_whitespace().take(3).filter_map(|s| s.parse().ok()).collect();
+    [
+        parts.first().copied().unwrap_or(0.0),
+        parts.get(1).copied().unwrap_or(0.0),
+        parts.get(2).copied().unwrap_or(0.0),
+    ]
+}
+
+/// The interface of the default route in /proc/net/route
+pub fn default_interface(output: &str) -> Option<String> {
+    output.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        (fields.len() > 1 && fields[1] == "00000000").then(|| fields[0].to_string())
+    })
+}
+
+/// Counters for `interface` from /proc/net/dev, or for the first one that isn't loopback
+pub fn parse_net_dev(output: &str, interface: Option<&str>) -> NetworkStats {
+    let interfaces: Vec<(&str, Vec<u64>)> = output
+        .lines()
+        .filter_map(|line| {
+            // "eth0: 123 ..." — large counters can run into the colon
+            let (name, counters) = line.split_once(':')?;
+            let counters: Vec<u64> = counters.split_whitespace().filter_map(|v| v.parse().ok()).collect();
+            (counters.len() >= 10).then(|| (name.trim(), counters))
+        })
+        .collect();
+    let chosen = interface
+        .and_then(|wanted| interfaces.iter().find(|(name, _)| *name == wanted))
+        .or_else(|| interfaces.iter().find(|(name, _)| *name != "lo"));
+
+    match chosen {
+        Some((name, counters)) => NetworkStats {
+            bytes_recv: counters[0],
+            packets_recv: counters[1],
+            bytes_sent: counters[8],
+            packets_sent: counters[9],
+            interface: name.to_string(),
+        },
+        None => NetworkStats {
+            bytes_sent: 0,
+            bytes_recv: 0,
+            packets_sent: 0,
+            packets_recv: 0,
+            interface: String::new(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SNAPSHOT: &str = "@@dpanel:cpu
+cpu  4705 150 1120 16250 520 0 25 0 0 0
+@@dpanel:meminfo
+MemTotal:        8000000 kB
+MemFree:         1000000 kB
+MemAvailable:    6000000 kB
+Buffers:          200000 kB
+@@dpanel:loadavg
+0.50 1.25 2.00 2/345 6789
+@@dpanel:uptime
+12345.67 40000.00
+@@dpanel:net
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo: 5000 50 0 0 0 0 0 0 5000 50 0 0 0 0 0 0
+  eth0:123456789 1000 0 0 0 0 0 0 987654 900 0 0 0 0 0 0
+@@dpanel:route
+Iface\tDestination\tGateway\tFlags
+eth0\t00000000\t0102A8C0\t0003
+eth0\t0002A8C0\t00000000\t0001
+@@dpanel:pids
+231
+@@dpanel:disk
+Filesystem     1-blocks      Used Available Capacity Mounted on
+/dev/sda1    1000000000 500000000 500000000      50% /
+/dev/sdb1     100000000  90000000  10000000      90% /mnt/backup disk
+/dev/loop3     60000000  60000000         0     100% /snap/core22/1380
+tmpfs           4000000   4000000         0     100% /run/user/1000
+";
+
+    #[test]
+    fn test_parses_proc_snapshot() {
+        let snapshot = ProcSnapshot::parse(SNAPSHOT);
+        assert_eq!(snapshot.memory_total, 8_192_000_000);
+        assert_eq!(snapshot.memory_used, 2_048_000_000);
+        assert_eq!(snapshot.memory_percent(), 25.0);
+        assert_eq!(snapshot.load_avg, [0.5, 1.25, 2.0]);
+        assert_eq!((snapshot.uptime, snapshot.process_count), (12345, 231));
+        assert_eq!(snapshot.network.interface, "eth0");
+        assert_eq!((snapshot.network.bytes_recv, snapshot.network.bytes_sent), (123456789, 987654));
+        assert_eq!(snapshot.disks.len(), 2);
+        assert_eq!((snapshot.disks[1].mount_point.as_str(), snapshot.disks[1].percent), ("/mnt/backup disk", 90.0));
+
+        // Old kernels without MemAvailable
+        assert_eq!(parse_meminfo("MemTotal: 100 kB\nMemFree: 50 kB\nCached: 25 kB\n"), (25 * 1024, 100 * 1024));
+    }
+
+    #[test]
+    fn test_cpu_percent_comes_from_deltas() {
+        let snapshot = ProcSnapshot::parse(SNAPSHOT);
+        let cpu = snapshot.cpu.unwrap();
+        assert_eq!(cpu, CpuTimes { idle: 16770, total: 22770 });
+
+        let earlier = CpuTimes { idle: 16370, total: 21770 };
+        assert_eq!(snapshot.cpu_percent(Some(&earlier)), 60.0);
+        // No earlier sample, or one from before a reboot: average since boot
+        let since_boot = 6000.0 / 22770.0 * 100.0;
+        assert_eq!(snapshot.cpu_percent(None), since_boot);
+        assert_eq!(cpu.percent_since(Some(&CpuTimes { idle: 90000, total: 99000 })), since_boot);
+
+        // A baseline taken inside the script wins over the previous poll
+        let sampled = format!("@@dpanel:cpu_baseline\ncpu 4605 150 1120 15350 520 0 25 0\n{}", SNAPSHOT);
+        assert_eq!(ProcSnapshot::parse(&sampled).cpu_percent(Some(&earlier)), 10.0);
+    }
+
+    #[test]
+    fn test_sections_round_trip() {
+        let script = sectioned_script(&[("cpu", "echo 1"), ("load", "cat /proc/loadavg")]);
+        assert!(script.starts_with("echo '@@dpanel:cpu'; { echo 1; } 2>/dev/null; "));
+        assert!(proc_script(true).starts_with("echo '@@dpanel:cpu_baseline'; { head -n 1 /proc/stat; sleep 0.25; }"));
+        assert!(!proc_script(false).contains("sleep"));
+
+        let sections = split_sections("@@dpanel:cpu\n12.5\n@@dpanel:units\n@@dpanel:load\n0.1 0.2 0.3\n");
+        assert_eq!(sections["cpu"], "12.5\n");
+        assert_eq!(sections["units"], "");
+        assert_eq!(parse_load(&sections["load"]), [0.1, 0.2, 0.3]);
+    }
+}
@@ -0,0 +1,269 @@
+use crate::commands::{parse_docker_stats, AppState, ServerConnection, DOCKER_STATS_COMMAND, STORE_FILENAME};
+use crate::metrics_store::MetricsStore;
+use crate::types::*;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State, WebviewWindow};
+use tauri_plugin_store::StoreExt;
+use tokio::time::Instant;
+
+pub const METRICS_UPDATE_EVENT: &str = "metrics://update";
+
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_INTERVAL: Duration = Duration::from_secs(300);
+const MAX_BACKOFF: Duration = Duration::from_secs(120);
+const TICK: Duration = Duration::from_secs(1);
+/// Store key of the background sampling interval in seconds, see `set_background_metrics_interval`
+const BACKGROUND_INTERVAL_KEY: &str = "background_metrics_secs";
+/// `docker stats` takes a couple of seconds, so containers are sampled less often
+const CONTAINER_INTERVAL: Duration = Duration::from_secs(60);
+
+struct Subscription {
+    windows: HashSet<String>,
+    interval: Duration,
+}
+
+/// Which windows want metrics from which server, and how often
+#[derive(Default)]
+pub struct MetricsSubscribers {
+    servers: Mutex<HashMap<String, Subscription>>,
+}
+
+impl MetricsSubscribers {
+    /// Add `window` as a listener; `interval` replaces the server's interval when given
+    pub fn subscribe(&self, server_id: &str, window: &str, interval: Option<Duration>) {
+        let mut servers = self.servers.lock().unwrap();
+        let subscription = servers.entry(server_id.to_string()).or_insert_with(|| Subscription {
+            windows: HashSet::new(),
+            interval: DEFAULT_INTERVAL,
+        });
+        subscription.windows.insert(window.to_string());
+        if let Some(interval) = interval {
+            subscription.interval = interval.clamp(MIN_INTERVAL, MAX_INTERVAL);
+        }
+    }
+
+    pub fn unsubscribe(&self, server_id: &str, window: &str) {
+        let mut servers = self.servers.lock().unwrap();
+        if let Some(subscription) = servers.get_mut(server_id) {
+            subscription.windows.remove(window);
+        }
+    }
+
+    /// The windows listening to `server_id` and the interval they asked for. Windows
+    /// `is_open` says are gone are dropped, so a closed window doesn't keep polling alive.
+    fn listeners(&self, server_id: &str, is_open: impl Fn(&str) -> bool) -> Option<(Vec<String>, Duration)> {
+        let mut servers = self.servers.lock().unwrap();
+        let subscription = servers.get_mut(server_id)?;
+        subscription.windows.retain(|window| is_open(window));
+        if subscription.windows.is_empty() {
+            return None;
+        }
+        Some((subscription.windows.iter().cloned().collect(), subscription.interval))
+    }
+}
+
+/// Wait after `failures` readings in a row failed: the interval, doubled per failure
+fn backoff(interval: Duration, failures: u32) -> Duration {
+    if failures == 0 {
+        return interval;
+    }
+    (interval * 2u32.pow(failures.min(6))).min(MAX_BACKOFF.max(interval))
+}
+
+/// How often servers are sampled for the history while no window shows their
+/// metrics. None (the default) means they aren't sampled at all then.
+fn background_interval(app: &AppHandle) -> Option<Duration> {
+    let secs = app.store(STORE_FILENAME).ok()?.get(BACKGROUND_INTERVAL_KEY)?.as_u64()?;
+    (secs > 0).then(|| Duration::from_secs(secs).clamp(MIN_INTERVAL, MAX_INTERVAL))
+}
+
+/// Store a reading in the history, with container stats when they're due
+async fn record_history(app: &AppHandle, connection: &ServerConnection, metrics: SystemMetrics, with_containers: bool) {
+    let client = Arc::clone(&connection.client);
+    let app = app.clone();
+    let recorded = tokio::task::spawn_blocking(move || {
+        // Hosts without docker just have no container series
+        let containers = with_containers
+            .then(|| client.execute_command(DOCKER_STATS_COMMAND).ok())
+            .flatten()
+            .map(|output| parse_docker_stats(&output));
+        app.state::<MetricsStore>().record(client.profile_id(), &metrics, containers.as_deref())
+    })
+    .await;
+    match recorded {
+        Ok(Err(e)) => log::warn!("Failed to store metrics: {}", e),
+        Err(e) => log::warn!("Metrics store task failed: {}", e),
+        Ok(Ok(())) => {}
+    }
+}
+
+/// Sample a connected server's metrics in the background, store them in the history
+/// and emit `metrics://update` to the windows subscribed to it. With no window
+/// subscribed it pauses, unless background sampling is turned on. Stops when the
+/// connection is dropped or closed.
+pub fn spawn(connection: &Arc<ServerConnection>, app: AppHandle) {
+    let connection = Arc::downgrade(connection);
+    tauri::async_runtime::spawn(run(connection, app));
+}
+
+async fn run(connection: Weak<ServerConnection>, app: AppHandle) {
+    let mut last_poll: Option<Instant> = None;
+    let mut last_containers: Option<Instant> = None;
+    let mut failures = 0;
+
+    loop {
+        tokio::time::sleep(TICK).await;
+        let Some(connection) = connection.upgrade() else {
+            return;
+        };
+        if connection.client.is_closed() {
+            return;
+        }
+        let server_id = connection.client.profile_id().to_string();
+        let listening = app
+            .state::<MetricsSubscribers>()
+            .listeners(&server_id, |label| app.get_webview_window(label).is_some())
+            .map(|(_, interval)| interval);
+        let Some(interval) = listening.or_else(|| background_interval(&app)) else {
+            continue;
+        };
+        let wait = backoff(interval, failures);
+        if last_poll.is_some_and(|last| last.elapsed() < wait) {
+            continue;
+        }
+        last_poll = Some(Instant::now());
+
+        // While the connection monitor is reconnecting there's nothing to ask
+        let reading = if connection.client.is_connected() {
+            connection.sample_metrics(wait).await
+        } else {
+            Err("Not connected".to_string())
+        };
+        failures = if reading.is_ok() { 0 } else { failures + 1 };
+        let wait = backoff(interval, failures);
+        if let Ok(metrics) = &reading {
+            let with_containers = last_containers.is_none_or(|last| last.elapsed() >= CONTAINER_INTERVAL);
+            if with_containers {
+                last_containers = Some(Instant::now());
+            }
+            record_history(&app, &connection, metrics.clone(), with_containers).await;
+        }
+
+        // Subscribers may have changed during the reading
+        let Some((windows, _)) = app
+            .state::<MetricsSubscribers>()
+            .listeners(&server_id, |label| app.get_webview_window(label).is_some())
+        else {
+            continue;
+        };
+        let (metrics, error) = match reading {
+            Ok(metrics) => (Some(metrics), None),
+            Err(e) => {
+                log::debug!("Metrics reading for {} failed, next in {:?}: {}", server_id, wait, e);
+                (None, Some(e))
+            }
+        };
+        let event = MetricsUpdateEvent {
+            server_id: server_id.clone(),
+            metrics,
+            error,
+            next_poll_ms: wait.as_millis() as u64,
+        };
+        for window in windows {
+            if let Err(e) = app.emit_to(window.as_str(), METRICS_UPDATE_EVENT, &event) {
+                log::warn!("Failed to emit metrics update: {}", e);
+            }
+        }
+    }
+}
+
+/// Start receiving `metrics://update` events for a server in this window, every
+/// `interval_secs` (5 s by default). While no window is subscribed, readings pause
+/// unless background sampling is turned on.
+#[tauri::command]
+pub async fn subscribe_metrics(
+    interval_secs: Option<u64>,
+    server_id: Option<String>,
+    window: WebviewWindow,
+    state: State<'_, AppState>,
+    subscribers: State<'_, MetricsSubscribers>,
+) -> Result<(), String> {
+    let connection = state.connection(server_id.as_deref()).await?;
+    subscribers.subscribe(
+        connection.client.profile_id(),
+        window.label(),
+        interval_secs.map(Duration::from_secs),
+    );
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn unsubscribe_metrics(
+    server_id: Option<String>,
+    window: WebviewWindow,
+    state: State<'_, AppState>,
+    subscribers: State<'_, MetricsSubscribers>,
+) -> Result<(), String> {
+    let server_id = match server_id {
+        Some(id) => id,
+        None => state.connection(None).await?.client.profile_id().to_string(),
+    };
+    subscribers.unsubscribe(&server_id, window.label());
+    Ok(())
+}
+
+/// The most recent reading of a server, without taking a new one. None until the
+/// first reading after connecting, which `metrics://update` then delivers.
+#[tauri::command]
+pub async fn get_latest_metrics(server_id: Option<String>, state: State<'_, AppState>) -> Result<Option<SystemMetrics>, String> {
+    let connection = state.connection(server_id.as_deref()).await?;
+    let latest = connection.metrics.lock().await.latest.clone();
+    Ok(latest)
+}
+
+/// The background sampling interval in seconds, None when it's off
+#[tauri::command]
+pub async fn get_background_metrics_interval(app: AppHandle) -> Result<Option<u64>, String> {
+    Ok(background_interval(&app).map(|interval| interval.as_secs()))
+}
+
+/// Keep sampling connected servers for the metrics history every `interval_secs`
+/// while no window shows their metrics; None or 0 turns it off. Off by default, so
+/// an idle connection costs nothing.
+#[tauri::command]
+pub async fn set_background_metrics_interval(interval_secs: Option<u64>, app: AppHandle) -> Result<(), String> {
+    let store = app.store(STORE_FILENAME).map_err(|e| format!("Failed to open settings: {}", e))?;
+    store.set(BACKGROUND_INTERVAL_KEY, interval_secs.unwrap_or(0));
+    store.save().map_err(|e| format!("Failed to save settings: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listeners_pause_and_back_off() {
+        let subscribers = MetricsSubscribers::default();
+        assert!(subscribers.listeners("web", |_| true).is_none());
+
+        subscribers.subscribe("web", "main", None);
+        subscribers.subscribe("web", "detached", Some(Duration::from_millis(10)));
+        let (mut windows, interval) = subscribers.listeners("web", |_| true).unwrap();
+        windows.sort();
+        assert_eq!((windows, interval), (vec!["detached".to_string(), "main".to_string()], MIN_INTERVAL));
+
+        // A window that was closed without unsubscribing is dropped
+        let (windows, _) = subscribers.listeners("web", |label| label == "main").unwrap();
+        assert_eq!(windows, vec!["main"]);
+        subscribers.unsubscribe("web", "main");
+        assert!(subscribers.listeners("web", |_| true).is_none());
+
+        assert_eq!(backoff(DEFAULT_INTERVAL, 0), DEFAULT_INTERVAL);
+        assert_eq!(backoff(DEFAULT_INTERVAL, 2), Duration::from_secs(20));
+        assert_eq!(backoff(DEFAULT_INTERVAL, 10), MAX_BACKOFF);
+        assert_eq!(backoff(MAX_INTERVAL, 1), MAX_INTERVAL);
+    }
+}
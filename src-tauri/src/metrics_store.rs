@@ -0,0 +1,507 @@
+//! Metrics history kept on disk per server, so graphs survive disconnects and restarts.
+//!
+//! Each server has a directory with one folder per resolution. Readings are appended
+//! as JSON lines to the `raw` files; once a minute (or hour) is over, its readings are
+//! averaged into the `minute` (or `hour`) files. Files cover a fixed stretch of time
+//! and are named after its start, so old data is dropped by deleting whole files and a
+//! range query only opens the files it overlaps.
+
+use crate::commands::{AppState, ContainerStats};
+use crate::metrics_poller::DEFAULT_INTERVAL;
+use crate::types::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+
+const MINUTE_MS: u64 = 60_000;
+const HOUR_MS: u64 = 60 * MINUTE_MS;
+const DAY_MS: u64 = 24 * HOUR_MS;
+
+/// Most points a query picks a resolution for; finer ones would just be averaged away by the chart
+const MAX_POINTS: u64 = 1500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Resolution {
+    Raw,
+    Minute,
+    Hour,
+}
+
+impl Resolution {
+    const ALL: [Resolution; 3] = [Resolution::Raw, Resolution::Minute, Resolution::Hour];
+
+    fn dir_name(self) -> &'static str {
+        match self {
+            Resolution::Raw => "raw",
+            Resolution::Minute => "minute",
+            Resolution::Hour => "hour",
+        }
+    }
+
+    /// Spacing of the points; raw readings come at the poll interval
+    fn step_ms(self) -> u64 {
+        match self {
+            Resolution::Raw => DEFAULT_INTERVAL.as_millis() as u64,
+            Resolution::Minute => MINUTE_MS,
+            Resolution::Hour => HOUR_MS,
+        }
+    }
+
+    fn retention_ms(self) -> u64 {
+        match self {
+            Resolution::Raw => DAY_MS,
+            Resolution::Minute => 30 * DAY_MS,
+            Resolution::Hour => 365 * DAY_MS,
+        }
+    }
+
+    /// How much time one file holds
+    fn segment_ms(self) -> u64 {
+        match self {
+            Resolution::Raw => HOUR_MS,
+            Resolution::Minute => DAY_MS,
+            Resolution::Hour => 30 * DAY_MS,
+        }
+    }
+
+    /// The resolution a rollup is averaged from
+    fn source(self) -> Option<Resolution> {
+        match self {
+            Resolution::Raw => None,
+            Resolution::Minute => Some(Resolution::Raw),
+            Resolution::Hour => Some(Resolution::Minute),
+        }
+    }
+
+    /// The finest resolution that still holds `from` and doesn't return too many points
+    fn pick(from: u64, to: u64, now: u64) -> Resolution {
+        Self::ALL
+            .into_iter()
+            .find(|r| now.saturating_sub(r.retention_ms()) <= from && to.saturating_sub(from) / r.step_ms() <= MAX_POINTS)
+            .unwrap_or(Resolution::Hour)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContainerPoint {
+    pub cpu_percent: f64,
+    pub memory_usage: f64,
+}
+
+/// One reading, or for rollups the average of the readings in the bucket starting at `timestamp`.
+/// Values missing from a reading are left out of the average rather than counted as zero.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricsPoint {
+    pub timestamp: u64,
+    /// How many readings went into this point
+    pub samples: u32,
+    #[serde(default)]
+    pub cpu_percent: Option<f64>,
+    #[serde(default)]
+    pub memory_percent: Option<f64>,
+    #[serde(default)]
+    pub memory_used: Option<f64>,
+    #[serde(default)]
+    pub load_avg: Option<[f64; 3]>,
+    /// Bytes per second since the previous reading
+    #[serde(default)]
+    pub network_rx_rate: Option<f64>,
+    #[serde(default)]
+    pub network_tx_rate: Option<f64>,
+    /// Percent used per mount point
+    #[serde(default)]
+    pub disks: BTreeMap<String, f64>,
+    /// Only in readings that sampled `docker stats`
+    #[serde(default)]
+    pub containers: BTreeMap<String, ContainerPoint>,
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0u32), |(sum, count), v| (sum + v, count + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+/// Average `points` into one point at `timestamp`
+fn average(points: &[MetricsPoint], timestamp: u64) -> MetricsPoint {
+    let load = |i: usize| mean(points.iter().filter_map(|p| p.load_avg.map(|l| l[i])));
+    let mut disks: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+    let mut containers: BTreeMap<String, Vec<&ContainerPoint>> = BTreeMap::new();
+    for point in points {
+        for (mount, percent) in &point.disks {
+            disks.entry(mount.clone()).or_default().push(*percent);
+        }
+        for (name, container) in &point.containers {
+            containers.entry(name.clone()).or_default().push(container);
+        }
+    }
+
+    MetricsPoint {
+        timestamp,
+        samples: points.iter().map(|p| p.samples).sum(),
+        cpu_percent: mean(points.iter().filter_map(|p| p.cpu_percent)),
+        memory_percent: mean(points.iter().filter_map(|p| p.memory_percent)),
+        memory_used: mean(points.iter().filter_map(|p| p.memory_used)),
+        load_avg: match (load(0), load(1), load(2)) {
+            (Some(a), Some(b), Some(c)) => Some([a, b, c]),
+            _ => None,
+        },
+        network_rx_rate: mean(points.iter().filter_map(|p| p.network_rx_rate)),
+        network_tx_rate: mean(points.iter().filter_map(|p| p.network_tx_rate)),
+        disks: disks
+            .into_iter()
+            .filter_map(|(mount, values)| Some((mount, mean(values.into_iter())?)))
+            .collect(),
+        containers: containers
+            .into_iter()
+            .map(|(name, values)| {
+                let point = ContainerPoint {
+                    cpu_percent: mean(values.iter().map(|c| c.cpu_percent)).unwrap_or(0.0),
+                    memory_usage: mean(values.iter().map(|c| c.memory_usage)).unwrap_or(0.0),
+                };
+                (name, point)
+            })
+            .collect(),
+    }
+}
+
+#[derive(Default)]
+struct Series {
+    /// Start of the newest bucket written per rollup resolution, read from disk on first use
+    last_rollup: HashMap<Resolution, Option<u64>>,
+    /// `(timestamp, bytes_sent, bytes_recv)` of the previous reading, for rates
+    last_network: Option<(u64, u64, u64)>,
+    last_pruned: u64,
+}
+
+pub struct MetricsStore {
+    dir: PathBuf,
+    series: Mutex<HashMap<String, Series>>,
+}
+
+impl MetricsStore {
+    pub fn new() -> Self {
+        let dir = dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("dpanel")
+            .join("metrics");
+        Self::with_dir(dir)
+    }
+
+    fn with_dir(dir: PathBuf) -> Self {
+        MetricsStore {
+            dir,
+            series: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn server_dir(&self, server_id: &str) -> PathBuf {
+        self.dir.join(profile_file_id(server_id))
+    }
+
+    /// `(segment start, path)` of a resolution's files, oldest first
+    fn segments(&self, server_id: &str, resolution: Resolution) -> Vec<(u64, PathBuf)> {
+        let Ok(entries) = fs::read_dir(self.server_dir(server_id).join(resolution.dir_name())) else {
+            return Vec::new();
+        };
+        let mut segments: Vec<(u64, PathBuf)> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let path = entry.path();
+                let start = path.file_stem()?.to_str()?.parse().ok()?;
+                Some((start, path))
+            })
+            .collect();
+        segments.sort_by_key(|(start, _)| *start);
+        segments
+    }
+
+    fn append(&self, server_id: &str, resolution: Resolution, points: &[MetricsPoint]) -> Result<(), String> {
+        let dir = self.server_dir(server_id).join(resolution.dir_name());
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let mut lines: BTreeMap<u64, String> = BTreeMap::new();
+        for point in points {
+            let line = serde_json::to_string(point).map_err(|e| format!("Failed to encode metrics: {}", e))?;
+            let segment = lines.entry(point.timestamp - point.timestamp % resolution.segment_ms()).or_default();
+            segment.push_str(&line);
+            segment.push('\n');
+        }
+        for (start, lines) in lines {
+            let path = dir.join(format!("{}.jsonl", start));
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .and_then(|mut file| file.write_all(lines.as_bytes()))
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+
+    /// Points with `from <= timestamp < to`, oldest first. Lines that don't parse,
+    /// e.g. one cut short by a crash, are skipped.
+    fn read_range(&self, server_id: &str, resolution: Resolution, from: u64, to: u64) -> Vec<MetricsPoint> {
+        let mut points = Vec::new();
+        for (start, path) in self.segments(server_id, resolution) {
+            if start >= to || start + resolution.segment_ms() <= from {
+                continue;
+            }
+            let Ok(content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            points.extend(
+                content
+                    .lines()
+                    .filter_map(|line| serde_json::from_str::<MetricsPoint>(line).ok())
+                    .filter(|p| p.timestamp >= from && p.timestamp < to),
+            );
+        }
+        points
+    }
+
+    fn newest_timestamp(&self, server_id: &str, resolution: Resolution) -> Option<u64> {
+        let (_, path) = self.segments(server_id, resolution).pop()?;
+        let content = fs::read_to_string(path).ok()?;
+        content
+            .lines()
+            .rev()
+            .find_map(|line| serde_json::from_str::<MetricsPoint>(line).ok())
+            .map(|p| p.timestamp)
+    }
+
+    /// Average every finished bucket of `resolution` that isn't written yet. Works from
+    /// what's on disk, so buckets missed while the app was closed are filled in later.
+    fn roll_up(&self, server_id: &str, series: &mut Series, resolution: Resolution, now: u64) -> Result<(), String> {
+        let Some(source) = resolution.source() else {
+            return Ok(());
+        };
+        let bucket = resolution.step_ms();
+        let current = now - now % bucket;
+        let last = *series
+            .last_rollup
+            .entry(resolution)
+            .or_insert_with(|| self.newest_timestamp(server_id, resolution));
+        let from = last.map_or(0, |last| last + bucket);
+        if from >= current {
+            return Ok(());
+        }
+
+        let mut buckets: BTreeMap<u64, Vec<MetricsPoint>> = BTreeMap::new();
+        for point in self.read_range(server_id, source, from, current) {
+            buckets.entry(point.timestamp - point.timestamp % bucket).or_default().push(point);
+        }
+        let rollups: Vec<MetricsPoint> = buckets.iter().map(|(start, points)| average(points, *start)).collect();
+        self.append(server_id, resolution, &rollups)?;
+        series.last_rollup.insert(resolution, Some(current - bucket));
+        Ok(())
+    }
+
+    /// Delete files that are entirely past their resolution's retention
+    fn prune(&self, server_id: &str, now: u64) {
+        for resolution in Resolution::ALL {
+            let cutoff = now.saturating_sub(resolution.retention_ms());
+            for (start, path) in self.segments(server_id, resolution) {
+                if start + resolution.segment_ms() <= cutoff {
+                    if let Err(e) = fs::remove_file(&path) {
+                        log::warn!("Failed to remove old metrics {}: {}", path.display(), e);
+                    }
+                }
+            }
+        }
+    }
+
+    fn add_point(&self, server_id: &str, series: &mut Series, point: MetricsPoint) -> Result<(), String> {
+        let now = point.timestamp;
+        self.append(server_id, Resolution::Raw, &[point])?;
+        self.roll_up(server_id, series, Resolution::Minute, now)?;
+        self.roll_up(server_id, series, Resolution::Hour, now)?;
+        if now.saturating_sub(series.last_pruned) >= HOUR_MS {
+            series.last_pruned = now;
+            self.prune(server_id, now);
+        }
+        Ok(())
+    }
+
+    /// Store a reading taken now. `containers` is None when `docker stats` wasn't sampled.
+    pub fn record(
+        &self,
+        server_id: &str,
+        metrics: &SystemMetrics,
+        containers: Option<&[ContainerStats]>,
+    ) -> Result<(), String> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let mut all = self.series.lock().unwrap();
+        let series = all.entry(server_id.to_string()).or_default();
+
+        let network = &metrics.network;
+        let rate = |current: u64, previous: u64, since: u64| {
+            // Counters reset when the interface or host restarts
+            (current >= previous && now > since).then(|| (current - previous) as f64 * 1000.0 / (now - since) as f64)
+        };
+        let (network_rx_rate, network_tx_rate) = match series.last_network {
+            Some((since, sent, recv)) => (rate(network.bytes_recv, recv, since), rate(network.bytes_sent, sent, since)),
+            None => (None, None),
+        };
+        series.last_network = Some((now, network.bytes_sent, network.bytes_recv));
+
+        let point = MetricsPoint {
+            timestamp: now,
+            samples: 1,
+            cpu_percent: Some(metrics.cpu_percent),
+            memory_percent: Some(crate::metrics::memory_percent(metrics.memory_used, metrics.memory_total)),
+            memory_used: Some(metrics.memory_used as f64),
+            load_avg: Some(metrics.load_avg),
+            network_rx_rate,
+            network_tx_rate,
+            disks: metrics.disk_usage.iter().map(|d| (d.mount_point.clone(), d.percent)).collect(),
+            containers: containers
+                .unwrap_or_default()
+                .iter()
+                .map(|c| {
+                    let point = ContainerPoint {
+                        cpu_percent: c.cpu_percent,
+                        memory_usage: c.memory_usage as f64,
+                    };
+                    (c.name.clone(), point)
+                })
+                .collect(),
+        };
+        self.add_point(server_id, series, point)
+    }
+
+    /// Drop a server's whole history, e.g. when its profile is deleted
+    pub fn forget(&self, server_id: &str) -> Result<(), String> {
+        self.series.lock().unwrap().remove(server_id);
+        let dir = self.server_dir(server_id);
+        if dir.exists() {
+            fs::remove_dir_all(&dir).map_err(|e| format!("Failed to remove {}: {}", dir.display(), e))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricsRange {
+    pub server_id: String,
+    pub resolution: Resolution,
+    pub from: u64,
+    pub to: u64,
+    pub points: Vec<MetricsPoint>,
+}
+
+/// Stored metrics of a server between `from` and `to` (ms since the epoch, `to` defaults
+/// to now). Without a `resolution` the finest one that still covers `from` is used,
+/// unless it would return too many points. The server doesn't need to be connected.
+#[tauri::command]
+pub async fn get_metrics_history(
+    from: u64,
+    to: Option<u64>,
+    resolution: Option<Resolution>,
+    server_id: Option<String>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<MetricsRange, String> {
+    let server_id = match server_id {
+        Some(id) => id,
+        None => state.connection(None).await?.client.profile_id().to_string(),
+    };
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    let to = to.unwrap_or(now);
+    if from >= to {
+        return Err("The range must end after it starts".to_string());
+    }
+    let resolution = resolution.unwrap_or_else(|| Resolution::pick(from, to, now));
+
+    let id = server_id.clone();
+    let points = tokio::task::spawn_blocking(move || app.state::<MetricsStore>().read_range(&id, resolution, from, to))
+        .await
+        .map_err(|e| format!("Metrics history task failed: {}", e))?;
+    Ok(MetricsRange {
+        server_id,
+        resolution,
+        from,
+        to,
+        points,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(timestamp: u64, cpu: f64) -> MetricsPoint {
+        MetricsPoint {
+            timestamp,
+            samples: 1,
+            cpu_percent: Some(cpu),
+            disks: BTreeMap::from([("/".to_string(), cpu / 2.0)]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_average_skips_missing_values() {
+        let mut with_container = reading(5_000, 30.0);
+        with_container.containers.insert("web".to_string(), ContainerPoint { cpu_percent: 4.0, memory_usage: 100.0 });
+        let point = average(&[reading(0, 10.0), with_container], 0);
+        assert_eq!(point.samples, 2);
+        assert_eq!(point.cpu_percent, Some(20.0));
+        assert_eq!(point.memory_percent, None);
+        assert_eq!(point.disks["/"], 10.0);
+        assert_eq!(point.containers["web"].cpu_percent, 4.0);
+    }
+
+    #[test]
+    fn test_resolution_pick() {
+        let now = 400 * DAY_MS;
+        assert_eq!(Resolution::pick(now - HOUR_MS, now, now), Resolution::Raw);
+        assert_eq!(Resolution::pick(now - 7 * DAY_MS, now, now), Resolution::Hour);
+        assert_eq!(Resolution::pick(now - DAY_MS, now, now), Resolution::Minute);
+        assert_eq!(Resolution::pick(now - 60 * DAY_MS, now - 59 * DAY_MS, now), Resolution::Hour);
+    }
+
+    #[test]
+    fn test_rollups_and_retention() {
+        let dir = std::env::temp_dir().join(format!("dpanel-metrics-test-{}", std::process::id()));
+        let store = MetricsStore::with_dir(dir.clone());
+        let mut series = Series::default();
+        let id = "ssh-config:web";
+
+        // Two minutes of readings, then one in the next hour to close both rollups
+        for (timestamp, cpu) in [(0, 10.0), (30_000, 20.0), (MINUTE_MS, 40.0), (HOUR_MS + 1_000, 80.0)] {
+            store.add_point(id, &mut series, reading(timestamp, cpu)).unwrap();
+        }
+        let minutes = store.read_range(id, Resolution::Minute, 0, u64::MAX);
+        let minutes: Vec<(u64, Option<f64>)> = minutes.iter().map(|p| (p.timestamp, p.cpu_percent)).collect();
+        assert_eq!(minutes, vec![(0, Some(15.0)), (MINUTE_MS, Some(40.0))]);
+        let hours = store.read_range(id, Resolution::Hour, 0, u64::MAX);
+        assert_eq!(hours.len(), 1);
+        assert_eq!((hours[0].cpu_percent, hours[0].samples), (Some(27.5), 3));
+
+        // A fresh store picks up where the files left off instead of rolling up again
+        let reopened = MetricsStore::with_dir(dir.clone());
+        let mut series = Series::default();
+        reopened.add_point(id, &mut series, reading(HOUR_MS + 2_000, 0.0)).unwrap();
+        assert_eq!(reopened.read_range(id, Resolution::Minute, 0, u64::MAX).len(), 2);
+        assert_eq!(reopened.read_range(id, Resolution::Raw, HOUR_MS, u64::MAX).len(), 2);
+
+        // Raw files older than a day go, rollups stay
+        reopened.add_point(id, &mut series, reading(2 * DAY_MS, 5.0)).unwrap();
+        assert_eq!(reopened.read_range(id, Resolution::Raw, 0, DAY_MS).len(), 0);
+        assert_eq!(reopened.read_range(id, Resolution::Minute, 0, DAY_MS).len(), 3);
+
+        reopened.forget(id).unwrap();
+        assert!(!dir.join("ssh-config_web").exists());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,223 @@
+use crate::commands::AppState;
+use crate::shell::Cmd;
+use crate::ssh::{CommandCategory, SshClient};
+use crate::types::*;
+use tauri::State;
+
+// Commands spell root access as a plain `sudo ...`; these shell functions shadow `sudo`
+// so the profile's escalation is applied wherever it appears in the command line.
+const RUN_DIRECTLY: &str = r#"sudo() { "$@"; }"#;
+// -n fails straight away instead of waiting for a password no one will type
+const SUDO_NOPASSWD: &str = r#"sudo() { command sudo -n "$@"; }"#;
+const DOAS_NOPASS: &str = r#"sudo() { command doas -n "$@"; }"#;
+// The password arrives as the first line on stdin and only ever passes through the
+// `read` and `printf` builtins, so it never shows up in a process's arguments. Whatever
+// the caller piped into `sudo` follows it. -k makes sudo always read the password line
+// rather than leave it for the command when credentials happen to be cached.
+const SUDO_PASSWORD: &str = r#"IFS= read -r dpanel_password
+sudo() { { printf '%s\n' "$dpanel_password"; cat; } | command sudo -S -k -p '' "$@"; }"#;
+// shadow-utils and busybox su only read the password from a terminal, so su runs on
+// a pty and the password is typed in when it prompts (see `take_password_prompt`).
+// The arguments are quoted into a single -c string, the one form every su accepts,
+// and LC_ALL=C keeps the prompt recognisable.
+const SU_TTY: &str = r#"sudo() {
+  dpanel_command=
+  for dpanel_arg; do
+    dpanel_command="$dpanel_command '$(printf '%s' "$dpanel_arg" | sed "s/'/'\\\\''/g")'"
+  done
+  LC_ALL=C command su -c "$dpanel_command" root
+}"#;
+
+/// Panels whose actions run through `sudo`, with what they use it for
+const ROOT_PANELS: &[(&str, &str)] = &[
+    ("services", "Starting, stopping and restarting systemd services"),
+    ("nginx", "Saving configuration, managing vhosts and reloading nginx"),
+    ("firewall", "Reading and changing ufw rules"),
+    ("users", "Managing users, groups, passwords and SSH keys"),
+];
+
+/// A command line ready to run, plus what it expects on stdin
+pub struct Escalated {
+    pub command: String,
+    pub stdin: Option<String>,
+    /// Run on a pty and answer password prompts with this, for su
+    pub tty_password: Option<String>,
+}
+
+impl Escalated {
+    /// Run `command` as it is
+    pub fn plain(command: &str) -> Self {
+        Escalated { command: command.to_string(), stdin: None, tty_password: None }
+    }
+}
+
+/// Rewrite `command` so its `sudo` calls escalate the way the profile says.
+/// Commands that never mention sudo are passed through untouched.
+pub fn escalate(profile: &ServerProfile, command: &str) -> Result<Escalated, String> {
+    if !command.contains("sudo ") {
+        return Ok(Escalated::plain(command));
+    }
+
+    let (prelude, password) = match &profile.escalation {
+        Escalation::None => (RUN_DIRECTLY, None),
+        Escalation::Sudo => (SUDO_NOPASSWD, None),
+        Escalation::Doas => (DOAS_NOPASS, None),
+        Escalation::SudoPassword { password } => {
+            let password = password.clone().or_else(|| match &profile.auth_method {
+                AuthMethod::Password { password } => Some(password.clone()),
+                _ => None,
+            });
+            // An empty one would only come back as a wrong password on every command
+            let password = password
+                .filter(|p| !p.is_empty())
+                .ok_or("sudo password required for this profile: it doesn't log in with a password, so set one for sudo")?;
+            (SUDO_PASSWORD, Some(password))
+        }
+        Escalation::Su { password } => {
+            let script = format!("{}\n{}", SU_TTY, command);
+            return Ok(Escalated {
+                command: Cmd::new("sh").raw("-c").arg(script).to_string(),
+                stdin: None,
+                tty_password: Some(password.clone()),
+            });
+        }
+    };
+
+    // Run under sh so the function syntax works whatever the login shell is
+    let script = format!("{}\n{}", prelude, command);
+    Ok(Escalated {
+        command: Cmd::new("sh").raw("-c").arg(script).to_string(),
+        stdin: password.map(|p| format!("{}\n", p)),
+        tty_password: None,
+    })
+}
+
+/// If `output` ends in a password prompt, remove the prompt and return true
+pub fn take_password_prompt(output: &mut Vec<u8>) -> bool {
+    let start = output.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+    let last_line = String::from_utf8_lossy(&output[start..]);
+    let is_prompt = last_line.trim_end().ends_with(':') && last_line.to_lowercase().contains("password");
+    if is_prompt {
+        output.truncate(start);
+    }
+    is_prompt
+}
+
+fn probe(client: &SshClient) -> Result<(bool, Option<String>), CommandError> {
+    if let Err(e) = escalate(client.profile(), "sudo true") {
+        return Ok((false, Some(e)));
+    }
+    let output = client.run_command(&Cmd::sudo("id").raw("-u"), CommandCategory::Query.timeout(), None)?;
+    if output.success() && output.stdout.trim() == "0" {
+        return Ok((true, None));
+    }
+    let message = if output.stderr.trim().is_empty() {
+        format!("Escalation did not give root (uid {})", output.stdout.trim())
+    } else {
+        output.stderr.trim().to_string()
+    };
+    Ok((false, Some(message)))
+}
+
+/// Whether commands that need root will work on this connection
+pub fn privilege_status(client: &SshClient) -> Result<PrivilegeStatus, String> {
+    let uid = client.execute_command("id -u").map_err(|e| e.message)?;
+    let (can_escalate, message) = probe(client).map_err(|e| e.message)?;
+
+    Ok(PrivilegeStatus {
+        escalation: client.escalation().kind().to_string(),
+        is_root: uid.trim() == "0",
+        can_escalate,
+        message,
+        root_panels: ROOT_PANELS
+            .iter()
+            .map(|(panel, actions)| RootPanel {
+                panel: panel.to_string(),
+                actions: actions.to_string(),
+            })
+            .collect(),
+    })
+}
+
+/// Check whether commands that need root will work, so the UI can flag the affected
+/// panels before the user runs into a failing action.
+#[tauri::command]
+pub async fn check_privileges(server_id: Option<String>, state: State<'_, AppState>) -> Result<PrivilegeStatus, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(privilege_status).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(escalation: Escalation) -> ServerProfile {
+        ServerProfile {
+            id: "web".to_string(),
+            name: "web".to_string(),
+            host: "example.com".to_string(),
+            port: 22,
+            username: "deploy".to_string(),
+            auth_method: AuthMethod::Password { password: "login-secret".to_string() },
+            jump_hosts: Vec::new(),
+            escalation,
+        }
+    }
+
+    #[test]
+    fn test_escalate_wraps_sudo_per_profile() {
+        // Commands without sudo run as they are
+        let plain = escalate(&profile(Escalation::SudoPassword { password: None }), "uptime").unwrap();
+        assert_eq!((plain.command.as_str(), plain.stdin, plain.tty_password), ("uptime", None, None));
+
+        let sudo = escalate(&profile(Escalation::Sudo), "sudo systemctl restart nginx").unwrap();
+        assert!(sudo.command.contains("command sudo -n"));
+        assert!(sudo.command.ends_with("sudo systemctl restart nginx'"));
+        assert_eq!(sudo.stdin, None);
+
+        // Without its own password, sudo gets the login password, on stdin only
+        let with_password = escalate(&profile(Escalation::SudoPassword { password: None }), "sudo id -u").unwrap();
+        assert!(with_password.command.contains("command sudo -S -k"));
+        assert!(!with_password.command.contains("login-secret"));
+        assert_eq!(with_password.stdin.as_deref(), Some("login-secret\n"));
+        let own_password = escalate(
+            &profile(Escalation::SudoPassword { password: Some("sudo-secret".to_string()) }),
+            "sudo id -u",
+        )
+        .unwrap();
+        assert_eq!(own_password.stdin.as_deref(), Some("sudo-secret\n"));
+        // Key logins have no password to fall back on
+        let mut key_login = profile(Escalation::SudoPassword { password: None });
+        key_login.auth_method = AuthMethod::Agent { identity: None };
+        assert!(escalate(&key_login, "sudo id -u").is_err());
+        assert!(escalate(&key_login, "uptime").is_ok());
+
+        let doas = escalate(&profile(Escalation::Doas), "sudo id -u").unwrap();
+        assert!(doas.command.contains("command doas -n"));
+        assert_eq!(doas.stdin, None);
+
+        let root = escalate(&profile(Escalation::None), "sudo id -u").unwrap();
+        assert!(root.command.contains(r#"sudo() { "$@"; }"#));
+
+        // su answers its prompt on a pty instead of reading stdin
+        let su = escalate(&profile(Escalation::Su { password: "root-secret".to_string() }), "sudo id -u").unwrap();
+        assert!(su.command.contains("command su -c"));
+        assert!(!su.command.contains("root-secret"));
+        assert_eq!(su.stdin, None);
+        assert_eq!(su.tty_password.as_deref(), Some("root-secret"));
+    }
+
+    #[test]
+    fn test_take_password_prompt() {
+        let mut output = b"starting\nPassword: ".to_vec();
+        assert!(take_password_prompt(&mut output));
+        assert_eq!(output, b"starting\n");
+
+        // Only the last line counts, and it has to look like a prompt
+        let mut output = b"Password: \nok\n".to_vec();
+        assert!(!take_password_prompt(&mut output));
+        let mut output = b"checking password strength".to_vec();
+        assert!(!take_password_prompt(&mut output));
+        assert_eq!(output, b"checking password strength");
+    }
+}
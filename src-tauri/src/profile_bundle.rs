@@ -0,0 +1,228 @@
+use crate::commands::{profiles_from_json, profiles_to_json, PROFILES_KEY, STORE_FILENAME};
+use crate::types::*;
+use crate::vault::{is_secret_ref, KdfParams, ProfileSecrets, Sealed, VaultState};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::State;
+use tauri_plugin_store::StoreExt;
+use zeroize::Zeroizing;
+
+const BUNDLE_FORMAT: &str = "dpanel-profiles";
+const BUNDLE_VERSION: u32 = 1;
+/// Binds the ciphertext to the format, so it can't be passed off as a different kind of file
+const BUNDLE_AAD: &[u8] = b"dpanel-profiles/1";
+
+/// What goes on disk: only the KDF parameters are readable without the passphrase
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleFile {
+    format: String,
+    version: u32,
+    kdf: KdfParams,
+    payload: Sealed,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleContents {
+    exported_at: u64,
+    includes_credentials: bool,
+    profiles: Vec<SavedServerProfile>,
+}
+
+/// What to do with a bundled profile whose id is already saved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportConflict {
+    Skip,
+    Overwrite,
+    Rename,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProfileImportResult {
+    pub added: Vec<String>,
+    pub overwritten: Vec<String>,
+    /// Bundled id -> id the profile was saved under
+    pub renamed: HashMap<String, String>,
+    pub skipped: Vec<String>,
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn clear_auth(auth: &mut AuthMethod) {
+    match auth {
+        AuthMethod::Password { password } => password.clear(),
+        AuthMethod::PrivateKey { passphrase, .. } => *passphrase = None,
+        _ => {}
+    }
+}
+
+/// Remove every password and passphrase, keeping the auth method itself
+fn strip_credentials(profile: &mut SavedServerProfile) {
+    clear_auth(&mut profile.auth_method);
+    for jump in &mut profile.jump_hosts {
+        clear_auth(&mut jump.auth_method);
+    }
+    match &mut profile.escalation {
+        Escalation::SudoPassword { password } => *password = None,
+        Escalation::Su { password } => password.clear(),
+        _ => {}
+    }
+}
+
+/// An id that isn't taken yet, in the same form the UI gives new profiles
+fn fresh_id(taken: &HashMap<String, SavedServerProfile>) -> String {
+    let mut id = now_millis();
+    while taken.contains_key(&id.to_string()) {
+        id += 1;
+    }
+    id.to_string()
+}
+
+/// Write the chosen saved profiles to `path`, encrypted with `passphrase`.
+///
+/// With `include_credentials` the passwords and passphrases are resolved from the
+/// vault and stored in the bundle; otherwise the bundle only describes how to connect.
+#[tauri::command]
+pub async fn export_profiles(
+    profile_ids: Vec<String>,
+    include_credentials: bool,
+    passphrase: String,
+    path: String,
+    vault: State<'_, VaultState>,
+    app: tauri::AppHandle,
+) -> Result<usize, String> {
+    let passphrase = Zeroizing::new(passphrase);
+    if passphrase.is_empty() {
+        return Err("Passphrase must not be empty".to_string());
+    }
+
+    let store = app.store(STORE_FILENAME).map_err(|e| format!("Failed to open profile store: {}", e))?;
+    let saved = profiles_from_json(store.get(PROFILES_KEY));
+    let mut profiles = Vec::new();
+    for id in &profile_ids {
+        let mut profile = saved.get(id).cloned().ok_or_else(|| format!("Profile not found: {}", id))?;
+        if include_credentials {
+            vault.open(&app, &mut profile)?;
+        } else {
+            strip_credentials(&mut profile);
+        }
+        profiles.push(profile);
+    }
+
+    let contents = BundleContents {
+        exported_at: now_millis(),
+        includes_credentials: include_credentials,
+        profiles,
+    };
+    let plaintext = Zeroizing::new(
+        serde_json::to_vec(&contents).map_err(|e| format!("Failed to serialize profiles: {}", e))?,
+    );
+
+    // Argon2 is deliberately slow, keep it off the async workers
+    let bundle = tokio::task::spawn_blocking(move || -> Result<BundleFile, String> {
+        let kdf = KdfParams::generate();
+        let key = kdf.derive_key(&passphrase)?;
+        Ok(BundleFile {
+            format: BUNDLE_FORMAT.to_string(),
+            version: BUNDLE_VERSION,
+            payload: Sealed::encrypt(&key, &plaintext, BUNDLE_AAD)?,
+            kdf,
+        })
+    })
+    .await
+    .map_err(|e| format!("Export task failed: {}", e))??;
+
+    let json = serde_json::to_vec_pretty(&bundle).map_err(|e| format!("Failed to serialize bundle: {}", e))?;
+    fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    Ok(contents.profiles.len())
+}
+
+/// Read a bundle written by `export_profiles` and save its profiles.
+///
+/// Conflicts are detected by profile id and settled by `conflict`. Bundled credentials
+/// go straight into the vault, so importing them needs the vault unlocked.
+#[tauri::command]
+pub async fn import_profiles(
+    path: String,
+    passphrase: String,
+    conflict: ImportConflict,
+    vault: State<'_, VaultState>,
+    app: tauri::AppHandle,
+) -> Result<ProfileImportResult, String> {
+    let passphrase = Zeroizing::new(passphrase);
+    let raw = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let bundle: BundleFile =
+        serde_json::from_slice(&raw).map_err(|_| "Not a DPanel profile bundle".to_string())?;
+    if bundle.format != BUNDLE_FORMAT {
+        return Err("Not a DPanel profile bundle".to_string());
+    }
+    if bundle.version > BUNDLE_VERSION {
+        return Err(format!("Bundle version {} is newer than this DPanel supports", bundle.version));
+    }
+
+    let plaintext = tokio::task::spawn_blocking(move || -> Result<Zeroizing<Vec<u8>>, String> {
+        let key = bundle.kdf.derive_key(&passphrase)?;
+        bundle
+            .payload
+            .decrypt(&key, BUNDLE_AAD)
+            .ok_or_else(|| "Wrong passphrase, or the bundle is damaged".to_string())
+    })
+    .await
+    .map_err(|e| format!("Import task failed: {}", e))??;
+    let contents: BundleContents =
+        serde_json::from_slice(&plaintext).map_err(|e| format!("Bundle contents are invalid: {}", e))?;
+
+    let mut incoming = contents.profiles;
+    for profile in &mut incoming {
+        // References into the exporting machine's vault mean nothing here
+        for (_, secret) in profile.secrets_mut() {
+            if is_secret_ref(secret) {
+                secret.clear();
+            }
+        }
+    }
+    let has_secrets = incoming
+        .iter_mut()
+        .any(|p| p.secrets_mut().iter().any(|(_, s)| !s.is_empty()));
+    if has_secrets {
+        vault.ensure_unlocked(&app)?;
+    }
+
+    let store = app.store(STORE_FILENAME).map_err(|e| format!("Failed to open profile store: {}", e))?;
+    let mut profiles_map = profiles_from_json(store.get(PROFILES_KEY));
+    let mut result = ProfileImportResult::default();
+
+    for mut profile in incoming {
+        let bundled_id = profile.id.clone();
+        profile.last_connected = None;
+        match (profiles_map.get(&bundled_id), conflict) {
+            (None, _) => result.added.push(bundled_id),
+            (Some(_), ImportConflict::Skip) => {
+                result.skipped.push(bundled_id);
+                continue;
+            }
+            (Some(existing), ImportConflict::Overwrite) => {
+                profile.last_connected = existing.last_connected;
+                result.overwritten.push(bundled_id);
+            }
+            (Some(_), ImportConflict::Rename) => {
+                profile.id = fresh_id(&profiles_map);
+                profile.name = format!("{} (imported)", profile.name);
+                result.renamed.insert(bundled_id, profile.id.clone());
+            }
+        }
+        vault.seal(&app, &mut profile)?;
+        profiles_map.insert(profile.id.clone(), profile);
+    }
+
+    store.set(PROFILES_KEY, profiles_to_json(&profiles_map));
+    store.save().map_err(|e| format!("Failed to save profiles: {}", e))?;
+    Ok(result)
+}
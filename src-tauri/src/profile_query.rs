@@ -0,0 +1,270 @@
+use crate::commands::{profiles_from_json, PROFILES_KEY, STORE_FILENAME};
+use crate::types::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use tauri_plugin_store::StoreExt;
+
+/// Which saved profiles to pick; every condition given must hold
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProfileFilter {
+    /// Profiles carrying all of these tags
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Profiles in this group or any group below it
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub environment: Option<Environment>,
+    /// Case-insensitive text to find in the name, host, tags or notes
+    #[serde(default)]
+    pub search: Option<String>,
+}
+
+fn in_group(group: Option<&str>, wanted: &str) -> bool {
+    let wanted = wanted.trim_matches('/');
+    group.is_some_and(|group| group == wanted || group.strip_prefix(wanted).is_some_and(|rest| rest.starts_with('/')))
+}
+
+impl ProfileFilter {
+    pub fn matches(&self, profile: &SavedServerProfile) -> bool {
+        let labels = &profile.labels;
+        let has_tags = self
+            .tags
+            .iter()
+            .all(|wanted| labels.tags.iter().any(|tag| tag.eq_ignore_ascii_case(wanted.trim())));
+        let group = self.group.as_deref().is_none_or(|wanted| in_group(labels.group.as_deref(), wanted));
+        let environment = self.environment.is_none_or(|wanted| labels.environment == Some(wanted));
+        let search = self.search.as_deref().map(str::trim).is_none_or(|text| {
+            let text = text.to_lowercase();
+            profile.name.to_lowercase().contains(&text)
+                || profile.host.to_lowercase().contains(&text)
+                || labels.tags.iter().any(|tag| tag.to_lowercase().contains(&text))
+                || labels.notes.as_deref().is_some_and(|notes| notes.to_lowercase().contains(&text))
+        });
+        has_tags && group && environment && search
+    }
+}
+
+/// Ids of the profiles in `ids` plus those matching `filter`, without duplicates.
+/// Listed ids keep their order; filter matches follow, sorted by name.
+pub fn select_profile_ids(
+    profiles: &HashMap<String, SavedServerProfile>,
+    ids: &[String],
+    filter: Option<&ProfileFilter>,
+) -> Vec<String> {
+    let mut selected: Vec<String> = Vec::new();
+    for id in ids {
+        if !selected.contains(id) {
+            selected.push(id.clone());
+        }
+    }
+    if let Some(filter) = filter {
+        let mut matching: Vec<&SavedServerProfile> = profiles.values().filter(|p| filter.matches(p)).collect();
+        matching.sort_by_key(|p| p.name.to_lowercase());
+        for profile in matching {
+            if !selected.contains(&profile.id) {
+                selected.push(profile.id.clone());
+            }
+        }
+    }
+    selected
+}
+
+/// A folder in the profile tree
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProfileGroup {
+    pub name: String,
+    /// Full path, usable as `ProfileFilter::group`
+    pub path: String,
+    /// Profiles directly in this group
+    pub profile_ids: Vec<String>,
+    pub children: Vec<ProfileGroup>,
+    /// Profiles in this group and all groups below it
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProfileTree {
+    pub groups: Vec<ProfileGroup>,
+    pub ungrouped: Vec<String>,
+}
+
+#[derive(Default)]
+struct GroupBuilder {
+    profile_ids: Vec<String>,
+    children: BTreeMap<String, GroupBuilder>,
+}
+
+impl GroupBuilder {
+    fn build(self, name: String, path: String) -> ProfileGroup {
+        let children: Vec<ProfileGroup> = self
+            .children
+            .into_iter()
+            .map(|(child, builder)| {
+                let child_path = format!("{}/{}", path, child);
+                builder.build(child, child_path)
+            })
+            .collect();
+        let total = self.profile_ids.len() + children.iter().map(|c| c.total).sum::<usize>();
+        ProfileGroup {
+            name,
+            path,
+            profile_ids: self.profile_ids,
+            children,
+            total,
+        }
+    }
+}
+
+/// Arrange profiles into their group folders; groups sort by name, profiles by name
+pub fn build_tree(profiles: &[SavedServerProfile]) -> ProfileTree {
+    let mut sorted: Vec<&SavedServerProfile> = profiles.iter().collect();
+    sorted.sort_by_key(|p| p.name.to_lowercase());
+
+    let mut root = GroupBuilder::default();
+    for profile in sorted {
+        let mut node = &mut root;
+        if let Some(group) = &profile.labels.group {
+            for segment in group.split('/').filter(|s| !s.is_empty()) {
+                node = node.children.entry(segment.to_string()).or_default();
+            }
+        }
+        node.profile_ids.push(profile.id.clone());
+    }
+
+    ProfileTree {
+        ungrouped: root.profile_ids,
+        groups: root
+            .children
+            .into_iter()
+            .map(|(name, builder)| builder.build(name.clone(), name))
+            .collect(),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+fn load_profiles(app: &tauri::AppHandle) -> Result<Vec<SavedServerProfile>, String> {
+    let store = app.store(STORE_FILENAME).map_err(|e| format!("Failed to open profile store: {}", e))?;
+    Ok(profiles_from_json(store.get(PROFILES_KEY)).into_values().collect())
+}
+
+/// Saved profiles matching `filter`, sorted by name
+#[tauri::command]
+pub async fn query_server_profiles(
+    filter: ProfileFilter,
+    app: tauri::AppHandle,
+) -> Result<Vec<SavedServerProfile>, String> {
+    let mut profiles: Vec<SavedServerProfile> = load_profiles(&app)?.into_iter().filter(|p| filter.matches(p)).collect();
+    profiles.sort_by_key(|p| p.name.to_lowercase());
+    Ok(profiles)
+}
+
+/// Saved profiles arranged by group, for a folder view of the server list
+#[tauri::command]
+pub async fn get_profile_tree(app: tauri::AppHandle) -> Result<ProfileTree, String> {
+    Ok(build_tree(&load_profiles(&app)?))
+}
+
+/// Every tag in use with how many profiles carry it, most used first
+#[tauri::command]
+pub async fn get_profile_tags(app: tauri::AppHandle) -> Result<Vec<TagCount>, String> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for profile in load_profiles(&app)? {
+        for tag in profile.labels.tags {
+            *counts.entry(tag).or_default() += 1;
+        }
+    }
+    let mut tags: Vec<TagCount> = counts.into_iter().map(|(tag, count)| TagCount { tag, count }).collect();
+    tags.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)));
+    Ok(tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(id: &str, group: Option<&str>, tags: &[&str], environment: Option<Environment>) -> SavedServerProfile {
+        SavedServerProfile {
+            id: id.to_string(),
+            name: id.to_string(),
+            host: format!("{}.example.com", id),
+            port: 22,
+            username: "deploy".to_string(),
+            auth_method: AuthMethod::Agent { identity: None },
+            jump_hosts: Vec::new(),
+            escalation: Escalation::default(),
+            created_at: 0,
+            last_connected: None,
+            connect_on_startup: false,
+            labels: ProfileLabels {
+                tags: tags.iter().map(|t| t.to_string()).collect(),
+                group: group.map(str::to_string),
+                environment,
+                color: None,
+                notes: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_filter_by_tags_group_and_environment() {
+        let web = profile("web", Some("clients/acme"), &["nginx", "public"], Some(Environment::Prod));
+        let db = profile("db", Some("clients/acme-legacy"), &["postgres"], Some(Environment::Prod));
+        let dev = profile("dev", None, &["nginx"], Some(Environment::Dev));
+
+        let acme = ProfileFilter { group: Some("clients/acme".to_string()), ..Default::default() };
+        assert!(acme.matches(&web));
+        assert!(!acme.matches(&db));
+
+        let clients = ProfileFilter { group: Some("clients".to_string()), ..Default::default() };
+        assert!(clients.matches(&web) && clients.matches(&db) && !clients.matches(&dev));
+
+        let prod_nginx = ProfileFilter {
+            tags: vec!["NGINX".to_string()],
+            environment: Some(Environment::Prod),
+            ..Default::default()
+        };
+        assert!(prod_nginx.matches(&web));
+        assert!(!prod_nginx.matches(&dev));
+
+        let search = ProfileFilter { search: Some("db.example".to_string()), ..Default::default() };
+        assert!(search.matches(&db) && !search.matches(&web));
+    }
+
+    #[test]
+    fn test_tree_nests_groups_and_counts_profiles() {
+        let profiles = vec![
+            profile("web", Some("clients/acme"), &[], None),
+            profile("api", Some("clients/acme"), &[], None),
+            profile("db", Some("clients"), &[], None),
+            profile("laptop", None, &[], None),
+        ];
+        let tree = build_tree(&profiles);
+        assert_eq!(tree.ungrouped, vec!["laptop"]);
+        assert_eq!(tree.groups.len(), 1);
+        let clients = &tree.groups[0];
+        assert_eq!((clients.path.as_str(), clients.total), ("clients", 3));
+        assert_eq!(clients.profile_ids, vec!["db"]);
+        assert_eq!(clients.children[0].path, "clients/acme");
+        assert_eq!(clients.children[0].profile_ids, vec!["api", "web"]);
+    }
+
+    #[test]
+    fn test_labels_are_normalized() {
+        let labels = ProfileLabels {
+            tags: vec![" web ".to_string(), "Web".to_string(), "".to_string()],
+            group: Some("/clients//acme/".to_string()),
+            color: Some("  ".to_string()),
+            ..Default::default()
+        }
+        .normalized();
+        assert_eq!(labels.tags, vec!["web"]);
+        assert_eq!(labels.group.as_deref(), Some("clients/acme"));
+        assert_eq!(labels.color, None);
+    }
+}
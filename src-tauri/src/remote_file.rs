@@ -0,0 +1,134 @@
+use crate::sftp::{with_sftp, SftpError};
+use crate::shell::Cmd;
+use crate::ssh::{CommandCategory, SshClient};
+use crate::types::CommandError;
+use ssh2::{OpenFlags, OpenType};
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static NEXT_UPLOAD_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Copies the uploaded content into a temp file beside the target and renames it over
+/// the target, so readers never see a half-written file. An existing file keeps its
+/// owner, mode and SELinux label, and a symlink is followed rather than replaced.
+///
+/// Arguments: target path, uploaded temp file, octal mode and optional `user[:group]`
+/// owner, the last two only applying to a file that doesn't exist yet.
+const INSTALL_SCRIPT: &str = r#"set -e
+target=$1 upload=$2 mode=$3 owner=$4
+stage=
+trap 'rm -f -- "$upload" ${stage:+"$stage"}' EXIT
+if [ -L "$target" ]; then target=$(readlink -f -- "$target"); fi
+stage=$(mktemp "$(dirname -- "$target")/.dpanel.XXXXXX")
+cat -- "$upload" > "$stage"
+if [ -e "$target" ]; then
+  chown --reference="$target" -- "$stage"
+  chmod --reference="$target" -- "$stage"
+  if command -v chcon >/dev/null 2>&1; then chcon --reference="$target" -- "$stage" 2>/dev/null || true; fi
+else
+  chmod "$mode" -- "$stage"
+  if [ -n "$owner" ]; then chown -- "$owner" "$stage"; fi
+fi
+mv -f -- "$stage" "$target"
+"#;
+
+/// How `write_file` installs the content
+#[derive(Debug, Clone, Copy)]
+pub struct WriteOptions<'a> {
+    /// Install as root through sudo
+    pub sudo: bool,
+    /// Mode given to a file that doesn't exist yet
+    pub mode: u32,
+    /// `user[:group]` given to a file that doesn't exist yet; defaults to whoever installs it
+    pub owner: Option<&'a str>,
+}
+
+impl WriteOptions<'_> {
+    /// A root-owned file installed through sudo
+    pub fn root(mode: u32) -> Self {
+        WriteOptions { sudo: true, mode, owner: None }
+    }
+}
+
+fn upload_error(e: SftpError) -> CommandError {
+    let code = match e {
+        SftpError::Cancelled => CommandError::CANCELLED,
+        _ => -1,
+    };
+    CommandError {
+        message: format!("Failed to upload file contents: {}", e),
+        code,
+    }
+}
+
+/// Upload `content` byte for byte to a fresh file under /tmp that only the login user can read
+fn upload_temp(client: &SshClient, content: &[u8]) -> Result<String, CommandError> {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+    let path = format!(
+        "/tmp/.dpanel-upload-{}-{}",
+        nanos,
+        NEXT_UPLOAD_ID.fetch_add(1, Ordering::Relaxed)
+    );
+
+    with_sftp(client, |sftp| {
+        // EXCLUSIVE refuses to follow anything another user planted at this path
+        let mut file = sftp
+            .open_mode(
+                Path::new(&path),
+                OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::EXCLUSIVE,
+                0o600,
+                OpenType::File,
+            )
+            .map_err(|e| SftpError::from_ssh(e, &path))?;
+        file.write_all(content)
+            .and_then(|_| file.flush())
+            .map_err(|e| SftpError::Other(format!("{}: {}", path, e)))
+    })
+    .map_err(upload_error)?;
+
+    Ok(path)
+}
+
+fn remove_temp(client: &SshClient, path: &str) {
+    if let Err(e) = with_sftp(client, |sftp| sftp.unlink(Path::new(path)).map_err(|e| SftpError::from_ssh(e, path))) {
+        log::warn!("Failed to remove temp file {}: {}", path, e);
+    }
+}
+
+/// Atomically replace `path` on the server with exactly `content`. Nothing passes
+/// through the shell, so quotes, backslashes and binary data arrive unchanged.
+pub fn write_file(client: &SshClient, path: &str, content: &[u8], options: WriteOptions) -> Result<(), CommandError> {
+    let upload = upload_temp(client, content)?;
+
+    let command = if options.sudo { Cmd::sudo("sh") } else { Cmd::new("sh") }
+        .raw("-c")
+        .arg(INSTALL_SCRIPT)
+        .raw("dpanel-write")
+        .arg(path)
+        .arg(&upload)
+        .arg(format!("{:o}", options.mode))
+        .arg(options.owner.unwrap_or(""));
+    let result = client.execute_checked(&command, CommandCategory::Action);
+
+    // The script cleans up after itself once it runs, but not if e.g. sudo refused it
+    if result.is_err() {
+        remove_temp(client, &upload);
+    }
+    result.map(|_| ())
+}
+
+/// Install `content` as the login user's crontab
+pub fn install_crontab(client: &SshClient, content: &str) -> Result<String, CommandError> {
+    // cron rejects a last entry without a trailing newline
+    let mut content = content.to_string();
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+
+    let upload = upload_temp(client, content.as_bytes())?;
+    let result = client.execute_checked(&Cmd::new("crontab").arg(&upload), CommandCategory::Action);
+    remove_temp(client, &upload);
+    result
+}
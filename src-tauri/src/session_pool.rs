@@ -0,0 +1,254 @@
+use crate::types::CommandError;
+use ssh2::Session;
+use std::ops::Deref;
+use std::sync::{Condvar, Mutex};
+use std::time::Instant;
+
+/// Authenticated sessions kept open per server
+pub const MAX_POOLED_SESSIONS: usize = 4;
+
+struct PoolState {
+    idle: Vec<Session>,
+    /// Sessions alive right now, idle or checked out
+    open: usize,
+    /// Ceiling on `open`; lowered when the server refuses another connection
+    limit: usize,
+    /// Bumped on every (re)connect and disconnect so stale sessions aren't returned
+    generation: u64,
+}
+
+/// A small pool of authenticated sessions to one server.
+///
+/// libssh2 sessions can only do one thing at a time, so each command checks out a
+/// whole session. The pool starts with the session from `connect` and opens more on
+/// demand, up to `MAX_POOLED_SESSIONS`, letting panels run commands concurrently.
+pub struct SessionPool {
+    state: Mutex<PoolState>,
+    returned: Condvar,
+}
+
+impl Default for SessionPool {
+    fn default() -> Self {
+        SessionPool {
+            state: Mutex::new(PoolState {
+                idle: Vec::new(),
+                open: 0,
+                limit: MAX_POOLED_SESSIONS,
+                generation: 0,
+            }),
+            returned: Condvar::new(),
+        }
+    }
+}
+
+impl SessionPool {
+    /// Replace whatever the pool held with a freshly connected session
+    pub fn reset(&self, session: Session) {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+        state.idle = vec![session];
+        state.open = 1;
+        state.limit = MAX_POOLED_SESSIONS;
+        self.returned.notify_all();
+    }
+
+    /// Drop every idle session; checked-out ones are dropped when they come back
+    pub fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+        state.idle.clear();
+        state.open = 0;
+        self.returned.notify_all();
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state.lock().unwrap().open > 0
+    }
+
+    /// Check out a session, opening another one with `open` if all are busy and the
+    /// pool has room, otherwise waiting until one is returned or `deadline` passes.
+    pub fn acquire<F>(&self, deadline: Instant, open: Option<F>) -> Result<PooledSession<'_>, CommandError>
+    where
+        F: FnOnce() -> Result<Session, CommandError>,
+    {
+        let mut open = open;
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            if state.open == 0 {
+                return Err(CommandError::not_connected());
+            }
+
+            if let Some(session) = state.idle.pop() {
+                return Ok(PooledSession::new(self, session, state.generation));
+            }
+
+            if state.open < state.limit {
+                if let Some(open) = open.take() {
+                    state.open += 1;
+                    let generation = state.generation;
+                    drop(state);
+
+                    match open() {
+                        Ok(session) => return Ok(PooledSession::new(self, session, generation)),
+                        Err(e) => {
+                            log::warn!("Failed to open additional SSH session: {}", e.message);
+                            state = self.state.lock().unwrap();
+                            if state.generation == generation {
+                                state.open -= 1;
+                                state.limit = state.open.max(1);
+                            }
+                            continue;
+                        }
+                    }
+                }
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(CommandError {
+                    message: "Timed out waiting for a free SSH session".to_string(),
+                    code: CommandError::TIMEOUT,
+                });
+            }
+            state = self.returned.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+
+    /// Check out every idle session without waiting, e.g. for health checks
+    pub fn take_idle(&self) -> Vec<PooledSession<'_>> {
+        let mut state = self.state.lock().unwrap();
+        let generation = state.generation;
+        state
+            .idle
+            .drain(..)
+            .map(|session| PooledSession::new(self, session, generation))
+            .collect()
+    }
+
+    fn give_back(&self, session: Session, generation: u64, broken: bool) {
+        let mut state = self.state.lock().unwrap();
+        if state.generation != generation {
+            return;
+        }
+        if broken {
+            state.open -= 1;
+            self.returned.notify_all();
+        } else {
+            state.idle.push(session);
+            self.returned.notify_one();
+        }
+    }
+}
+
+/// A checked-out session, returned to the pool on drop
+pub struct PooledSession<'a> {
+    pool: &'a SessionPool,
+    session: Option<Session>,
+    generation: u64,
+    broken: bool,
+}
+
+impl<'a> PooledSession<'a> {
+    fn new(pool: &'a SessionPool, session: Session, generation: u64) -> Self {
+        PooledSession { pool, session: Some(session), generation, broken: false }
+    }
+
+    /// Close this session instead of returning it, e.g. after the transport failed
+    pub fn discard(&mut self) {
+        self.broken = true;
+    }
+}
+
+impl Deref for PooledSession<'_> {
+    type Target = Session;
+
+    fn deref(&self) -> &Session {
+        self.session.as_ref().expect("pooled session already returned")
+    }
+}
+
+impl Drop for PooledSession<'_> {
+    fn drop(&mut self) {
+        if let Some(session) = self.session.take() {
+            self.pool.give_back(session, self.generation, self.broken);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    type Opener = fn() -> Result<Session, CommandError>;
+
+    fn soon() -> Instant {
+        Instant::now() + Duration::from_millis(50)
+    }
+
+    #[test]
+    fn test_checkout_reuses_and_opens_up_to_limit() {
+        let pool = SessionPool::default();
+        assert_eq!(pool.acquire(soon(), None::<Opener>).err().unwrap().code, CommandError::NOT_CONNECTED);
+
+        pool.reset(Session::new().unwrap());
+        let first = pool.acquire(soon(), None::<Opener>).unwrap();
+        drop(first);
+        // The returned session is handed out again rather than opening another
+        let first = pool.acquire(soon(), Some(|| panic!("should reuse the idle session"))).unwrap();
+
+        let mut busy = vec![first];
+        for _ in 1..MAX_POOLED_SESSIONS {
+            busy.push(pool.acquire(soon(), Some(|| Ok(Session::new().unwrap()))).unwrap());
+        }
+        assert_eq!(pool.state.lock().unwrap().open, MAX_POOLED_SESSIONS);
+
+        // At the limit the opener isn't called, the caller waits instead
+        let waited = pool.acquire(soon(), Some(|| panic!("pool is full")));
+        assert_eq!(waited.err().unwrap().code, CommandError::TIMEOUT);
+
+        // A discarded session frees its slot
+        busy[0].discard();
+        busy.remove(0);
+        assert_eq!(pool.state.lock().unwrap().open, MAX_POOLED_SESSIONS - 1);
+    }
+
+    #[test]
+    fn test_refused_session_lowers_limit() {
+        let pool = SessionPool::default();
+        pool.reset(Session::new().unwrap());
+        let _busy = pool.acquire(soon(), None::<Opener>).unwrap();
+
+        let refused = pool.acquire(
+            soon(),
+            Some(|| Err(CommandError { message: "refused".to_string(), code: -1 })),
+        );
+        assert_eq!(refused.err().unwrap().code, CommandError::TIMEOUT);
+        let state = pool.state.lock().unwrap();
+        assert_eq!((state.open, state.limit), (1, 1));
+    }
+
+    #[test]
+    fn test_waits_for_returned_session_until_deadline() {
+        let pool = SessionPool::default();
+        pool.reset(Session::new().unwrap());
+        let busy = pool.acquire(soon(), None::<Opener>).unwrap();
+
+        thread::scope(|scope| {
+            scope.spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                drop(busy);
+            });
+            let deadline = Instant::now() + Duration::from_secs(5);
+            assert!(pool.acquire(deadline, None::<Opener>).is_ok());
+        });
+
+        // Sessions checked out before a reconnect aren't put back into the new pool
+        let stale = pool.acquire(soon(), None::<Opener>).unwrap();
+        pool.reset(Session::new().unwrap());
+        drop(stale);
+        assert_eq!(pool.state.lock().unwrap().idle.len(), 1);
+    }
+}
@@ -0,0 +1,334 @@
+use crate::commands::AppState;
+use crate::shell::Cmd;
+use crate::ssh::{CommandCategory, SshClient};
+use crate::types::CommandError;
+use serde::{Deserialize, Serialize, Serializer};
+use ssh2::{ErrorCode, FileStat, FileType, RenameFlags, Sftp};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tauri::State;
+
+// SFTP status codes from draft-ietf-secsh-filexfer-02 (plus OpenSSH extensions)
+const FX_NO_SUCH_FILE: i32 = 2;
+const FX_PERMISSION_DENIED: i32 = 3;
+const FX_FAILURE: i32 = 4;
+const FX_NO_SUCH_PATH: i32 = 10;
+const FX_FILE_ALREADY_EXISTS: i32 = 11;
+const FX_DIR_NOT_EMPTY: i32 = 18;
+const FX_NOT_A_DIRECTORY: i32 = 19;
+
+/// What went wrong with a remote file operation, so the file manager can react to
+/// the cause rather than parsing message text
+#[derive(Debug, thiserror::Error)]
+pub enum SftpError {
+    #[error("Not connected")]
+    NotConnected,
+    #[error("No such file or directory: {0}")]
+    NotFound(String),
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+    #[error("Already exists: {0}")]
+    AlreadyExists(String),
+    #[error("Directory not empty: {0}")]
+    NotEmpty(String),
+    #[error("Not a directory: {0}")]
+    NotADirectory(String),
+    #[error("Transfer cancelled")]
+    Cancelled,
+    #[error("Checksum mismatch for {0}")]
+    ChecksumMismatch(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl SftpError {
+    fn kind(&self) -> &'static str {
+        match self {
+            SftpError::NotConnected => "not_connected",
+            SftpError::NotFound(_) => "not_found",
+            SftpError::PermissionDenied(_) => "permission_denied",
+            SftpError::AlreadyExists(_) => "already_exists",
+            SftpError::NotEmpty(_) => "not_empty",
+            SftpError::NotADirectory(_) => "not_a_directory",
+            SftpError::Cancelled => "cancelled",
+            SftpError::ChecksumMismatch(_) => "checksum_mismatch",
+            SftpError::Other(_) => "other",
+        }
+    }
+
+    /// Classify an ssh2 error for the operation on `path`
+    pub fn from_ssh(e: ssh2::Error, path: &str) -> Self {
+        let path = path.to_string();
+        match e.code() {
+            ErrorCode::SFTP(FX_NO_SUCH_FILE) | ErrorCode::SFTP(FX_NO_SUCH_PATH) => SftpError::NotFound(path),
+            ErrorCode::SFTP(FX_PERMISSION_DENIED) => SftpError::PermissionDenied(path),
+            ErrorCode::SFTP(FX_FILE_ALREADY_EXISTS) => SftpError::AlreadyExists(path),
+            ErrorCode::SFTP(FX_DIR_NOT_EMPTY) => SftpError::NotEmpty(path),
+            ErrorCode::SFTP(FX_NOT_A_DIRECTORY) => SftpError::NotADirectory(path),
+            _ => SftpError::Other(format!("{}: {}", path, e)),
+        }
+    }
+}
+
+impl From<CommandError> for SftpError {
+    fn from(e: CommandError) -> Self {
+        match e.code {
+            CommandError::NOT_CONNECTED => SftpError::NotConnected,
+            CommandError::CANCELLED => SftpError::Cancelled,
+            _ => SftpError::Other(e.message),
+        }
+    }
+}
+
+impl From<String> for SftpError {
+    fn from(message: String) -> Self {
+        SftpError::Other(message)
+    }
+}
+
+/// Serialized as `{ kind, message }` for the frontend
+impl Serialize for SftpError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Payload<'a> {
+            kind: &'a str,
+            message: String,
+        }
+        Payload { kind: self.kind(), message: self.to_string() }.serialize(serializer)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RemoteFileKind {
+    File,
+    Directory,
+    Symlink,
+    Other,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteFileEntry {
+    pub name: String,
+    pub path: String,
+    pub kind: RemoteFileKind,
+    pub size: u64,
+    /// Permission bits, e.g. 0o644
+    pub mode: u32,
+    /// `ls -l` style permission string, e.g. "rwxr-xr-x"
+    pub permissions: String,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    /// Seconds since the Unix epoch
+    pub modified: Option<u64>,
+    pub symlink_target: Option<String>,
+}
+
+fn permission_string(mode: u32) -> String {
+    let bits = [
+        (0o400, 'r'), (0o200, 'w'), (0o100, 'x'),
+        (0o040, 'r'), (0o020, 'w'), (0o010, 'x'),
+        (0o004, 'r'), (0o002, 'w'), (0o001, 'x'),
+    ];
+    bits.iter().map(|&(bit, c)| if mode & bit != 0 { c } else { '-' }).collect()
+}
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+fn entry_from_stat(sftp: &Sftp, path: &Path, stat: &FileStat) -> RemoteFileEntry {
+    let kind = match stat.file_type() {
+        FileType::RegularFile => RemoteFileKind::File,
+        FileType::Directory => RemoteFileKind::Directory,
+        FileType::Symlink => RemoteFileKind::Symlink,
+        _ => RemoteFileKind::Other,
+    };
+    let symlink_target = if kind == RemoteFileKind::Symlink {
+        sftp.readlink(path).ok().map(|target| path_string(&target))
+    } else {
+        None
+    };
+    let mode = stat.perm.unwrap_or(0) & 0o7777;
+
+    RemoteFileEntry {
+        name: path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| path_string(path)),
+        path: path_string(path),
+        kind,
+        size: stat.size.unwrap_or(0),
+        mode,
+        permissions: permission_string(mode),
+        uid: stat.uid,
+        gid: stat.gid,
+        owner: None,
+        group: None,
+        modified: stat.mtime,
+        symlink_target,
+    }
+}
+
+/// Map ids to names with one `getent` call per database
+fn lookup_names(client: &SshClient, database: &'static str, ids: &[u32]) -> HashMap<u32, String> {
+    if ids.is_empty() {
+        return HashMap::new();
+    }
+    let output = client
+        .execute_command(&Cmd::new("getent").raw(database).args(ids.iter().map(|id| id.to_string())))
+        .unwrap_or_default();
+
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            let id = fields.get(2)?.parse().ok()?;
+            Some((id, fields.first()?.to_string()))
+        })
+        .collect()
+}
+
+fn resolve_owners(client: &SshClient, entries: &mut [RemoteFileEntry]) {
+    let mut uids: Vec<u32> = entries.iter().filter_map(|e| e.uid).collect();
+    let mut gids: Vec<u32> = entries.iter().filter_map(|e| e.gid).collect();
+    uids.sort_unstable();
+    uids.dedup();
+    gids.sort_unstable();
+    gids.dedup();
+
+    let users = lookup_names(client, "passwd", &uids);
+    let groups = lookup_names(client, "group", &gids);
+    for entry in entries {
+        entry.owner = entry.uid.and_then(|uid| users.get(&uid).cloned());
+        entry.group = entry.gid.and_then(|gid| groups.get(&gid).cloned());
+    }
+}
+
+/// Run `f` with an SFTP subsystem on a pooled session
+pub fn with_sftp<T>(client: &SshClient, f: impl FnOnce(&Sftp) -> Result<T, SftpError>) -> Result<T, SftpError> {
+    client.with_session(CommandCategory::Query.timeout(), |session| {
+        let sftp = session
+            .sftp()
+            .map_err(|e| SftpError::Other(format!("Failed to start SFTP: {}", e)))?;
+        f(&sftp)
+    })
+}
+
+fn delete_recursive(sftp: &Sftp, path: &Path) -> Result<(), SftpError> {
+    let display = path_string(path);
+    let stat = sftp.lstat(path).map_err(|e| SftpError::from_ssh(e, &display))?;
+    if !stat.is_dir() {
+        return sftp.unlink(path).map_err(|e| SftpError::from_ssh(e, &display));
+    }
+
+    for (child, _) in sftp.readdir(path).map_err(|e| SftpError::from_ssh(e, &display))? {
+        delete_recursive(sftp, &child)?;
+    }
+    sftp.rmdir(path).map_err(|e| SftpError::from_ssh(e, &display))
+}
+
+/// List a remote directory, directories first, then by name
+#[tauri::command]
+pub async fn list_remote_directory(path: String, server_id: Option<String>, state: State<'_, AppState>) -> Result<Vec<RemoteFileEntry>, SftpError> {
+    let client = state.client(server_id.as_deref()).await.map_err(|_| SftpError::NotConnected)?;
+    client.run_blocking(move |client| {
+        let mut entries = with_sftp(client, |sftp| {
+            let listing = sftp.readdir(Path::new(&path)).map_err(|e| SftpError::from_ssh(e, &path))?;
+            Ok(listing
+                .iter()
+                .map(|(child, stat)| entry_from_stat(sftp, child, stat))
+                .collect::<Vec<_>>())
+        })?;
+
+        resolve_owners(client, &mut entries);
+        entries.sort_by(|a, b| {
+            (b.kind == RemoteFileKind::Directory)
+                .cmp(&(a.kind == RemoteFileKind::Directory))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        Ok(entries)
+    })
+    .await
+}
+
+/// Describe one remote path; symlinks are reported as links with their target
+#[tauri::command]
+pub async fn stat_remote_path(path: String, server_id: Option<String>, state: State<'_, AppState>) -> Result<RemoteFileEntry, SftpError> {
+    let client = state.client(server_id.as_deref()).await.map_err(|_| SftpError::NotConnected)?;
+    client.run_blocking(move |client| {
+        let mut entry = with_sftp(client, |sftp| {
+            let remote = Path::new(&path);
+            let stat = sftp.lstat(remote).map_err(|e| SftpError::from_ssh(e, &path))?;
+            Ok(entry_from_stat(sftp, remote, &stat))
+        })?;
+
+        resolve_owners(client, std::slice::from_mut(&mut entry));
+        Ok(entry)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn create_remote_directory(path: String, mode: Option<u32>, server_id: Option<String>, state: State<'_, AppState>) -> Result<(), SftpError> {
+    let client = state.client(server_id.as_deref()).await.map_err(|_| SftpError::NotConnected)?;
+    client.run_blocking(move |client| {
+        with_sftp(client, |sftp| {
+            sftp.mkdir(Path::new(&path), mode.unwrap_or(0o755) as i32)
+                .map_err(|e| SftpError::from_ssh(e, &path))
+        })
+    })
+    .await
+}
+
+/// Rename or move a remote path; an existing destination is only replaced with `overwrite`
+#[tauri::command]
+pub async fn rename_remote_path(
+    from: String,
+    to: String,
+    overwrite: Option<bool>,
+    server_id: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), SftpError> {
+    let client = state.client(server_id.as_deref()).await.map_err(|_| SftpError::NotConnected)?;
+    client.run_blocking(move |client| {
+        with_sftp(client, |sftp| {
+            let destination = PathBuf::from(&to);
+            if !overwrite.unwrap_or(false) && sftp.lstat(&destination).is_ok() {
+                return Err(SftpError::AlreadyExists(to.clone()));
+            }
+            let flags = RenameFlags::OVERWRITE | RenameFlags::ATOMIC | RenameFlags::NATIVE;
+            sftp.rename(Path::new(&from), &destination, Some(flags))
+                .map_err(|e| SftpError::from_ssh(e, &from))
+        })
+    })
+    .await
+}
+
+/// Delete a file, symlink or directory; non-empty directories need `recursive`
+#[tauri::command]
+pub async fn delete_remote_path(path: String, recursive: Option<bool>, server_id: Option<String>, state: State<'_, AppState>) -> Result<(), SftpError> {
+    let client = state.client(server_id.as_deref()).await.map_err(|_| SftpError::NotConnected)?;
+    client.run_blocking(move |client| {
+        with_sftp(client, |sftp| {
+            let remote = Path::new(&path);
+            if recursive.unwrap_or(false) {
+                return delete_recursive(sftp, remote);
+            }
+
+            let stat = sftp.lstat(remote).map_err(|e| SftpError::from_ssh(e, &path))?;
+            if stat.is_dir() {
+                sftp.rmdir(remote).map_err(|e| {
+                    // OpenSSH reports a non-empty directory as a generic failure
+                    if e.code() == ErrorCode::SFTP(FX_FAILURE) {
+                        SftpError::NotEmpty(path.clone())
+                    } else {
+                        SftpError::from_ssh(e, &path)
+                    }
+                })
+            } else {
+                sftp.unlink(remote).map_err(|e| SftpError::from_ssh(e, &path))
+            }
+        })
+    })
+    .await
+}
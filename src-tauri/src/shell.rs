@@ -0,0 +1,232 @@
+//! Building command lines for the remote login shell. Anything that came from the
+//! frontend or from server output goes through `Cmd::arg`, which quotes it; only string
+//! literals can be spliced in unquoted. The validators below reject identifiers that
+//! would be misread by the program receiving them, e.g. a "name" starting with `-`.
+
+use std::fmt;
+use std::net::IpAddr;
+use std::ops::Deref;
+
+/// Quote `value` as a single POSIX shell word, whatever characters it contains
+pub fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// A shell command line built from fixed fragments and quoted arguments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cmd {
+    line: String,
+}
+
+impl Cmd {
+    pub fn new(program: &'static str) -> Self {
+        Cmd { line: program.to_string() }
+    }
+
+    /// `program` run as root through sudo
+    pub fn sudo(program: &'static str) -> Self {
+        Cmd { line: format!("sudo {}", program) }
+    }
+
+    /// Append one argument, quoted
+    pub fn arg(mut self, value: impl AsRef<str>) -> Self {
+        self.line.push(' ');
+        self.line.push_str(&quote(value.as_ref()));
+        self
+    }
+
+    pub fn args<I>(mut self, values: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        for value in values {
+            self = self.arg(value);
+        }
+        self
+    }
+
+    /// Append a fixed fragment verbatim: a flag, an allowlisted keyword or a redirection
+    pub fn raw(mut self, fragment: &'static str) -> Self {
+        self.line.push(' ');
+        self.line.push_str(fragment);
+        self
+    }
+
+    /// `self | next`
+    pub fn pipe(self, next: Cmd) -> Self {
+        self.join("|", next)
+    }
+
+    /// `self && next`
+    pub fn and(self, next: Cmd) -> Self {
+        self.join("&&", next)
+    }
+
+    /// `self || next`
+    pub fn or(self, next: Cmd) -> Self {
+        self.join("||", next)
+    }
+
+    fn join(mut self, operator: &str, next: Cmd) -> Self {
+        self.line = format!("{} {} {}", self.line, operator, next.line);
+        self
+    }
+}
+
+impl Deref for Cmd {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.line
+    }
+}
+
+impl fmt::Display for Cmd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.line)
+    }
+}
+
+fn invalid(what: &str, value: &str) -> String {
+    format!("Invalid {}: '{}'", what, value)
+}
+
+/// A login or group name as accepted by useradd/groupadd
+pub fn username(value: &str) -> Result<&str, String> {
+    let body = value.strip_suffix('$').unwrap_or(value);
+    let valid = !body.is_empty()
+        && value.len() <= 32
+        && body.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && body.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if valid {
+        Ok(value)
+    } else {
+        Err(invalid("user or group name", value))
+    }
+}
+
+/// A systemd unit name such as `nginx`, `getty@tty1.service` or `dev-sda1.mount`
+pub fn unit_name(value: &str) -> Result<&str, String> {
+    let valid = !value.is_empty()
+        && value.len() <= 256
+        && !value.starts_with('-')
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, ':' | '_' | '.' | '@' | '-' | '\\'));
+    if valid {
+        Ok(value)
+    } else {
+        Err(invalid("unit name", value))
+    }
+}
+
+/// A Docker container, network or volume name, or an object id
+pub fn docker_name(value: &str) -> Result<&str, String> {
+    let valid = value.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'));
+    if valid {
+        Ok(value)
+    } else {
+        Err(invalid("container name", value))
+    }
+}
+
+/// A single path component, e.g. a vhost or cron.d file name
+pub fn file_name(value: &str) -> Result<&str, String> {
+    let valid = !value.is_empty()
+        && value != "."
+        && value != ".."
+        && !value.starts_with('-')
+        && !value.chars().any(|c| c == '/' || c.is_control());
+    if valid {
+        Ok(value)
+    } else {
+        Err(invalid("file name", value))
+    }
+}
+
+/// A port, a `low:high` range, or a comma separated list of those
+pub fn port(value: &str) -> Result<&str, String> {
+    let in_range = |p: &str| p.parse::<u16>().is_ok_and(|p| p > 0) && p.chars().all(|c| c.is_ascii_digit());
+    let valid = value.split(',').all(|part| match part.split_once(':') {
+        Some((low, high)) => in_range(low) && in_range(high),
+        None => in_range(part),
+    });
+    if valid {
+        Ok(value)
+    } else {
+        Err(invalid("port", value))
+    }
+}
+
+/// An IPv4/IPv6 address, optionally with a `/prefix`
+pub fn address(value: &str) -> Result<&str, String> {
+    let (ip, prefix) = match value.split_once('/') {
+        Some((ip, prefix)) => (ip, Some(prefix)),
+        None => (value, None),
+    };
+    let valid = match ip.parse::<IpAddr>() {
+        Ok(ip) => {
+            let max = if ip.is_ipv4() { 32 } else { 128 };
+            prefix.is_none_or(|p| p.parse::<u8>().is_ok_and(|p| p <= max))
+        }
+        Err(_) => false,
+    };
+    if valid {
+        Ok(value)
+    } else {
+        Err(invalid("address", value))
+    }
+}
+
+/// Text that has to stay on a single line, e.g. one crontab entry or a password fed to chpasswd
+pub fn single_line(what: &str, value: &str) -> Result<(), String> {
+    if value.contains(['\n', '\r', '\0']) {
+        Err(format!("{} must not contain line breaks", what))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quotes_every_argument() {
+        let cmd = Cmd::sudo("systemctl")
+            .raw("restart")
+            .arg("it's; rm -rf /")
+            .pipe(Cmd::new("tail").raw("-n").arg("5"));
+        assert_eq!(&*cmd, r"sudo systemctl restart 'it'\''s; rm -rf /' | tail -n '5'");
+    }
+
+    #[test]
+    fn test_validates_identifiers() {
+        assert!(username("deploy").is_ok());
+        assert!(username("machine$").is_ok());
+        assert!(username("-oProxyCommand").is_err());
+        assert!(username("a b").is_err());
+
+        assert!(unit_name("getty@tty1.service").is_ok());
+        assert!(unit_name("--now").is_err());
+        assert!(unit_name("nginx;reboot").is_err());
+
+        assert!(docker_name("web_1").is_ok());
+        assert!(docker_name("-f").is_err());
+
+        assert!(file_name("example.com").is_ok());
+        assert!(file_name("../nginx.conf").is_err());
+        assert!(file_name("..").is_err());
+
+        assert!(port("443").is_ok());
+        assert!(port("8000:8010,9000").is_ok());
+        assert!(port("0").is_err());
+        assert!(port("70000").is_err());
+        assert!(port("+22").is_err());
+
+        assert!(address("10.0.0.0/8").is_ok());
+        assert!(address("::1").is_ok());
+        assert!(address("10.0.0.1/33").is_err());
+        assert!(address("any; reboot").is_err());
+    }
+}
@@ -1,131 +1,811 @@
+use crate::known_hosts;
+use crate::privilege;
+use crate::session_pool::{PooledSession, SessionPool};
+use crate::tunnel;
 use crate::types::*;
-use ssh2::Session;
-use std::net::TcpStream;
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use ssh2::{Agent, KeyboardInteractivePrompt, Prompt, PtyModeOpcode, PtyModes, PublicKey, Session};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::io::Read;
+use std::io::{Read, Write};
 use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// libssh2's LIBSSH2_ERROR_TIMEOUT
+const LIBSSH2_ERROR_TIMEOUT: i32 = -9;
+const READ_POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// How long to wait for the server to acknowledge closing an abandoned channel
+const CHANNEL_CLOSE_TIMEOUT_MS: u32 = 2000;
+/// Interval libssh2 uses when asked to send keepalives
+const KEEPALIVE_INTERVAL_SECS: u32 = 20;
+/// How long a liveness probe may take before the connection is considered dead
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long opening the TCP connection may take, per resolved address; the OS default
+/// is minutes when a host is down
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the server may take to complete the SSH handshake
+const HANDSHAKE_TIMEOUT_MS: u32 = 15_000;
+
+/// Default timeouts by kind of remote command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandCategory {
+    /// Reads and status checks
+    Query,
+    /// Service, container and config changes
+    Action,
+    /// Filesystem scans and other slow lookups
+    Scan,
+    /// Log tails and other output that may keep coming
+    Stream,
+}
+
+impl CommandCategory {
+    pub fn timeout(self) -> Duration {
+        match self {
+            CommandCategory::Query => Duration::from_secs(30),
+            CommandCategory::Action => Duration::from_secs(120),
+            CommandCategory::Scan => Duration::from_secs(300),
+            CommandCategory::Stream => Duration::from_secs(600),
+        }
+    }
+}
+
+/// Shared flag used to abort a running command from another task
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// OpenSSH-style SHA256 fingerprint of a public key blob
+pub fn key_fingerprint(blob: &[u8]) -> String {
+    format!("SHA256:{}", STANDARD_NO_PAD.encode(Sha256::digest(blob)))
+}
+
+/// Answers keyboard-interactive challenges, typically by asking the user.
+/// Returning `None` cancels the authentication attempt.
+pub trait ChallengeResponder: Send + Sync {
+    fn respond(&self, challenge: &AuthChallenge) -> Option<Vec<String>>;
+}
+
+struct InteractivePrompter<'a> {
+    responder: Option<&'a dyn ChallengeResponder>,
+    /// Stored password, offered once for a lone password prompt
+    password: Option<&'a str>,
+    cancelled: bool,
+    /// Whether any prompt had to be shown to the user
+    relayed: bool,
+}
+
+impl KeyboardInteractivePrompt for InteractivePrompter<'_> {
+    fn prompt<'b>(&mut self, username: &str, instructions: &str, prompts: &[Prompt<'b>]) -> Vec<String> {
+        if prompts.is_empty() || self.cancelled {
+            return Vec::new();
+        }
+
+        if prompts.len() == 1 && !prompts[0].echo && prompts[0].text.to_lowercase().contains("password") {
+            if let Some(password) = self.password.take() {
+                return vec![password.to_string()];
+            }
+        }
+
+        let challenge = AuthChallenge {
+            username: username.to_string(),
+            instructions: instructions.to_string(),
+            prompts: prompts
+                .iter()
+                .map(|p| AuthPrompt { text: p.text.to_string(), echo: p.echo })
+                .collect(),
+        };
+
+        self.relayed = true;
+        match self.responder.and_then(|r| r.respond(&challenge)) {
+            Some(answers) => answers,
+            None => {
+                self.cancelled = true;
+                Vec::new()
+            }
+        }
+    }
+}
 
 pub struct SshClient {
     config: ServerProfile,
-    session: Arc<Mutex<Option<Session>>>,
+    sessions: SessionPool,
+    /// Login needed answers from the user, so extra sessions can't be opened unattended
+    interactive: AtomicBool,
+    /// Set by `disconnect`, so a dropped connection isn't brought back
+    closed: AtomicBool,
+    host_key: Mutex<Option<HostKeyInfo>>,
+    failed_hop: Mutex<Option<usize>>,
+    responder: Option<Arc<dyn ChallengeResponder>>,
 }
 
 impl SshClient {
     pub fn new(config: ServerProfile) -> Self {
         SshClient {
             config,
-            session: Arc::new(Mutex::new(None)),
+            sessions: SessionPool::default(),
+            interactive: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+            host_key: Mutex::new(None),
+            failed_hop: Mutex::new(None),
+            responder: None,
         }
     }
 
-    fn expand_tilde(path: &str) -> String {
-        if path.starts_with("~/") {
-            if let Ok(home) = std::env::var("HOME") {
-                return format!("{}/{}", home, &path[2..]);
+    /// Relay keyboard-interactive prompts (OTP codes, PAM challenges) through `responder`
+    pub fn with_challenge_responder(mut self, responder: Arc<dyn ChallengeResponder>) -> Self {
+        self.responder = Some(responder);
+        self
+    }
+
+    /// Open a TCP connection and complete the SSH handshake, without authenticating
+    pub fn start_session(host: &str, port: u16) -> Result<Session, CommandError> {
+        let failed = |e: std::io::Error| CommandError {
+            message: format!("Failed to connect to {}:{}: {}", host, port, e),
+            code: -1,
+        };
+        let mut last_error = None;
+        for addr in (host, port).to_socket_addrs().map_err(failed)? {
+            match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+                Ok(tcp) => return Self::handshake(tcp),
+                Err(e) => last_error = Some(e),
             }
         }
-        path.to_string()
+        Err(failed(last_error.unwrap_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, "no addresses found")
+        })))
     }
 
-    pub fn connect(&self) -> Result<(), CommandError> {
-        let tcp = TcpStream::connect(format!("{}:{}", self.config.host, self.config.port))
-            .map_err(|e| CommandError {
-                message: format!("Failed to connect to {}:{}: {}", self.config.host, self.config.port, e),
-                code: -1,
-            })?;
-
+    /// Complete the SSH handshake over an already connected stream
+    fn handshake(tcp: TcpStream) -> Result<Session, CommandError> {
         let mut session = Session::new().map_err(|e| CommandError {
             message: format!("Failed to create SSH session: {}", e),
             code: -1,
         })?;
-        
+
         session.set_tcp_stream(tcp);
-        
+
         // Use blocking mode for simplicity
         session.set_blocking(true);
 
-        // Handshake
+        // Handshake; a server that accepts the connection but never answers fails here
+        // instead of hanging. Authentication may wait on the user, so it has no limit.
+        session.set_timeout(HANDSHAKE_TIMEOUT_MS);
         session.handshake().map_err(|e| CommandError {
             message: format!("SSH handshake failed: {}", e),
             code: -1,
         })?;
+        session.set_timeout(0);
 
-        // Authenticate based on auth method
-        match &self.config.auth_method {
+        Ok(session)
+    }
+
+    /// Connect to `host:port` directly, or through a `direct-tcpip` channel on `via`
+    fn start_session_via(via: Option<Session>, host: &str, port: u16) -> Result<Session, CommandError> {
+        match via {
+            None => Self::start_session(host, port),
+            Some(jump) => Self::handshake(tunnel::open_direct_tcpip(jump, host, port)?),
+        }
+    }
+
+    /// Hop label used in error messages once a jump chain is involved
+    fn hop_label(&self, index: usize) -> String {
+        match self.config.jump_hosts.get(index) {
+            Some(jump) => format!("Jump host {} ({}@{}:{})", index + 1, jump.username, jump.host, jump.port),
+            None => format!("Target {}@{}:{}", self.config.username, self.config.host, self.config.port),
+        }
+    }
+
+    fn hop_failed(&self, index: usize, e: CommandError) -> CommandError {
+        *self.failed_hop.lock().unwrap() = Some(index);
+        if self.config.jump_hosts.is_empty() {
+            e
+        } else {
+            CommandError {
+                message: format!("{}: {}", self.hop_label(index), e.message),
+                code: e.code,
+            }
+        }
+    }
+
+    /// Handshaken but unauthenticated session to the target, after logging in to
+    /// every jump host in order
+    pub fn open_transport(&self) -> Result<Session, CommandError> {
+        *self.failed_hop.lock().unwrap() = None;
+
+        let mut via = None;
+        for (index, jump) in self.config.jump_hosts.iter().enumerate() {
+            let session = Self::start_session_via(via.take(), &jump.host, jump.port)
+                .and_then(|session| {
+                    self.check_host_key(&session, &jump.host, jump.port)?;
+                    self.login(&session, &jump.username, &jump.auth_method)?;
+                    Ok(session)
+                })
+                .map_err(|e| self.hop_failed(index, e))?;
+            via = Some(session);
+        }
+
+        Self::start_session_via(via, &self.config.host, self.config.port)
+            .map_err(|e| self.hop_failed(self.config.jump_hosts.len(), e))
+    }
+
+    /// Refuse to continue unless the server's host key is already trusted
+    fn check_host_key(&self, session: &Session, host: &str, port: u16) -> Result<(), CommandError> {
+        let info = known_hosts::verify_host_key(session, host, port)?;
+        *self.host_key.lock().unwrap() = Some(info.clone());
+
+        match info.status {
+            HostKeyStatus::Trusted => Ok(()),
+            HostKeyStatus::Unknown => Err(CommandError {
+                message: format!(
+                    "The authenticity of host '{}:{}' can't be established. {} key fingerprint is {}.",
+                    info.host, info.port, info.key_type, info.fingerprint
+                ),
+                code: CommandError::HOST_KEY_UNKNOWN,
+            }),
+            HostKeyStatus::Changed => Err(CommandError {
+                message: format!(
+                    "Host key for '{}:{}' has changed (now {} {}). Someone could be intercepting the connection, so it was refused. Remove the old key from known_hosts if the change is expected.",
+                    info.host, info.port, info.key_type, info.fingerprint
+                ),
+                code: CommandError::HOST_KEY_CHANGED,
+            }),
+        }
+    }
+
+    /// Host key seen during the last connection attempt
+    pub fn host_key(&self) -> Option<HostKeyInfo> {
+        self.host_key.lock().unwrap().clone()
+    }
+
+    /// Index of the hop that failed during the last connection attempt
+    /// (jump hosts first, then the target)
+    pub fn failed_hop(&self) -> Option<usize> {
+        *self.failed_hop.lock().unwrap()
+    }
+
+    fn expand_tilde(path: &str) -> String {
+        if path.starts_with("~/") {
+            if let Ok(home) = std::env::var("HOME") {
+                return format!("{}/{}", home, &path[2..]);
+            }
+        }
+        path.to_string()
+    }
+
+    pub fn connect(&self) -> Result<(), CommandError> {
+        self.interactive.store(false, Ordering::SeqCst);
+        let session = self.open_authenticated()?;
+        if self.is_closed() {
+            return Err(CommandError {
+                message: "Disconnected".to_string(),
+                code: -1,
+            });
+        }
+        self.sessions.reset(session);
+        Ok(())
+    }
+
+    /// Open an extra authenticated session outside the pool, for long-lived channels
+    /// like terminals that would otherwise hold a pooled session indefinitely
+    pub fn open_dedicated_session(&self) -> Result<Session, CommandError> {
+        if !self.is_connected() {
+            return Err(CommandError::not_connected());
+        }
+        if self.interactive.load(Ordering::SeqCst) {
+            return Err(CommandError {
+                message: "This server's login needs answers from you, so another session can't be opened automatically".to_string(),
+                code: -1,
+            });
+        }
+        self.open_authenticated()
+    }
+
+    /// Open a fully authenticated session to the target, through any jump hosts
+    fn open_authenticated(&self) -> Result<Session, CommandError> {
+        let session = self.open_transport()?;
+        let target = self.config.jump_hosts.len();
+
+        // Verify the server before sending any credentials
+        self.check_host_key(&session, &self.config.host, self.config.port)
+            .and_then(|_| self.login(&session, &self.config.username, &self.config.auth_method))
+            .map_err(|e| self.hop_failed(target, e))?;
+
+        session.set_keepalive(true, KEEPALIVE_INTERVAL_SECS);
+        Ok(session)
+    }
+
+    /// Authenticate one hop, following up with keyboard-interactive when the server asks for more
+    fn login(&self, session: &Session, username: &str, auth_method: &AuthMethod) -> Result<(), CommandError> {
+        let primary = self.authenticate(session, username, auth_method);
+
+        // The server may want a second factor after a partial success (e.g. publickey
+        // followed by an OTP), or only offer keyboard-interactive in the first place
+        if !session.authenticated() && matches!(auth_method, AuthMethod::KeyboardInteractive) {
+            // The prompt's own failure, or the user cancelling, says more than the generic error below
+            primary?;
+        } else if !session.authenticated() {
+            let methods = session.auth_methods(username).unwrap_or("").to_string();
+            if methods.split(',').any(|m| m == "keyboard-interactive") {
+                self.authenticate_interactive(session, username, auth_method).map_err(|e| match &primary {
+                    Err(first) => CommandError {
+                        message: format!("{}; {}", first.message, e.message),
+                        code: e.code,
+                    },
+                    Ok(()) => e,
+                })?;
+            } else {
+                primary?;
+            }
+        }
+
+        // Verify authentication succeeded
+        if !session.authenticated() {
+            return Err(CommandError {
+                message: "SSH authentication failed".to_string(),
+                code: -1,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Authenticate based on auth method
+    fn authenticate(&self, session: &Session, username: &str, auth_method: &AuthMethod) -> Result<(), CommandError> {
+        match auth_method {
             AuthMethod::Password { password } => {
-                session.userauth_password(&self.config.username, password)
+                session.userauth_password(username, password)
                     .map_err(|e| CommandError {
                         message: format!("Password authentication failed: {}", e),
                         code: -1,
-                    })?;
+                    })
             }
             AuthMethod::PrivateKey { key_path, passphrase } => {
                 let expanded_path = Self::expand_tilde(key_path);
                 let path_ref = Path::new(&expanded_path);
-                
-                if let Some(pass) = passphrase {
-                    session.userauth_pubkey_file(&self.config.username, None, path_ref, Some(pass))
-                        .map_err(|e| CommandError {
-                            message: format!("Key authentication failed: {}", e),
-                            code: -1,
-                        })?;
-                } else {
-                    session.userauth_pubkey_file(&self.config.username, None, path_ref, None)
-                        .map_err(|e| CommandError {
-                            message: format!("Key authentication failed: {}", e),
-                            code: -1,
-                        })?;
-                }
+
+                session.userauth_pubkey_file(username, None, path_ref, passphrase.as_deref())
+                    .map_err(|e| CommandError {
+                        message: format!("Key authentication failed: {}", e),
+                        code: -1,
+                    })
             }
+            AuthMethod::Agent { identity } => {
+                Self::authenticate_with_agent(session, username, identity.as_deref())
+            }
+            AuthMethod::KeyboardInteractive => self.authenticate_interactive(session, username, auth_method),
         }
+    }
 
-        // Verify authentication succeeded
-        if !session.authenticated() {
+    /// Run a keyboard-interactive exchange, relaying prompts through the challenge responder
+    fn authenticate_interactive(&self, session: &Session, username: &str, auth_method: &AuthMethod) -> Result<(), CommandError> {
+        let password = match auth_method {
+            AuthMethod::Password { password } => Some(password.as_str()),
+            _ => None,
+        };
+        // Only the initial connection may ask the user; pooled sessions must log in unattended
+        let responder = if self.sessions.is_open() { None } else { self.responder.as_deref() };
+        let mut prompter = InteractivePrompter {
+            responder,
+            password,
+            cancelled: false,
+            relayed: false,
+        };
+
+        let result = session.userauth_keyboard_interactive(username, &mut prompter);
+        if prompter.relayed {
+            self.interactive.store(true, Ordering::SeqCst);
+        }
+        if prompter.cancelled {
             return Err(CommandError {
-                message: "SSH authentication failed".to_string(),
+                message: "Keyboard-interactive authentication was cancelled".to_string(),
                 code: -1,
             });
         }
+        result.map_err(|e| CommandError {
+            message: format!("Keyboard-interactive authentication failed: {}", e),
+            code: -1,
+        })
+    }
 
-        let mut session_guard = self.session.lock().unwrap();
-        *session_guard = Some(session);
-
-        Ok(())
+    fn connect_agent(session: &Session) -> Result<Agent, CommandError> {
+        let mut agent = session.agent().map_err(|e| CommandError {
+            message: format!("Failed to initialise ssh-agent: {}", e),
+            code: -1,
+        })?;
+        agent.connect().map_err(|e| CommandError {
+            message: format!("Failed to connect to ssh-agent (is SSH_AUTH_SOCK set?): {}", e),
+            code: -1,
+        })?;
+        agent.list_identities().map_err(|e| CommandError {
+            message: format!("Failed to list ssh-agent identities: {}", e),
+            code: -1,
+        })?;
+        Ok(agent)
     }
 
-    pub fn disconnect(&self) {
-        let mut session_guard = self.session.lock().unwrap();
-        if let Some(_session) = session_guard.take() {
-            // Session will be dropped and connection closed automatically
-            // The ssh2 library handles cleanup on drop
+    fn identity_matches(key: &PublicKey, wanted: &str) -> bool {
+        let wanted = wanted.trim();
+        if key.comment() == wanted {
+            return true;
         }
+        let fingerprint = key_fingerprint(key.blob());
+        fingerprint == wanted || fingerprint.trim_start_matches("SHA256:") == wanted
     }
 
-    pub fn execute_command(&self, command: &str) -> Result<String, CommandError> {
-        let session_guard = self.session.lock().unwrap();
-        let session = session_guard.as_ref().ok_or_else(|| CommandError {
-            message: "Not connected".to_string(),
+    /// Try the agent's identities in order, or only the one matching `identity`
+    fn authenticate_with_agent(session: &Session, username: &str, identity: Option<&str>) -> Result<(), CommandError> {
+        let mut agent = Self::connect_agent(session)?;
+        let identities = agent.identities().map_err(|e| CommandError {
+            message: format!("Failed to read ssh-agent identities: {}", e),
             code: -1,
         })?;
 
-        let mut channel = session.channel_session().map_err(|e| CommandError {
-            message: format!("Failed to open channel: {}", e),
+        let candidates: Vec<&PublicKey> = identities
+            .iter()
+            .filter(|key| identity.is_none_or(|wanted| Self::identity_matches(key, wanted)))
+            .collect();
+
+        if candidates.is_empty() {
+            let _ = agent.disconnect();
+            return Err(CommandError {
+                message: match identity {
+                    Some(wanted) => format!("No ssh-agent identity matches '{}'", wanted),
+                    None => "ssh-agent has no identities loaded".to_string(),
+                },
+                code: -1,
+            });
+        }
+
+        let mut last_error = None;
+        for key in candidates {
+            match agent.userauth(username, key) {
+                Ok(()) => {
+                    let _ = agent.disconnect();
+                    return Ok(());
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        let _ = agent.disconnect();
+        Err(CommandError {
+            message: format!(
+                "Agent authentication failed: {}",
+                last_error.map(|e| e.to_string()).unwrap_or_default()
+            ),
             code: -1,
-        })?;
+        })
+    }
 
-        channel.exec(command).map_err(|e| CommandError {
-            message: format!("Failed to execute command: {}", e),
+    /// Identities currently offered by the local ssh-agent
+    pub fn agent_identities() -> Result<Vec<AgentIdentity>, CommandError> {
+        let session = Session::new().map_err(|e| CommandError {
+            message: format!("Failed to create SSH session: {}", e),
             code: -1,
         })?;
-
-        let mut output = String::new();
-        let stderr = String::new();
-        
-        // Read stdout
-        channel.read_to_string(&mut output).map_err(|e| CommandError {
-            message: format!("Failed to read output: {}", e),
+        let mut agent = Self::connect_agent(&session)?;
+        let identities = agent.identities().map_err(|e| CommandError {
+            message: format!("Failed to read ssh-agent identities: {}", e),
             code: -1,
         })?;
+        let _ = agent.disconnect();
+
+        Ok(identities
+            .iter()
+            .map(|key| AgentIdentity {
+                comment: key.comment().to_string(),
+                fingerprint: key_fingerprint(key.blob()),
+            })
+            .collect())
+    }
+
+    pub fn disconnect(&self) {
+        // Sessions are dropped and connections closed automatically
+        // The ssh2 library handles cleanup on drop
+        self.closed.store(true, Ordering::SeqCst);
+        self.sessions.close();
+    }
+
+    /// Whether `disconnect` was called, as opposed to the connection dropping
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// Run `job` on the blocking thread pool. Every SSH call blocks until the server
+    /// answers (or a pooled session frees up), so commands must not make them on an
+    /// async worker.
+    pub async fn run_blocking<T, E, F>(self: &Arc<Self>, job: F) -> Result<T, E>
+    where
+        T: Send + 'static,
+        E: From<String> + Send + 'static,
+        F: FnOnce(&SshClient) -> Result<T, E> + Send + 'static,
+    {
+        let client = Arc::clone(self);
+        tokio::task::spawn_blocking(move || job(&client))
+            .await
+            .unwrap_or_else(|e| Err(E::from(format!("SSH task failed: {}", e))))
+    }
+
+    /// Send keepalives on idle sessions and make sure the server still answers.
+    ///
+    /// If it doesn't, every session is dropped so `is_connected` turns false and the
+    /// connection can be re-established. Busy sessions are left to their commands.
+    pub fn check_alive(&self) -> bool {
+        let mut idle = self.sessions.take_idle();
+        for session in idle.iter_mut() {
+            if session.keepalive_send().is_err() {
+                session.discard();
+            }
+        }
+
+        let alive = match idle.first_mut() {
+            Some(session) => {
+                let deadline = Instant::now() + PROBE_TIMEOUT;
+                session.set_timeout(PROBE_TIMEOUT.as_millis() as u32);
+                let result = Self::run_channel(session, &privilege::Escalated::plain("true"), PROBE_TIMEOUT, deadline, None);
+                session.set_blocking(true);
+                session.set_timeout(0);
+                result.is_ok()
+            }
+            None => self.sessions.is_open(),
+        };
+
+        if !alive {
+            log::warn!("SSH connection to {} stopped responding", self.config.host);
+            drop(idle);
+            self.sessions.close();
+        }
+        alive
+    }
+
+    /// Run a command with the default timeout for quick queries
+    pub fn execute_command(&self, command: &str) -> Result<String, CommandError> {
+        self.execute_command_with(command, CommandCategory::Query.timeout(), None)
+    }
+
+    /// Run a command and return its stdout, tolerating non-zero exits.
+    ///
+    /// Meant for reads with shell fallbacks (`|| echo ...`, `grep` with no matches);
+    /// it only fails when the command exits non-zero with nothing but stderr.
+    pub fn execute_command_with(
+        &self,
+        command: &str,
+        timeout: Duration,
+        cancel: Option<&CancelToken>,
+    ) -> Result<String, CommandError> {
+        let output = self.run_command(command, timeout, cancel)?;
+        if !output.success() && output.stdout.is_empty() && !output.stderr.is_empty() {
+            Err(CommandError {
+                message: output.stderr.trim_end().to_string(),
+                code: output.exit_code,
+            })
+        } else {
+            Ok(output.stdout)
+        }
+    }
+
+    /// Run a command that must succeed; a non-zero exit becomes an error carrying stderr
+    pub fn execute_checked(&self, command: &str, category: CommandCategory) -> Result<String, CommandError> {
+        self.run_command(command, category.timeout(), None)?.into_result()
+    }
+
+    /// Run `f` on a pooled session, for work like SFTP that doesn't fit `run_command`.
+    /// Blocking calls inside `f` fail once `timeout` passes without progress.
+    pub fn with_session<T, E>(&self, timeout: Duration, f: impl FnOnce(&Session) -> Result<T, E>) -> Result<T, E>
+    where
+        E: From<CommandError>,
+    {
+        let open_more = (!self.interactive.load(Ordering::SeqCst)).then_some(|| self.open_authenticated());
+        let session = self.sessions.acquire(Instant::now() + timeout, open_more)?;
+
+        session.set_timeout(timeout.as_millis().min(u32::MAX as u128) as u32);
+        let result = f(&session);
+        session.set_timeout(0);
+        result
+    }
+
+    /// Run a command, giving up after `timeout` or once `cancel` is triggered.
+    ///
+    /// Returns stdout, stderr and the exit code whatever the exit status. On timeout or
+    /// cancellation the channel is closed and an error with `CommandError::TIMEOUT` or
+    /// `CommandError::CANCELLED` is returned.
+    pub fn run_command(
+        &self,
+        command: &str,
+        timeout: Duration,
+        cancel: Option<&CancelToken>,
+    ) -> Result<CommandOutput, CommandError> {
+        let started = Instant::now();
+        let deadline = started + timeout;
+
+        // Grow the pool only when logging in again doesn't need the user
+        let open_more = (!self.interactive.load(Ordering::SeqCst)).then_some(|| self.open_authenticated());
+        // `sudo` is rewritten to the profile's escalation, which may need a password on stdin
+        let escalated = privilege::escalate(&self.config, command)?;
+        let mut session = self.sessions.acquire(deadline, open_more)?;
+
+        // Bounds the blocking channel setup below; reads are polled against the deadline
+        session.set_timeout(timeout.as_millis().min(u32::MAX as u128) as u32);
+        let result = Self::run_channel(&mut session, &escalated, timeout, deadline, cancel);
+        session.set_blocking(true);
+        session.set_timeout(0);
+
+        let (stdout, stderr, exit_code) = result?;
+        Ok(CommandOutput {
+            stdout,
+            stderr,
+            exit_code,
+            duration: started.elapsed(),
+        })
+    }
+
+    /// Write all of `data` while the session is in non-blocking mode
+    fn write_nonblocking(channel: &mut ssh2::Channel, mut data: &[u8], deadline: Instant) -> Result<(), CommandError> {
+        while !data.is_empty() {
+            let written = match channel.write(data) {
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => 0,
+                Err(e) => {
+                    return Err(CommandError {
+                        message: format!("Failed to answer the password prompt: {}", e),
+                        code: -1,
+                    })
+                }
+            };
+            if written > 0 {
+                data = &data[written..];
+            } else if Instant::now() < deadline {
+                thread::sleep(READ_POLL_INTERVAL);
+            } else {
+                return Err(CommandError {
+                    message: "Timed out answering the password prompt".to_string(),
+                    code: CommandError::TIMEOUT,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn run_channel(
+        session: &mut PooledSession<'_>,
+        escalated: &privilege::Escalated,
+        timeout: Duration,
+        deadline: Instant,
+        cancel: Option<&CancelToken>,
+    ) -> Result<(String, String, i32), CommandError> {
+        let timed_out = |e: &ssh2::Error| e.code() == ssh2::ErrorCode::Session(LIBSSH2_ERROR_TIMEOUT);
+
+        let mut channel = match session.channel_session() {
+            Ok(channel) => channel,
+            Err(e) => {
+                // A session that can't open channels is most likely dead; don't hand it out again
+                session.discard();
+                return Err(CommandError {
+                    message: if timed_out(&e) {
+                        format!("Timed out opening channel after {}s", timeout.as_secs())
+                    } else {
+                        format!("Failed to open channel: {}", e)
+                    },
+                    code: if timed_out(&e) { CommandError::TIMEOUT } else { -1 },
+                });
+            }
+        };
+
+        if escalated.tty_password.is_some() {
+            // No echo, so the password doesn't come back in the output, and plain newlines
+            let mut modes = PtyModes::new();
+            modes.set_boolean(PtyModeOpcode::ECHO, false);
+            modes.set_boolean(PtyModeOpcode::ONLCR, false);
+            channel.request_pty("dumb", Some(modes), None).map_err(|e| CommandError {
+                message: format!("Failed to request a terminal for su: {}", e),
+                code: if timed_out(&e) { CommandError::TIMEOUT } else { -1 },
+            })?;
+        }
+
+        channel.exec(&escalated.command).map_err(|e| CommandError {
+            message: format!("Failed to execute command: {}", e),
+            code: if timed_out(&e) { CommandError::TIMEOUT } else { -1 },
+        })?;
+
+        if let Some(input) = &escalated.stdin {
+            channel
+                .write_all(input.as_bytes())
+                .and_then(|_| channel.send_eof().map_err(std::io::Error::from))
+                .map_err(|e| CommandError {
+                    message: format!("Failed to write command input: {}", e),
+                    code: -1,
+                })?;
+        }
+
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+
+        // Poll both streams without blocking so the deadline and cancel flag are honoured,
+        // and so a chatty stderr can't stall stdout
+        session.set_blocking(false);
+        let mut buf = [0u8; 16 * 1024];
+        let (mut stdout_open, mut stderr_open) = (true, true);
+        let read_result = loop {
+            if !stdout_open && !stderr_open {
+                break Ok(());
+            }
+            if cancel.is_some_and(|c| c.is_cancelled()) {
+                break Err(CommandError {
+                    message: "Command cancelled".to_string(),
+                    code: CommandError::CANCELLED,
+                });
+            }
+            if Instant::now() >= deadline {
+                break Err(CommandError {
+                    message: format!("Command timed out after {}s", timeout.as_secs()),
+                    code: CommandError::TIMEOUT,
+                });
+            }
+
+            let mut progressed = false;
+            let streams = [
+                (0, &mut stdout_open, &mut stdout),
+                (1, &mut stderr_open, &mut stderr),
+            ];
+            let mut failure = None;
+            for (stream_id, open, sink) in streams {
+                if !*open {
+                    continue;
+                }
+                match channel.stream(stream_id).read(&mut buf) {
+                    Ok(0) => *open = false,
+                    Ok(n) => {
+                        sink.extend_from_slice(&buf[..n]);
+                        progressed = true;
+                        if let Some(password) = &escalated.tty_password {
+                            if stream_id == 0 && privilege::take_password_prompt(sink) {
+                                let answer = format!("{}\n", password);
+                                if let Err(e) = Self::write_nonblocking(&mut channel, answer.as_bytes(), deadline) {
+                                    failure = Some(e);
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                    Err(e) => {
+                        failure = Some(CommandError {
+                            message: format!("Failed to read output: {}", e),
+                            code: -1,
+                        });
+                        break;
+                    }
+                }
+            }
+            if let Some(e) = failure {
+                break Err(e);
+            }
+            if !progressed {
+                thread::sleep(READ_POLL_INTERVAL);
+            }
+        };
+        session.set_blocking(true);
+
+        if let Err(e) = read_result {
+            // Don't let a stuck remote process hold the session; closing sends the channel close
+            session.set_timeout(CHANNEL_CLOSE_TIMEOUT_MS);
+            let _ = channel.close();
+            return Err(e);
+        }
 
         channel.wait_close().map_err(|e| CommandError {
             message: format!("Failed to wait for channel close: {}", e),
@@ -137,21 +817,28 @@ impl SshClient {
             code: -1,
         })?;
 
-        // Return output even if exit status is non-zero (common with fallbacks)
-        // Only error if we have stderr and no stdout
-        if output.is_empty() && !stderr.is_empty() {
-            Err(CommandError {
-                message: stderr,
-                code: exit_status,
-            })
-        } else {
-            Ok(output)
-        }
+        Ok((
+            String::from_utf8_lossy(&stdout).into_owned(),
+            String::from_utf8_lossy(&stderr).into_owned(),
+            exit_status,
+        ))
     }
 
     pub fn is_connected(&self) -> bool {
-        let session_guard = self.session.lock().unwrap();
-        session_guard.as_ref().map_or(false, |s| s.authenticated())
+        self.sessions.is_open()
+    }
+
+    pub fn profile_id(&self) -> &str {
+        &self.config.id
+    }
+
+    pub fn escalation(&self) -> &Escalation {
+        &self.config.escalation
+    }
+
+    /// The profile this client was created with, credentials included
+    pub fn profile(&self) -> &ServerProfile {
+        &self.config
     }
 
     pub fn get_host(&self) -> String {
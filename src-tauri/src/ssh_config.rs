@@ -0,0 +1,555 @@
+use crate::commands::{forget_profile_data, profiles_from_json, profiles_to_json, AppState, PROFILES_KEY, STORE_FILENAME};
+use crate::types::*;
+use crate::vault::VaultState;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::State;
+use tauri_plugin_store::StoreExt;
+
+/// Profiles created from ssh config get a stable id so re-importing updates them in place
+pub const SSH_CONFIG_ID_PREFIX: &str = "ssh-config:";
+
+/// Nesting limit for `Include`, matching OpenSSH
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// A `Host` (or `Match`) block with its options in file order
+#[derive(Debug, Clone)]
+struct ConfigBlock {
+    patterns: Vec<String>,
+    options: Vec<(String, String)>,
+}
+
+/// A parsed OpenSSH client config, with `Include` directives already inlined
+#[derive(Debug, Clone)]
+pub struct SshConfig {
+    blocks: Vec<ConfigBlock>,
+}
+
+/// A concrete host alias with every matching block applied
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SshConfigHost {
+    pub alias: String,
+    pub host_name: String,
+    pub user: Option<String>,
+    pub port: u16,
+    pub identity_file: Option<String>,
+    pub proxy_jump: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SshConfigImportResult {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+fn home_dir() -> PathBuf {
+    dirs::home_dir().unwrap_or_else(|| PathBuf::from("."))
+}
+
+fn local_username() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "root".to_string())
+}
+
+fn expand_tilde(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => home_dir().join(rest),
+        None if path == "~" => home_dir(),
+        None => PathBuf::from(path),
+    }
+}
+
+/// OpenSSH-style wildcard match supporting `*` and `?`
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn has_wildcard(pattern: &str) -> bool {
+    pattern.contains('*') || pattern.contains('?')
+}
+
+/// Split a config line into its lowercased keyword and raw argument string
+fn split_line(line: &str) -> Option<(String, &str)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let end = line.find(|c: char| c.is_whitespace() || c == '=').unwrap_or(line.len());
+    let keyword = line[..end].to_lowercase();
+    let rest = line[end..].trim_start();
+    let rest = rest.strip_prefix('=').unwrap_or(rest).trim();
+    Some((keyword, rest))
+}
+
+/// Split arguments on whitespace, keeping double-quoted strings together
+fn split_args(args: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in args.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    out.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        out.push(current);
+    }
+    out
+}
+
+impl SshConfig {
+    /// The user's `~/.ssh/config`
+    pub fn default_path() -> PathBuf {
+        home_dir().join(".ssh").join("config")
+    }
+
+    pub fn load(path: &Path) -> Result<SshConfig, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let include_dir = home_dir().join(".ssh");
+        Ok(Self::parse(&content, &include_dir))
+    }
+
+    /// Parse config text; relative `Include` paths are resolved against `include_dir`
+    pub fn parse(content: &str, include_dir: &Path) -> SshConfig {
+        let mut config = SshConfig {
+            blocks: vec![ConfigBlock { patterns: vec!["*".to_string()], options: Vec::new() }],
+        };
+        config.parse_into(content, include_dir, 0);
+        config
+    }
+
+    fn parse_into(&mut self, content: &str, include_dir: &Path, depth: usize) {
+        for line in content.lines() {
+            let Some((keyword, args)) = split_line(line) else {
+                continue;
+            };
+
+            match keyword.as_str() {
+                "host" => self.blocks.push(ConfigBlock {
+                    patterns: split_args(args),
+                    options: Vec::new(),
+                }),
+                "match" => {
+                    // Match criteria need a live connection to evaluate; only `Match all` is honoured
+                    let patterns = if args.trim().eq_ignore_ascii_case("all") {
+                        vec!["*".to_string()]
+                    } else {
+                        Vec::new()
+                    };
+                    self.blocks.push(ConfigBlock { patterns, options: Vec::new() });
+                }
+                "include" => {
+                    if depth >= MAX_INCLUDE_DEPTH {
+                        log::warn!("Ignoring ssh config Include nested deeper than {}", MAX_INCLUDE_DEPTH);
+                        continue;
+                    }
+                    let enclosing = self.blocks.len();
+                    for pattern in split_args(args) {
+                        for path in Self::include_paths(&pattern, include_dir) {
+                            match fs::read_to_string(&path) {
+                                Ok(included) => self.parse_into(&included, include_dir, depth + 1),
+                                Err(e) => log::warn!("Failed to read included ssh config {}: {}", path.display(), e),
+                            }
+                        }
+                    }
+                    // Lines after the Include still belong to the block it appeared in, so
+                    // continue that block after whatever the included files declared
+                    if self.blocks.len() > enclosing {
+                        let patterns = self.blocks[enclosing - 1].patterns.clone();
+                        self.blocks.push(ConfigBlock { patterns, options: Vec::new() });
+                    }
+                }
+                _ => {
+                    let value = split_args(args).join(" ");
+                    if let Some(block) = self.blocks.last_mut() {
+                        block.options.push((keyword, value));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Files named by an `Include` pattern, with wildcards in the file name expanded
+    fn include_paths(pattern: &str, include_dir: &Path) -> Vec<PathBuf> {
+        let path = expand_tilde(pattern);
+        let path = if path.is_absolute() { path } else { include_dir.join(path) };
+
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if !has_wildcard(file_name) {
+            return vec![path];
+        }
+
+        let Some(dir) = path.parent() else {
+            return Vec::new();
+        };
+        let mut matches: Vec<PathBuf> = fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .filter(|p| p.is_file())
+                    .filter(|p| {
+                        p.file_name()
+                            .and_then(|n| n.to_str())
+                            .is_some_and(|name| wildcard_match(file_name, name))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        matches.sort();
+        matches
+    }
+
+    fn block_matches(block: &ConfigBlock, host: &str) -> bool {
+        let mut matched = false;
+        for pattern in &block.patterns {
+            match pattern.strip_prefix('!') {
+                Some(negated) if wildcard_match(negated, host) => return false,
+                Some(_) => {}
+                None => matched |= wildcard_match(pattern, host),
+            }
+        }
+        matched
+    }
+
+    /// Options that apply to `host`; as in OpenSSH, the first value seen for a keyword wins
+    fn options_for(&self, host: &str) -> HashMap<String, String> {
+        let mut options = HashMap::new();
+        for block in self.blocks.iter().filter(|b| Self::block_matches(b, host)) {
+            for (keyword, value) in &block.options {
+                options.entry(keyword.clone()).or_insert_with(|| value.clone());
+            }
+        }
+        options
+    }
+
+    /// Concrete aliases declared in `Host` lines, in file order
+    pub fn aliases(&self) -> Vec<String> {
+        let mut aliases: Vec<String> = Vec::new();
+        for block in &self.blocks {
+            for pattern in &block.patterns {
+                if !has_wildcard(pattern) && !pattern.starts_with('!') && !aliases.contains(pattern) {
+                    aliases.push(pattern.clone());
+                }
+            }
+        }
+        aliases
+    }
+
+    /// Expand `%` tokens; `%h` is the host name, `%n` the alias as given
+    fn expand_tokens(value: &str, alias: &str, host_name: &str, user: &str) -> String {
+        let mut out = String::new();
+        let mut chars = value.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                out.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('h') => out.push_str(host_name),
+                Some('n') => out.push_str(alias),
+                Some('r') => out.push_str(user),
+                Some('u') => out.push_str(&local_username()),
+                Some('d') => out.push_str(&home_dir().to_string_lossy()),
+                Some('%') => out.push('%'),
+                Some(other) => {
+                    out.push('%');
+                    out.push(other);
+                }
+                None => out.push('%'),
+            }
+        }
+        out
+    }
+
+    /// Resolve every option for one alias
+    pub fn host(&self, alias: &str) -> SshConfigHost {
+        let options = self.options_for(alias);
+        let user = options.get("user").cloned();
+        let token_user = user.clone().unwrap_or_else(local_username);
+
+        let host_name = options
+            .get("hostname")
+            // Within HostName itself, %h is still the alias
+            .map(|h| Self::expand_tokens(h, alias, alias, &token_user))
+            .unwrap_or_else(|| alias.to_string());
+        let identity_file = options
+            .get("identityfile")
+            .filter(|f| !f.eq_ignore_ascii_case("none"))
+            .map(|f| Self::expand_tokens(f, alias, &host_name, &token_user));
+        let proxy_jump = options
+            .get("proxyjump")
+            .filter(|j| !j.eq_ignore_ascii_case("none"))
+            .map(|j| j.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+            .unwrap_or_default();
+
+        SshConfigHost {
+            alias: alias.to_string(),
+            host_name,
+            user,
+            port: options.get("port").and_then(|p| p.parse().ok()).unwrap_or(22),
+            identity_file,
+            proxy_jump,
+        }
+    }
+
+    pub fn hosts(&self) -> Vec<SshConfigHost> {
+        self.aliases().iter().map(|alias| self.host(alias)).collect()
+    }
+
+    /// Turn a `[user@]host[:port]` ProxyJump entry into a jump host, resolving aliases
+    fn jump_host(&self, spec: &str) -> JumpHost {
+        let spec = spec.strip_prefix("ssh://").unwrap_or(spec);
+        let (user, rest) = match spec.rsplit_once('@') {
+            Some((user, rest)) => (Some(user.to_string()), rest),
+            None => (None, spec),
+        };
+        let (host, port) = match rest.rsplit_once(':') {
+            Some((host, port)) if !host.contains(':') => (host, port.parse().ok()),
+            _ => (rest, None),
+        };
+
+        let resolved = self.host(host);
+        JumpHost {
+            host: resolved.host_name,
+            port: port.unwrap_or(resolved.port),
+            username: user.or(resolved.user).unwrap_or_else(local_username),
+            auth_method: auth_method_for(resolved.identity_file.as_deref()),
+        }
+    }
+
+    /// Build the saved profile for one alias
+    pub fn to_profile(&self, host: &SshConfigHost) -> SavedServerProfile {
+        let profile = ServerProfile {
+            id: format!("{}{}", SSH_CONFIG_ID_PREFIX, host.alias),
+            name: host.alias.clone(),
+            host: host.host_name.clone(),
+            port: host.port,
+            username: host.user.clone().unwrap_or_else(local_username),
+            auth_method: auth_method_for(host.identity_file.as_deref()),
+            jump_hosts: host.proxy_jump.iter().map(|spec| self.jump_host(spec)).collect(),
+            escalation: Escalation::default(),
+        };
+        SavedServerProfile::from(profile)
+    }
+}
+
+/// IdentityFile means key auth; otherwise fall back to whatever ssh-agent offers
+fn auth_method_for(identity_file: Option<&str>) -> AuthMethod {
+    match identity_file {
+        Some(path) => AuthMethod::PrivateKey { key_path: path.to_string(), passphrase: None },
+        None => AuthMethod::Agent { identity: None },
+    }
+}
+
+/// Merge a freshly imported profile into an existing one, keeping what the user set in DPanel
+fn merge_imported(existing: &SavedServerProfile, mut imported: SavedServerProfile) -> SavedServerProfile {
+    let keep_auth = match (&existing.auth_method, &imported.auth_method) {
+        // Same key file: keep any passphrase entered since the last import
+        (AuthMethod::PrivateKey { key_path: old, .. }, AuthMethod::PrivateKey { key_path: new, .. }) => old == new,
+        // No IdentityFile in the config: keep whatever auth was configured in DPanel
+        (_, AuthMethod::Agent { identity: None }) => true,
+        _ => false,
+    };
+    if keep_auth {
+        imported.auth_method = existing.auth_method.clone();
+    }
+    imported.escalation = existing.escalation.clone();
+    imported.created_at = existing.created_at;
+    imported.last_connected = existing.last_connected;
+    imported.connect_on_startup = existing.connect_on_startup;
+    imported.labels = existing.labels.clone();
+    imported
+}
+
+/// List the hosts an import would create, without touching saved profiles
+#[tauri::command]
+pub async fn preview_ssh_config(path: Option<String>) -> Result<Vec<SshConfigHost>, String> {
+    let path = path.map(|p| expand_tilde(&p)).unwrap_or_else(SshConfig::default_path);
+    Ok(SshConfig::load(&path)?.hosts())
+}
+
+/// Import (or re-sync) profiles from an OpenSSH config file.
+///
+/// `hosts` limits the import to the given aliases. With `prune`, profiles imported
+/// earlier whose alias no longer exists in the config are deleted, along with their
+/// vault entries and metrics history.
+#[tauri::command]
+pub async fn import_ssh_config(
+    path: Option<String>,
+    hosts: Option<Vec<String>>,
+    prune: Option<bool>,
+    state: State<'_, AppState>,
+    vault: State<'_, VaultState>,
+    app: tauri::AppHandle,
+) -> Result<SshConfigImportResult, String> {
+    let path = path.map(|p| expand_tilde(&p)).unwrap_or_else(SshConfig::default_path);
+    let config = SshConfig::load(&path)?;
+    let entries: Vec<SshConfigHost> = config
+        .hosts()
+        .into_iter()
+        .filter(|h| hosts.as_ref().is_none_or(|wanted| wanted.contains(&h.alias)))
+        .collect();
+
+    let store = app.store(STORE_FILENAME).map_err(|e| format!("Failed to open profile store: {}", e))?;
+    let mut profiles_map = profiles_from_json(store.get(PROFILES_KEY));
+    let mut result = SshConfigImportResult::default();
+
+    for entry in &entries {
+        let imported = config.to_profile(entry);
+        let id = imported.id.clone();
+        match profiles_map.get(&id) {
+            Some(existing) => {
+                let merged = merge_imported(existing, imported);
+                profiles_map.insert(id, merged);
+                result.updated.push(entry.alias.clone());
+            }
+            None => {
+                profiles_map.insert(id, imported);
+                result.added.push(entry.alias.clone());
+            }
+        }
+    }
+
+    if prune.unwrap_or(false) {
+        let aliases = config.aliases();
+        let stale: Vec<String> = profiles_map
+            .keys()
+            .filter(|id| {
+                id.strip_prefix(SSH_CONFIG_ID_PREFIX)
+                    .is_some_and(|alias| !aliases.iter().any(|a| a == alias))
+            })
+            .cloned()
+            .collect();
+        for id in stale {
+            state.server_profiles.lock().await.remove(&id);
+            forget_profile_data(&app, &vault, &id)?;
+            profiles_map.remove(&id);
+            result.removed.push(id.trim_start_matches(SSH_CONFIG_ID_PREFIX).to_string());
+        }
+    }
+
+    store.set(PROFILES_KEY, profiles_to_json(&profiles_map));
+    store.save().map_err(|e| format!("Failed to save profiles: {}", e))?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcard_blocks_apply_first_value() {
+        let config = SshConfig::parse(
+            r#"
+Host web1 web2
+    HostName %h.example.com
+    Port 2222
+
+Host web*
+    User deploy
+    Port 22
+    IdentityFile ~/.ssh/%h-%n
+
+Host *
+    User root
+    IdentityFile ~/.ssh/id_ed25519
+"#,
+            Path::new("/nonexistent"),
+        );
+
+        assert_eq!(config.aliases(), vec!["web1", "web2"]);
+        let web1 = config.host("web1");
+        assert_eq!(web1.host_name, "web1.example.com");
+        assert_eq!(web1.port, 2222);
+        assert_eq!(web1.user.as_deref(), Some("deploy"));
+        assert_eq!(web1.identity_file.as_deref(), Some("~/.ssh/web1.example.com-web1"));
+    }
+
+    #[test]
+    fn test_negated_patterns_and_proxy_jump() {
+        let config = SshConfig::parse(
+            r#"
+Host bastion
+    HostName 203.0.113.10
+    User jump
+    Port 2200
+
+Host db
+    HostName 10.0.0.5
+    ProxyJump bastion,ops@gateway:2022
+
+Host * !bastion
+    User app
+"#,
+            Path::new("/nonexistent"),
+        );
+
+        assert_eq!(config.host("bastion").user.as_deref(), Some("jump"));
+        let db = config.host("db");
+        assert_eq!(db.user.as_deref(), Some("app"));
+
+        let profile = config.to_profile(&db);
+        assert_eq!(profile.id, "ssh-config:db");
+        assert_eq!(profile.jump_hosts.len(), 2);
+        assert_eq!(profile.jump_hosts[0].host, "203.0.113.10");
+        assert_eq!(profile.jump_hosts[0].port, 2200);
+        assert_eq!(profile.jump_hosts[0].username, "jump");
+        assert_eq!(profile.jump_hosts[1].host, "gateway");
+        assert_eq!(profile.jump_hosts[1].port, 2022);
+        assert_eq!(profile.jump_hosts[1].username, "ops");
+    }
+
+    #[test]
+    fn test_include_is_inlined() {
+        let dir = std::env::temp_dir().join(format!("dpanel-ssh-config-{}", std::process::id()));
+        fs::create_dir_all(dir.join("conf.d")).unwrap();
+        fs::write(dir.join("conf.d").join("staging.conf"), "Host staging\n    HostName 192.0.2.7\n").unwrap();
+
+        let config = SshConfig::parse(
+            "Include conf.d/*.conf\nHost prod\n    HostName 192.0.2.1\n    Include conf.d/*.conf\n    User deploy\n",
+            &dir,
+        );
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(config.aliases(), vec!["staging", "prod"]);
+        assert_eq!(config.host("staging").host_name, "192.0.2.7");
+        // Lines after an Include stay with the enclosing Host block
+        assert_eq!(config.host("prod").user.as_deref(), Some("deploy"));
+        assert_eq!(config.host("staging").user, None);
+    }
+}
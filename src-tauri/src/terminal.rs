@@ -0,0 +1,274 @@
+use crate::commands::AppState;
+use crate::ssh::SshClient;
+use crate::types::*;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ssh2::{Channel, ExtendedData, Session};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, TryRecvError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+pub const TERMINAL_OUTPUT_EVENT: &str = "terminal://output";
+pub const TERMINAL_EXIT_EVENT: &str = "terminal://exit";
+
+/// libssh2's LIBSSH2_ERROR_EAGAIN, returned by a non-blocking session that would block
+const LIBSSH2_ERROR_EAGAIN: i32 = -37;
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// Upper bound for a single channel request (open, pty, resize, write) to go through
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+enum TerminalInput {
+    Data(Vec<u8>),
+    Resize { cols: u32, rows: u32 },
+    Close,
+}
+
+struct Terminal {
+    server_id: String,
+    input: mpsc::Sender<TerminalInput>,
+}
+
+/// Open terminal tabs, plus one non-blocking session per server that all of that
+/// server's tabs multiplex their PTY channels over
+#[derive(Default)]
+pub struct TerminalState {
+    terminals: Mutex<HashMap<String, Terminal>>,
+    sessions: Mutex<HashMap<String, Session>>,
+    next_id: AtomicU64,
+}
+
+impl TerminalState {
+    fn send(&self, terminal_id: &str, input: TerminalInput) -> Result<(), String> {
+        let terminals = self.terminals.lock().unwrap();
+        let terminal = terminals
+            .get(terminal_id)
+            .ok_or_else(|| format!("Terminal {} is not open", terminal_id))?;
+        terminal
+            .input
+            .send(input)
+            .map_err(|_| format!("Terminal {} has exited", terminal_id))
+    }
+
+    /// Ask every terminal of a server to close, e.g. when it is disconnected
+    pub fn close_server(&self, server_id: &str) {
+        for terminal in self.terminals.lock().unwrap().values() {
+            if terminal.server_id == server_id {
+                let _ = terminal.input.send(TerminalInput::Close);
+            }
+        }
+    }
+
+    fn remove(&self, terminal_id: &str) {
+        let mut terminals = self.terminals.lock().unwrap();
+        if let Some(terminal) = terminals.remove(terminal_id) {
+            // Drop the shared session along with the server's last tab
+            if !terminals.values().any(|t| t.server_id == terminal.server_id) {
+                self.sessions.lock().unwrap().remove(&terminal.server_id);
+            }
+        }
+    }
+
+    fn session_for(&self, client: &SshClient) -> Result<Session, CommandError> {
+        let mut sessions = self.sessions.lock().unwrap();
+        if let Some(session) = sessions.get(client.profile_id()) {
+            return Ok(session.clone());
+        }
+
+        let session = client.open_dedicated_session()?;
+        session.set_blocking(false);
+        sessions.insert(client.profile_id().to_string(), session.clone());
+        Ok(session)
+    }
+
+    fn forget_session(&self, server_id: &str) {
+        self.sessions.lock().unwrap().remove(server_id);
+    }
+}
+
+fn would_block(e: &ssh2::Error) -> bool {
+    e.code() == ssh2::ErrorCode::Session(LIBSSH2_ERROR_EAGAIN)
+}
+
+/// Repeat a request on the non-blocking session until it stops returning EAGAIN
+fn retry<T>(mut request: impl FnMut() -> Result<T, ssh2::Error>) -> Result<T, ssh2::Error> {
+    let deadline = Instant::now() + REQUEST_TIMEOUT;
+    loop {
+        match request() {
+            Err(e) if would_block(&e) && Instant::now() < deadline => thread::sleep(POLL_INTERVAL),
+            result => return result,
+        }
+    }
+}
+
+fn open_pty(session: &Session, term: &str, cols: u32, rows: u32) -> Result<Channel, ssh2::Error> {
+    let mut channel = retry(|| session.channel_session())?;
+    retry(|| channel.handle_extended_data(ExtendedData::Merge))?;
+    retry(|| channel.request_pty(term, None, Some((cols, rows, 0, 0))))?;
+    retry(|| channel.shell())?;
+    Ok(channel)
+}
+
+fn write_all(channel: &mut Channel, mut data: &[u8]) -> Result<(), String> {
+    let deadline = Instant::now() + REQUEST_TIMEOUT;
+    while !data.is_empty() {
+        // A full window can also show up as a zero-length write; both mean try again shortly
+        let written = match channel.write(data) {
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => 0,
+            Err(e) => return Err(format!("Failed to write to terminal: {}", e)),
+        };
+        if written > 0 {
+            data = &data[written..];
+        } else if Instant::now() < deadline {
+            thread::sleep(POLL_INTERVAL);
+        } else {
+            return Err("Failed to write to terminal: timed out".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Shuttle keystrokes in and output out until the shell exits or the tab is closed
+fn pump(app: AppHandle, terminal_id: String, mut channel: Channel, input: mpsc::Receiver<TerminalInput>) {
+    let mut buf = [0u8; 16 * 1024];
+    let mut failure = None;
+
+    'session: loop {
+        let mut busy = false;
+
+        loop {
+            match input.try_recv() {
+                Ok(TerminalInput::Data(bytes)) => {
+                    if let Err(e) = write_all(&mut channel, &bytes) {
+                        failure = Some(e);
+                        break 'session;
+                    }
+                    busy = true;
+                }
+                Ok(TerminalInput::Resize { cols, rows }) => {
+                    if let Err(e) = retry(|| channel.request_pty_size(cols, rows, None, None)) {
+                        log::warn!("Failed to resize terminal {}: {}", terminal_id, e);
+                    }
+                }
+                Ok(TerminalInput::Close) | Err(TryRecvError::Disconnected) => {
+                    let _ = retry(|| channel.close());
+                    break 'session;
+                }
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+
+        match channel.read(&mut buf) {
+            Ok(0) if channel.eof() => break,
+            Ok(0) => {}
+            Ok(n) => {
+                let output = TerminalOutput {
+                    terminal_id: terminal_id.clone(),
+                    data: STANDARD.encode(&buf[..n]),
+                };
+                if let Err(e) = app.emit(TERMINAL_OUTPUT_EVENT, output) {
+                    log::warn!("Failed to emit terminal output: {}", e);
+                }
+                busy = true;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+            Err(e) => {
+                failure = Some(format!("Terminal connection lost: {}", e));
+                break;
+            }
+        }
+
+        if !busy {
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    let exit_code = channel.exit_status().ok().filter(|_| failure.is_none());
+    app.state::<TerminalState>().remove(&terminal_id);
+
+    let exit = TerminalExit {
+        terminal_id,
+        exit_code,
+        message: failure,
+    };
+    if let Err(e) = app.emit(TERMINAL_EXIT_EVENT, exit) {
+        log::warn!("Failed to emit terminal exit: {}", e);
+    }
+}
+
+/// Open an interactive shell on the connected server. Output arrives as base64 chunks
+/// in `terminal://output` events and `terminal://exit` fires when the shell ends.
+#[tauri::command]
+pub async fn open_terminal(
+    cols: u32,
+    rows: u32,
+    term: Option<String>,
+    server_id: Option<String>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    let term = term.unwrap_or_else(|| "xterm-256color".to_string());
+
+    let opening = app.clone();
+    let channel = tokio::task::spawn_blocking(move || {
+        let terminals = opening.state::<TerminalState>();
+        let session = terminals.session_for(&client).map_err(|e| e.message)?;
+
+        match open_pty(&session, &term, cols, rows) {
+            Ok(channel) => Ok((client.profile_id().to_string(), channel)),
+            Err(first) => {
+                // The shared session may have died since the last tab was opened
+                log::warn!("Failed to open terminal on existing session: {}", first);
+                terminals.forget_session(client.profile_id());
+                let session = terminals.session_for(&client).map_err(|e| e.message)?;
+                open_pty(&session, &term, cols, rows)
+                    .map(|channel| (client.profile_id().to_string(), channel))
+                    .map_err(|e| format!("Failed to open terminal: {}", e))
+            }
+        }
+    })
+    .await
+    .map_err(|e| format!("Terminal task failed: {}", e))?;
+    let (server_id, channel) = channel?;
+
+    let terminals = app.state::<TerminalState>();
+    let terminal_id = format!("term-{}", terminals.next_id.fetch_add(1, Ordering::Relaxed));
+    let (tx, rx) = mpsc::channel();
+    terminals
+        .terminals
+        .lock()
+        .unwrap()
+        .insert(terminal_id.clone(), Terminal { server_id, input: tx });
+
+    let pump_app = app.clone();
+    let pump_id = terminal_id.clone();
+    thread::spawn(move || pump(pump_app, pump_id, channel, rx));
+
+    Ok(terminal_id)
+}
+
+#[tauri::command]
+pub async fn write_terminal(terminal_id: String, data: String, terminals: State<'_, TerminalState>) -> Result<(), String> {
+    terminals.send(&terminal_id, TerminalInput::Data(data.into_bytes()))
+}
+
+#[tauri::command]
+pub async fn resize_terminal(
+    terminal_id: String,
+    cols: u32,
+    rows: u32,
+    terminals: State<'_, TerminalState>,
+) -> Result<(), String> {
+    terminals.send(&terminal_id, TerminalInput::Resize { cols, rows })
+}
+
+#[tauri::command]
+pub async fn close_terminal(terminal_id: String, terminals: State<'_, TerminalState>) -> Result<(), String> {
+    terminals.send(&terminal_id, TerminalInput::Close)
+}
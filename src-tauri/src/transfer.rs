@@ -0,0 +1,358 @@
+use crate::commands::AppState;
+use crate::sftp::SftpError;
+use crate::shell::Cmd;
+use crate::ssh::{CancelToken, CommandCategory, SshClient};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use ssh2::{OpenFlags, OpenType, Session};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, State};
+
+pub const TRANSFER_PROGRESS_EVENT: &str = "transfer://progress";
+
+const CHUNK_SIZE: usize = 256 * 1024;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+/// How long a single SFTP read or write may stall before the transfer fails
+const STALL_TIMEOUT: Duration = Duration::from_secs(60);
+
+static NEXT_TRANSFER_ID: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferDirection {
+    Upload,
+    Download,
+}
+
+/// Payload of `transfer://progress` events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferProgress {
+    pub transfer_id: String,
+    pub direction: TransferDirection,
+    pub bytes_done: u64,
+    pub total_bytes: u64,
+    /// Bytes per second over this run, not counting a resumed prefix
+    pub rate: f64,
+    pub eta_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferResult {
+    pub transfer_id: String,
+    pub bytes: u64,
+    /// Bytes that were already in place and skipped
+    pub resumed_from: u64,
+    pub sha256: String,
+    /// False when the server couldn't compute a checksum (no `sha256sum`)
+    pub verified: bool,
+}
+
+struct ProgressReporter<'a> {
+    app: &'a AppHandle,
+    transfer_id: &'a str,
+    direction: TransferDirection,
+    total: u64,
+    resumed_from: u64,
+    started: Instant,
+    last_emit: Option<Instant>,
+}
+
+impl ProgressReporter<'_> {
+    fn report(&mut self, done: u64, force: bool) {
+        if !force && self.last_emit.is_some_and(|t| t.elapsed() < PROGRESS_INTERVAL) {
+            return;
+        }
+        self.last_emit = Some(Instant::now());
+
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 { (done - self.resumed_from) as f64 / elapsed } else { 0.0 };
+        let eta_secs = (rate > 0.0).then(|| (self.total.saturating_sub(done) as f64 / rate).ceil() as u64);
+
+        let progress = TransferProgress {
+            transfer_id: self.transfer_id.to_string(),
+            direction: self.direction,
+            bytes_done: done,
+            total_bytes: self.total,
+            rate,
+            eta_secs,
+        };
+        if let Err(e) = self.app.emit(TRANSFER_PROGRESS_EVENT, progress) {
+            log::warn!("Failed to emit transfer progress: {}", e);
+        }
+    }
+}
+
+fn local_error(path: &Path, e: std::io::Error) -> SftpError {
+    SftpError::Other(format!("{}: {}", path.display(), e))
+}
+
+/// Copy `reader` into `writer` chunk by chunk, reporting progress and honouring cancellation
+fn pump(
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+    mut done: u64,
+    progress: &mut ProgressReporter<'_>,
+    cancel: &CancelToken,
+) -> Result<u64, SftpError> {
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        if cancel.is_cancelled() {
+            return Err(SftpError::Cancelled);
+        }
+        let n = reader.read(&mut buf).map_err(|e| SftpError::Other(format!("Read failed: {}", e)))?;
+        if n == 0 {
+            break;
+        }
+        writer
+            .write_all(&buf[..n])
+            .map_err(|e| SftpError::Other(format!("Write failed: {}", e)))?;
+        done += n as u64;
+        progress.report(done, false);
+    }
+    writer.flush().map_err(|e| SftpError::Other(format!("Write failed: {}", e)))?;
+    progress.report(done, true);
+    Ok(done)
+}
+
+fn local_sha256(path: &Path) -> Result<String, SftpError> {
+    let mut file = File::open(path).map_err(|e| local_error(path, e))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        let n = file.read(&mut buf).map_err(|e| local_error(path, e))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Compare the local file with `sha256sum` on the server; returns the digest and
+/// whether the server could confirm it
+fn verify_checksum(client: &SshClient, local: &Path, remote: &str) -> Result<(String, bool), SftpError> {
+    let local_digest = local_sha256(local)?;
+    let output = client.run_command(
+        &Cmd::new("sha256sum").raw("--").arg(remote),
+        CommandCategory::Scan.timeout(),
+        None,
+    )?;
+
+    if !output.success() {
+        log::warn!("Could not verify {} on the server: {}", remote, output.stderr.trim());
+        return Ok((local_digest, false));
+    }
+
+    let remote_digest = output.stdout.split_whitespace().next().unwrap_or("").to_lowercase();
+    if remote_digest != local_digest {
+        return Err(SftpError::ChecksumMismatch(remote.to_string()));
+    }
+    Ok((local_digest, true))
+}
+
+/// Run a transfer on its own session when possible, so long copies don't tie up the pool
+fn with_transfer_session<T>(
+    client: &SshClient,
+    f: impl FnOnce(&Session) -> Result<T, SftpError>,
+) -> Result<T, SftpError> {
+    match client.open_dedicated_session() {
+        Ok(session) => {
+            session.set_timeout(STALL_TIMEOUT.as_millis() as u32);
+            f(&session)
+        }
+        Err(e) => {
+            log::info!("Using a pooled session for transfer: {}", e.message);
+            client.with_session(STALL_TIMEOUT, f)
+        }
+    }
+}
+
+fn upload(
+    session: &Session,
+    local: &Path,
+    remote: &str,
+    resume: bool,
+    progress: &mut ProgressReporter<'_>,
+    cancel: &CancelToken,
+) -> Result<(u64, u64), SftpError> {
+    let sftp = session
+        .sftp()
+        .map_err(|e| SftpError::Other(format!("Failed to start SFTP: {}", e)))?;
+    let mut source = File::open(local).map_err(|e| local_error(local, e))?;
+    let total = source.metadata().map_err(|e| local_error(local, e))?.len();
+    progress.total = total;
+
+    let existing = if resume {
+        sftp.stat(Path::new(remote)).ok().and_then(|stat| stat.size).unwrap_or(0)
+    } else {
+        0
+    };
+    // A remote file larger than the local one isn't a partial copy of it
+    let offset = if existing <= total { existing } else { 0 };
+
+    let mut flags = OpenFlags::WRITE | OpenFlags::CREATE;
+    if offset == 0 {
+        flags |= OpenFlags::TRUNCATE;
+    }
+    let mut target = sftp
+        .open_mode(Path::new(remote), flags, 0o644, OpenType::File)
+        .map_err(|e| SftpError::from_ssh(e, remote))?;
+
+    source.seek(SeekFrom::Start(offset)).map_err(|e| local_error(local, e))?;
+    target
+        .seek(SeekFrom::Start(offset))
+        .map_err(|e| SftpError::Other(format!("{}: {}", remote, e)))?;
+
+    progress.resumed_from = offset;
+    let done = pump(&mut source, &mut target, offset, progress, cancel)?;
+    Ok((done, offset))
+}
+
+fn download(
+    session: &Session,
+    remote: &str,
+    local: &Path,
+    resume: bool,
+    progress: &mut ProgressReporter<'_>,
+    cancel: &CancelToken,
+) -> Result<(u64, u64), SftpError> {
+    let sftp = session
+        .sftp()
+        .map_err(|e| SftpError::Other(format!("Failed to start SFTP: {}", e)))?;
+    let mut source = sftp.open(Path::new(remote)).map_err(|e| SftpError::from_ssh(e, remote))?;
+    let total = source
+        .stat()
+        .map_err(|e| SftpError::from_ssh(e, remote))?
+        .size
+        .unwrap_or(0);
+    progress.total = total;
+
+    let existing = if resume { fs::metadata(local).map(|m| m.len()).unwrap_or(0) } else { 0 };
+    let offset = if existing <= total { existing } else { 0 };
+
+    let mut target = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(offset == 0)
+        .open(local)
+        .map_err(|e| local_error(local, e))?;
+    target.seek(SeekFrom::Start(offset)).map_err(|e| local_error(local, e))?;
+    source
+        .seek(SeekFrom::Start(offset))
+        .map_err(|e| SftpError::Other(format!("{}: {}", remote, e)))?;
+
+    progress.resumed_from = offset;
+    let done = pump(&mut source, &mut target, offset, progress, cancel)?;
+    Ok((done, offset))
+}
+
+struct TransferRequest {
+    transfer_id: String,
+    direction: TransferDirection,
+    local: String,
+    remote: String,
+    resume: bool,
+}
+
+fn run_transfer(
+    client: Arc<SshClient>,
+    app: AppHandle,
+    request: TransferRequest,
+    cancel: CancelToken,
+) -> Result<TransferResult, SftpError> {
+    let TransferRequest { transfer_id, direction, local, remote, resume } = request;
+    let local_path = Path::new(&local);
+    let mut progress = ProgressReporter {
+        app: &app,
+        transfer_id: &transfer_id,
+        direction,
+        total: 0,
+        resumed_from: 0,
+        started: Instant::now(),
+        last_emit: None,
+    };
+
+    let (bytes, resumed_from) = with_transfer_session(&client, |session| match direction {
+        TransferDirection::Upload => upload(session, local_path, &remote, resume, &mut progress, &cancel),
+        TransferDirection::Download => download(session, &remote, local_path, resume, &mut progress, &cancel),
+    })?;
+
+    let (sha256, verified) = verify_checksum(&client, local_path, &remote)?;
+    Ok(TransferResult {
+        transfer_id,
+        bytes,
+        resumed_from,
+        sha256,
+        verified,
+    })
+}
+
+fn next_transfer_id(transfer_id: Option<String>) -> String {
+    transfer_id.unwrap_or_else(|| format!("transfer-{}", NEXT_TRANSFER_ID.fetch_add(1, Ordering::Relaxed)))
+}
+
+async fn start_transfer(
+    request: TransferRequest,
+    server_id: Option<String>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<TransferResult, SftpError> {
+    let client = state.client(server_id.as_deref()).await.map_err(|_| SftpError::NotConnected)?;
+
+    // Registered so `cancel_command` can stop it; the guard unregisters once we return
+    let running = state.running_commands.register(Some(request.transfer_id.clone()));
+    let cancel = running.token.clone();
+
+    tokio::task::spawn_blocking(move || run_transfer(client, app, request, cancel))
+    .await
+    .map_err(|e| SftpError::Other(format!("Transfer task failed: {}", e)))?
+}
+
+/// Upload a local file over SFTP, reporting `transfer://progress` events.
+/// Cancel with `cancel_command(transfer_id)`; with `resume` a partial remote file is continued.
+#[tauri::command]
+pub async fn upload_file(
+    local_path: String,
+    remote_path: String,
+    resume: Option<bool>,
+    transfer_id: Option<String>,
+    server_id: Option<String>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<TransferResult, SftpError> {
+    let request = TransferRequest {
+        transfer_id: next_transfer_id(transfer_id),
+        direction: TransferDirection::Upload,
+        local: local_path,
+        remote: remote_path,
+        resume: resume.unwrap_or(false),
+    };
+    start_transfer(request, server_id, state, app).await
+}
+
+/// Download a remote file over SFTP, reporting `transfer://progress` events.
+/// Cancel with `cancel_command(transfer_id)`; with `resume` a partial local file is continued.
+#[tauri::command]
+pub async fn download_file(
+    remote_path: String,
+    local_path: String,
+    resume: Option<bool>,
+    transfer_id: Option<String>,
+    server_id: Option<String>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<TransferResult, SftpError> {
+    let request = TransferRequest {
+        transfer_id: next_transfer_id(transfer_id),
+        direction: TransferDirection::Download,
+        local: local_path,
+        remote: remote_path,
+        resume: resume.unwrap_or(false),
+    };
+    start_transfer(request, server_id, state, app).await
+}
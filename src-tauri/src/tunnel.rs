@@ -0,0 +1,105 @@
+use crate::types::CommandError;
+use ssh2::{Channel, Session};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+const BUFFER_SIZE: usize = 32 * 1024;
+const IDLE_SLEEP: Duration = Duration::from_millis(5);
+
+/// Open a `direct-tcpip` channel from `jump` to `host:port` and expose it as a local
+/// socket, so the next session can treat it like an ordinary TCP connection.
+///
+/// The jump session is moved into a forwarding thread that lives until either end closes.
+pub fn open_direct_tcpip(jump: Session, host: &str, port: u16) -> Result<TcpStream, CommandError> {
+    let channel = jump.channel_direct_tcpip(host, port, None).map_err(|e| CommandError {
+        message: format!("Failed to open tunnel to {}:{}: {}", host, port, e),
+        code: -1,
+    })?;
+
+    let (local, forwarded) = socket_pair().map_err(|e| CommandError {
+        message: format!("Failed to create local tunnel socket: {}", e),
+        code: -1,
+    })?;
+
+    let target = format!("{}:{}", host, port);
+    std::thread::spawn(move || {
+        if let Err(e) = forward(&jump, channel, forwarded) {
+            log::debug!("Tunnel to {} closed: {}", target, e);
+        }
+    });
+
+    Ok(local)
+}
+
+/// A connected pair of loopback sockets (portable stand-in for socketpair(2))
+fn socket_pair() -> std::io::Result<(TcpStream, TcpStream)> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let client = TcpStream::connect(listener.local_addr()?)?;
+    loop {
+        let (server, peer) = listener.accept()?;
+        // Make sure no other local process raced us to the listener
+        if peer == client.local_addr()? {
+            return Ok((client, server));
+        }
+    }
+}
+
+/// Copy bytes both ways between the channel and the local socket until either side closes
+fn forward(jump: &Session, mut channel: Channel, mut local: TcpStream) -> std::io::Result<()> {
+    jump.set_blocking(false);
+    local.set_nonblocking(true)?;
+
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    loop {
+        let mut idle = true;
+
+        match local.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                write_all(&mut channel, &buf[..n])?;
+                idle = false;
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e),
+        }
+
+        match channel.read(&mut buf) {
+            Ok(0) if channel.eof() => break,
+            Ok(0) => {}
+            Ok(n) => {
+                write_all(&mut local, &buf[..n])?;
+                idle = false;
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e),
+        }
+
+        if idle {
+            std::thread::sleep(IDLE_SLEEP);
+        }
+    }
+
+    let _ = channel.send_eof();
+    let _ = channel.close();
+    Ok(())
+}
+
+/// `write_all` for non-blocking writers
+fn write_all<W: Write>(writer: &mut W, mut data: &[u8]) -> std::io::Result<()> {
+    while !data.is_empty() {
+        match writer.write(data) {
+            Ok(0) => return Err(ErrorKind::WriteZero.into()),
+            Ok(n) => data = &data[n..],
+            Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(IDLE_SLEEP),
+            Err(e) => return Err(e),
+        }
+    }
+    loop {
+        match writer.flush() {
+            Ok(()) => return Ok(()),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(IDLE_SLEEP),
+            Err(e) => return Err(e),
+        }
+    }
+}
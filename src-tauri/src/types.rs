@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerProfile {
@@ -8,6 +9,20 @@ pub struct ServerProfile {
     pub port: u16,
     pub username: String,
     pub auth_method: AuthMethod,
+    /// Bastions to tunnel through, in connection order
+    #[serde(default)]
+    pub jump_hosts: Vec<JumpHost>,
+    /// How commands that need root get it
+    #[serde(default)]
+    pub escalation: Escalation,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JumpHost {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub auth_method: AuthMethod,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,9 +33,65 @@ pub struct SavedServerProfile {
     pub port: u16,
     pub username: String,
     pub auth_method: AuthMethod,
+    #[serde(default)]
+    pub jump_hosts: Vec<JumpHost>,
+    #[serde(default)]
+    pub escalation: Escalation,
     pub created_at: u64,
     pub last_connected: Option<u64>,
     pub connect_on_startup: bool,
+    #[serde(flatten)]
+    pub labels: ProfileLabels,
+}
+
+/// Deployment stage a server belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Environment {
+    Prod,
+    Staging,
+    Dev,
+}
+
+/// How a saved profile is organised in the server list
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProfileLabels {
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Folder path, outermost first, separated by `/`, e.g. `clients/acme`
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub environment: Option<Environment>,
+    /// Badge color as the UI stores it, e.g. `#e03131` or `red`
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+impl ProfileLabels {
+    /// Trim everything, drop empty and duplicate tags, and tidy the group path
+    pub fn normalized(self) -> Self {
+        let mut tags: Vec<String> = Vec::new();
+        for tag in self.tags {
+            let tag = tag.trim();
+            if !tag.is_empty() && !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                tags.push(tag.to_string());
+            }
+        }
+        let group = self.group.map(|group| {
+            group.split('/').map(str::trim).filter(|s| !s.is_empty()).collect::<Vec<_>>().join("/")
+        });
+        let non_empty = |value: Option<String>| value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        ProfileLabels {
+            tags,
+            group: non_empty(group),
+            environment: self.environment,
+            color: non_empty(self.color),
+            notes: non_empty(self.notes),
+        }
+    }
 }
 
 impl From<ServerProfile> for SavedServerProfile {
@@ -32,16 +103,28 @@ impl From<ServerProfile> for SavedServerProfile {
             port: profile.port,
             username: profile.username,
             auth_method: profile.auth_method,
+            jump_hosts: profile.jump_hosts,
+            escalation: profile.escalation,
             created_at: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_millis() as u64,
             last_connected: None,
             connect_on_startup: false,
+            labels: ProfileLabels::default(),
         }
     }
 }
 
+/// A profile id that can be used in a file name. Ids may contain characters that
+/// aren't allowed there, e.g. `ssh-config:web`.
+pub fn profile_file_id(profile_id: &str) -> String {
+    profile_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+        .collect()
+}
+
 impl From<SavedServerProfile> for ServerProfile {
     fn from(profile: SavedServerProfile) -> Self {
         ServerProfile {
@@ -51,6 +134,8 @@ impl From<SavedServerProfile> for ServerProfile {
             port: profile.port,
             username: profile.username,
             auth_method: profile.auth_method,
+            jump_hosts: profile.jump_hosts,
+            escalation: profile.escalation,
         }
     }
 }
@@ -60,15 +145,76 @@ impl From<SavedServerProfile> for ServerProfile {
 pub enum AuthMethod {
     Password { password: String },
     PrivateKey { key_path: String, passphrase: Option<String> },
+    /// Keys held by ssh-agent (or Pageant); `identity` picks one by comment or SHA256 fingerprint
+    Agent { identity: Option<String> },
+    /// Server-driven prompts (PAM, OTP) answered by the user at connect time
+    KeyboardInteractive,
 }
 
 // Helper for SSH command building
 impl AuthMethod {
     pub fn is_key_based(&self) -> bool {
-        matches!(self, AuthMethod::PrivateKey { .. })
+        matches!(self, AuthMethod::PrivateKey { .. } | AuthMethod::Agent { .. })
     }
 }
 
+/// How a profile runs commands as root
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Escalation {
+    /// The login user is root already
+    None,
+    /// sudo without a password (NOPASSWD)
+    #[default]
+    Sudo,
+    /// sudo that asks for a password; without one the login password is tried
+    SudoPassword { password: Option<String> },
+    /// doas without a password (`permit nopass`)
+    Doas,
+    /// su with the root password
+    Su { password: String },
+}
+
+impl Escalation {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Escalation::None => "none",
+            Escalation::Sudo => "sudo",
+            Escalation::SudoPassword { .. } => "sudo_password",
+            Escalation::Doas => "doas",
+            Escalation::Su { .. } => "su",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthChallenge {
+    pub username: String,
+    pub instructions: String,
+    pub prompts: Vec<AuthPrompt>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthPrompt {
+    pub text: String,
+    /// Whether the answer may be shown while typing (false for passwords and codes)
+    pub echo: bool,
+}
+
+/// Payload of the `ssh://auth-prompt` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthPromptRequest {
+    pub request_id: String,
+    pub host: String,
+    pub challenge: AuthChallenge,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentIdentity {
+    pub comment: String,
+    pub fingerprint: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemMetrics {
     pub cpu_percent: f64,
@@ -155,6 +301,95 @@ pub struct LoginAttempt {
 pub struct ConnectionResult {
     pub success: bool,
     pub message: String,
+    /// Host key presented by the server, so the UI can show its fingerprint for trust decisions
+    pub host_key: Option<HostKeyInfo>,
+    /// Hop that failed, counting jump hosts first and the target last
+    pub failed_hop: Option<usize>,
+    /// Connected, but something that should have happened alongside didn't, e.g. the
+    /// profile couldn't be saved because the credential vault is locked
+    pub warning: Option<String>,
+    /// Whether root commands will work, checked right after connecting
+    pub privileges: Option<PrivilegeStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RootPanel {
+    pub panel: String,
+    pub actions: String,
+}
+
+/// Whether this connection can act as root, and which panels depend on it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrivilegeStatus {
+    pub escalation: String,
+    pub is_root: bool,
+    pub can_escalate: bool,
+    /// Why escalation failed, as reported by sudo/doas/su
+    pub message: Option<String>,
+    pub root_panels: Vec<RootPanel>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostKeyInfo {
+    pub host: String,
+    pub port: u16,
+    pub key_type: String,
+    pub fingerprint: String,
+    pub status: HostKeyStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HostKeyStatus {
+    Trusted,
+    Unknown,
+    Changed,
+}
+
+/// Payload of `terminal://output` events; `data` is base64 since output may split UTF-8 sequences
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerminalOutput {
+    pub terminal_id: String,
+    pub data: String,
+}
+
+/// Payload of `terminal://exit` events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerminalExit {
+    pub terminal_id: String,
+    pub exit_code: Option<i32>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionState {
+    Connected,
+    Reconnecting,
+    Failed,
+    Disconnected,
+}
+
+/// Payload of `ssh://connection-state` events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionStateEvent {
+    pub server_id: String,
+    pub host: String,
+    pub state: ConnectionState,
+    /// Reconnect attempt this event belongs to, 0 outside reconnects
+    pub attempt: u32,
+    pub message: Option<String>,
+}
+
+/// Payload of `metrics://update` events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricsUpdateEvent {
+    pub server_id: String,
+    /// None when the reading failed
+    pub metrics: Option<SystemMetrics>,
+    pub error: Option<String>,
+    /// When the next reading is due; grows while the server can't be reached
+    pub next_poll_ms: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -163,12 +398,220 @@ pub struct CommandError {
     pub code: i32,
 }
 
+/// Everything a remote command produced, whatever its exit status
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: i32,
+    pub duration: Duration,
+}
+
+impl CommandOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == 0
+    }
+
+    /// Stdout on success, otherwise an error with stderr (or stdout if stderr is empty)
+    pub fn into_result(self) -> Result<String, CommandError> {
+        if self.success() {
+            return Ok(self.stdout);
+        }
+
+        let detail = if self.stderr.trim().is_empty() { &self.stdout } else { &self.stderr };
+        let detail = detail.trim();
+        Err(CommandError {
+            message: if detail.is_empty() {
+                format!("Command failed with exit code {}", self.exit_code)
+            } else {
+                detail.to_string()
+            },
+            code: self.exit_code,
+        })
+    }
+}
+
+impl CommandError {
+    /// The server's host key is not in any known_hosts file yet
+    pub const HOST_KEY_UNKNOWN: i32 = -10;
+    /// The server's host key differs from the recorded one
+    pub const HOST_KEY_CHANGED: i32 = -11;
+    /// A remote command ran past its timeout and was abandoned
+    pub const TIMEOUT: i32 = -20;
+    /// A remote command was cancelled by the user
+    pub const CANCELLED: i32 = -21;
+    /// The client has no live connection to the server
+    pub const NOT_CONNECTED: i32 = -30;
+
+    pub fn not_connected() -> Self {
+        CommandError {
+            message: "Not connected".to_string(),
+            code: CommandError::NOT_CONNECTED,
+        }
+    }
+}
+
 impl From<String> for CommandError {
     fn from(message: String) -> Self {
         CommandError { message, code: -1 }
     }
 }
 
+/// `docker <action> <container>` subcommands the panel may run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerAction {
+    Start,
+    Stop,
+    Restart,
+    Pause,
+    Unpause,
+}
+
+impl ContainerAction {
+    pub fn as_arg(self) -> &'static str {
+        match self {
+            ContainerAction::Start => "start",
+            ContainerAction::Stop => "stop",
+            ContainerAction::Restart => "restart",
+            ContainerAction::Pause => "pause",
+            ContainerAction::Unpause => "unpause",
+        }
+    }
+}
+
+/// `systemctl <action> <unit>` verbs the panel may run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceAction {
+    Start,
+    Stop,
+    Restart,
+    Reload,
+    Enable,
+    Disable,
+}
+
+impl ServiceAction {
+    pub fn as_arg(self) -> &'static str {
+        match self {
+            ServiceAction::Start => "start",
+            ServiceAction::Stop => "stop",
+            ServiceAction::Restart => "restart",
+            ServiceAction::Reload => "reload",
+            ServiceAction::Enable => "enable",
+            ServiceAction::Disable => "disable",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UfwAction {
+    Enable,
+    Disable,
+    Reset,
+    Reload,
+}
+
+/// What a firewall rule does with matching traffic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UfwRuleType {
+    Allow,
+    Deny,
+    Reject,
+    Limit,
+}
+
+impl UfwRuleType {
+    pub fn as_arg(self) -> &'static str {
+        match self {
+            UfwRuleType::Allow => "allow",
+            UfwRuleType::Deny => "deny",
+            UfwRuleType::Reject => "reject",
+            UfwRuleType::Limit => "limit",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UfwProtocol {
+    Tcp,
+    Udp,
+}
+
+impl UfwProtocol {
+    pub fn as_arg(self) -> &'static str {
+        match self {
+            UfwProtocol::Tcp => "tcp",
+            UfwProtocol::Udp => "udp",
+        }
+    }
+}
+
+/// Default policy; unlike rules a default can't be `limit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UfwPolicy {
+    Allow,
+    Deny,
+    Reject,
+}
+
+impl UfwPolicy {
+    pub fn as_arg(self) -> &'static str {
+        match self {
+            UfwPolicy::Allow => "allow",
+            UfwPolicy::Deny => "deny",
+            UfwPolicy::Reject => "reject",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UfwDirection {
+    Incoming,
+    Outgoing,
+    Routed,
+}
+
+impl UfwDirection {
+    pub fn as_arg(self) -> &'static str {
+        match self {
+            UfwDirection::Incoming => "incoming",
+            UfwDirection::Outgoing => "outgoing",
+            UfwDirection::Routed => "routed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UfwLogLevel {
+    On,
+    Off,
+    Low,
+    Medium,
+    High,
+    Full,
+}
+
+impl UfwLogLevel {
+    pub fn as_arg(self) -> &'static str {
+        match self {
+            UfwLogLevel::On => "on",
+            UfwLogLevel::Off => "off",
+            UfwLogLevel::Low => "low",
+            UfwLogLevel::Medium => "medium",
+            UfwLogLevel::High => "high",
+            UfwLogLevel::Full => "full",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UfwStatus {
     pub active: bool,
@@ -1,4 +1,6 @@
-use crate::ssh::SshClient;
+use crate::remote_file::{self, WriteOptions};
+use crate::shell::{self, Cmd};
+use crate::ssh::{CommandCategory, SshClient};
 use crate::types::*;
 use crate::commands::AppState;
 use std::sync::Arc;
@@ -6,177 +8,242 @@ use tauri::State;
 use tokio::sync::Mutex;
 
 #[tauri::command]
-pub async fn get_system_users(state: State<'_, AppState>) -> Result<Vec<SystemUser>, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-
-    let passwd_output = client.execute_command("cat /etc/passwd").map_err(|e| e.message)?;
-    let locked_output = client.execute_command("sudo awk -F: '/^!/ || /^\\*/ || /!\\*/ || /!!/ {print $1}' /etc/shadow 2>/dev/null || echo ''").unwrap_or_default();
-    let passwd_users_output = client.execute_command("sudo awk -F: '($2 != \"x\" && $2 != \"!\" && $2 != \"*\" && $2 != \"!!\") {print $1}' /etc/shadow 2>/dev/null || echo ''").unwrap_or_default();
-
-    let locked_users: std::collections::HashSet<String> = locked_output.lines().map(|s| s.trim().to_string()).collect();
-    let users_with_password: std::collections::HashSet<String> = passwd_users_output.lines().map(|s| s.trim().to_string()).collect();
-
-    let mut users = Vec::new();
-    for line in passwd_output.lines() {
-        let parts: Vec<&str> = line.split(':').collect();
-        if parts.len() >= 7 {
-            let username = parts[0].to_string();
-            let uid: u32 = parts[2].parse().unwrap_or(0);
-            if uid < 1000 && uid != 0 { continue; }
-
-            let gid: u32 = parts[3].parse().unwrap_or(0);
-            let gecos = parts[4].to_string();
-            let home = parts[5].to_string();
-            let shell = parts[6].to_string();
-
-            let groups_output = client.execute_command(&format!("id -Gn {} 2>/dev/null", username)).unwrap_or_default();
-            let groups: Vec<String> = groups_output.trim().split_whitespace().map(|s| s.to_string()).collect();
-
-            let last_login_output = client.execute_command(&format!("lastlog -u {} 2>/dev/null | tail -1 | awk '{{print $4, $5, $6, $7}}'", username)).unwrap_or_default();
-            let last_login = if last_login_output.trim().is_empty() || last_login_output.contains("Never") { None } else { Some(last_login_output.trim().to_string()) };
-
-            users.push(SystemUser { username: username.clone(), uid, gid, groups, home, shell, gecos, locked: locked_users.contains(&username), has_password: users_with_password.contains(&username), last_login });
+pub async fn get_system_users(server_id: Option<String>, state: State<'_, AppState>) -> Result<Vec<SystemUser>, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let passwd_output = client.execute_command("cat /etc/passwd").map_err(|e| e.message)?;
+        let locked_output = client.execute_command("sudo awk -F: '/^!/ || /^\\*/ || /!\\*/ || /!!/ {print $1}' /etc/shadow 2>/dev/null || echo ''").unwrap_or_default();
+        let passwd_users_output = client.execute_command("sudo awk -F: '($2 != \"x\" && $2 != \"!\" && $2 != \"*\" && $2 != \"!!\") {print $1}' /etc/shadow 2>/dev/null || echo ''").unwrap_or_default();
+
+        let locked_users: std::collections::HashSet<String> = locked_output.lines().map(|s| s.trim().to_string()).collect();
+        let users_with_password: std::collections::HashSet<String> = passwd_users_output.lines().map(|s| s.trim().to_string()).collect();
+
+        let mut users = Vec::new();
+        for line in passwd_output.lines() {
+            let parts: Vec<&str> = line.split(':').collect();
+            if parts.len() >= 7 {
+                let username = parts[0].to_string();
+                let uid: u32 = parts[2].parse().unwrap_or(0);
+                if uid < 1000 && uid != 0 { continue; }
+
+                let gid: u32 = parts[3].parse().unwrap_or(0);
+                let gecos = parts[4].to_string();
+                let home = parts[5].to_string();
+                let shell = parts[6].to_string();
+
+                let groups_output = client.execute_command(&Cmd::new("id").raw("-Gn").arg(&username).raw("2>/dev/null")).unwrap_or_default();
+                let groups: Vec<String> = groups_output.trim().split_whitespace().map(|s| s.to_string()).collect();
+
+                let last_login_cmd = Cmd::new("lastlog").raw("-u").arg(&username).raw("2>/dev/null").pipe(Cmd::new("tail").raw("-1")).pipe(Cmd::new("awk").raw("'{print $4, $5, $6, $7}'"));
+                let last_login_output = client.execute_command(&last_login_cmd).unwrap_or_default();
+                let last_login = if last_login_output.trim().is_empty() || last_login_output.contains("Never") { None } else { Some(last_login_output.trim().to_string()) };
+
+                users.push(SystemUser { username: username.clone(), uid, gid, groups, home, shell, gecos, locked: locked_users.contains(&username), has_password: users_with_password.contains(&username), last_login });
+            }
         }
-    }
-    users.sort_by(|a, b| a.username.cmp(&b.username));
-    Ok(users)
+        users.sort_by(|a, b| a.username.cmp(&b.username));
+        Ok(users)
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn get_system_groups(state: State<'_, AppState>) -> Result<Vec<SystemGroup>, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-    let group_output = client.execute_command("cat /etc/group").map_err(|e| e.message)?;
-    let mut groups = Vec::new();
-    for line in group_output.lines() {
-        let parts: Vec<&str> = line.split(':').collect();
-        if parts.len() >= 4 {
-            let name = parts[0].to_string();
-            let gid: u32 = parts[2].parse().unwrap_or(0);
-            let members: Vec<String> = if !parts[3].is_empty() { parts[3].split(',').map(|s| s.to_string()).collect() } else { Vec::new() };
-            groups.push(SystemGroup { name, gid, members });
+pub async fn get_system_groups(server_id: Option<String>, state: State<'_, AppState>) -> Result<Vec<SystemGroup>, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let group_output = client.execute_command("cat /etc/group").map_err(|e| e.message)?;
+        let mut groups = Vec::new();
+        for line in group_output.lines() {
+            let parts: Vec<&str> = line.split(':').collect();
+            if parts.len() >= 4 {
+                let name = parts[0].to_string();
+                let gid: u32 = parts[2].parse().unwrap_or(0);
+                let members: Vec<String> = if !parts[3].is_empty() { parts[3].split(',').map(|s| s.to_string()).collect() } else { Vec::new() };
+                groups.push(SystemGroup { name, gid, members });
+            }
         }
-    }
-    groups.sort_by(|a, b| a.name.cmp(&b.name));
-    Ok(groups)
+        groups.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(groups)
+    })
+    .await
 }
 
-#[tauri::command]
-pub async fn create_user(request: CreateUserRequest, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-    let mut cmd = String::from("sudo useradd");
-    if request.create_home { cmd.push_str(" -m"); }
-    if let Some(home) = &request.home { cmd.push_str(&format!(" -d {}", home)); }
-    if let Some(shell) = &request.shell { cmd.push_str(&format!(" -s {}", shell)); }
-    if !request.groups.is_empty() { cmd.push_str(&format!(" -G {}", request.groups.join(","))); }
-    cmd.push_str(&format!(" {}", request.username));
-    client.execute_command(&cmd).map_err(|e| e.message)?;
-    if let Some(password) = &request.password {
-        client.execute_command(&format!("echo '{}:{}' | sudo chpasswd", request.username, password)).map_err(|e| e.message)?;
-    }
-    Ok(format!("User '{}' created successfully", request.username))
+/// `user:password` fed to chpasswd on stdin, so the password never appears in a command line
+fn chpasswd(username: &str, password: &str) -> Result<Cmd, String> {
+    shell::single_line("Password", password)?;
+    Ok(Cmd::new("printf").arg("%s\\n").arg(format!("{}:{}", shell::username(username)?, password)).pipe(Cmd::sudo("chpasswd")))
 }
 
 #[tauri::command]
-pub async fn delete_user(username: String, remove_home: bool, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-    if username == "root" || username == "admin" || username.is_empty() { return Err("Cannot delete protected user".to_string()); }
-    let cmd = if remove_home { format!("sudo userdel -r {}", username) } else { format!("sudo userdel {}", username) };
-    client.execute_command(&cmd).map_err(|e| e.message)?;
-    Ok(format!("User '{}' deleted successfully", username))
+pub async fn create_user(request: CreateUserRequest, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        shell::username(&request.username)?;
+        let mut cmd = Cmd::sudo("useradd");
+        if request.create_home { cmd = cmd.raw("-m"); }
+        if let Some(home) = &request.home { cmd = cmd.raw("-d").arg(home); }
+        if let Some(login_shell) = &request.shell { cmd = cmd.raw("-s").arg(login_shell); }
+        if !request.groups.is_empty() {
+            let groups = request.groups.iter().map(|g| shell::username(g)).collect::<Result<Vec<_>, _>>()?;
+            cmd = cmd.raw("-G").arg(groups.join(","));
+        }
+        let password_cmd = request.password.as_deref().map(|password| chpasswd(&request.username, password)).transpose()?;
+        cmd = cmd.raw("--").arg(&request.username);
+        client.execute_checked(&cmd, CommandCategory::Action).map_err(|e| e.message)?;
+        if let Some(password_cmd) = password_cmd {
+            client.execute_checked(&password_cmd, CommandCategory::Action).map_err(|e| e.message)?;
+        }
+        Ok(format!("User '{}' created successfully", request.username))
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn lock_user(username: String, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-    client.execute_command(&format!("sudo usermod -L {}", username)).map_err(|e| e.message)?;
-    Ok(format!("User '{}' locked", username))
+pub async fn delete_user(username: String, remove_home: bool, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        if username == "root" || username == "admin" || username.is_empty() { return Err("Cannot delete protected user".to_string()); }
+        let mut cmd = Cmd::sudo("userdel");
+        if remove_home { cmd = cmd.raw("-r"); }
+        let cmd = cmd.raw("--").arg(shell::username(&username)?);
+        client.execute_checked(&cmd, CommandCategory::Action).map_err(|e| e.message)?;
+        Ok(format!("User '{}' deleted successfully", username))
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn unlock_user(username: String, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-    client.execute_command(&format!("sudo usermod -U {}", username)).map_err(|e| e.message)?;
-    Ok(format!("User '{}' unlocked", username))
+pub async fn lock_user(username: String, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        client.execute_checked(&Cmd::sudo("usermod").raw("-L --").arg(shell::username(&username)?), CommandCategory::Action).map_err(|e| e.message)?;
+        Ok(format!("User '{}' locked", username))
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn set_user_password(username: String, new_password: String, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-    client.execute_command(&format!("echo '{}:{}' | sudo chpasswd", username, new_password)).map_err(|e| e.message)?;
-    Ok(format!("Password updated for user '{}'", username))
+pub async fn unlock_user(username: String, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        client.execute_checked(&Cmd::sudo("usermod").raw("-U --").arg(shell::username(&username)?), CommandCategory::Action).map_err(|e| e.message)?;
+        Ok(format!("User '{}' unlocked", username))
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn add_user_to_group(username: String, group: String, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-    client.execute_command(&format!("sudo usermod -aG {} {}", group, username)).map_err(|e| e.message)?;
-    Ok(format!("User '{}' added to group '{}'", username, group))
+pub async fn set_user_password(username: String, new_password: String, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        client.execute_checked(&chpasswd(&username, &new_password)?, CommandCategory::Action).map_err(|e| e.message)?;
+        Ok(format!("Password updated for user '{}'", username))
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn remove_user_from_group(username: String, group: String, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-    client.execute_command(&format!("sudo gpasswd -d {} {}", username, group)).map_err(|e| e.message)?;
-    Ok(format!("User '{}' removed from group '{}'", username, group))
+pub async fn add_user_to_group(username: String, group: String, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        client.execute_checked(&Cmd::sudo("usermod").raw("-aG").arg(shell::username(&group)?).raw("--").arg(shell::username(&username)?), CommandCategory::Action).map_err(|e| e.message)?;
+        Ok(format!("User '{}' added to group '{}'", username, group))
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn add_ssh_key(username: String, key: String, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-    let home_output = client.execute_command(&format!("getent passwd {} | cut -d: -f6", username)).map_err(|e| e.message)?;
-    let home = home_output.trim();
-    if home.is_empty() { return Err("User home directory not found".to_string()); }
-    client.execute_command(&format!("sudo mkdir -p {}/.ssh && sudo chmod 700 {}/.ssh", home, home)).map_err(|e| e.message)?;
-    client.execute_command(&format!("echo '{}' | sudo tee -a {}/.ssh/authorized_keys", key, home)).map_err(|e| e.message)?;
-    client.execute_command(&format!("sudo chown -R {}: {}/.ssh && sudo chmod 600 {}/.ssh/authorized_keys", username, home, home)).map_err(|e| e.message)?;
-    Ok("SSH key added successfully".to_string())
+pub async fn remove_user_from_group(username: String, group: String, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        client.execute_checked(&Cmd::sudo("gpasswd").raw("-d").arg(shell::username(&username)?).arg(shell::username(&group)?), CommandCategory::Action).map_err(|e| e.message)?;
+        Ok(format!("User '{}' removed from group '{}'", username, group))
+    })
+    .await
 }
 
-#[tauri::command]
-pub async fn delete_ssh_key(username: String, key_index: usize, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-    let home_output = client.execute_command(&format!("getent passwd {} | cut -d: -f6", username)).map_err(|e| e.message)?;
+/// Append `key` to the user's authorized_keys unless it is already there. Returns false when it was.
+pub(crate) fn install_authorized_key(client: &SshClient, username: &str, key: &str) -> Result<bool, String> {
+    let home_output = client.execute_command(&Cmd::new("getent").raw("passwd").arg(shell::username(username)?).pipe(Cmd::new("cut").raw("-d: -f6"))).map_err(|e| e.message)?;
     let home = home_output.trim();
     if home.is_empty() { return Err("User home directory not found".to_string()); }
-    let keys_output = client.execute_command(&format!("sudo cat {}/.ssh/authorized_keys 2>/dev/null || echo ''", home)).unwrap_or_default();
-    let keys: Vec<&str> = keys_output.lines().filter(|l| !l.trim().is_empty()).collect();
-    if key_index >= keys.len() { return Err("Invalid key index".to_string()); }
-    let new_keys: Vec<&str> = keys.iter().enumerate().filter(|(i, _)| *i != key_index).map(|(_, &k)| k).collect();
-    if new_keys.is_empty() {
-        client.execute_command(&format!("sudo rm -f {}/.ssh/authorized_keys", home)).map_err(|e| e.message)?;
-    } else {
-        let new_content = new_keys.join("\n");
-        client.execute_command(&format!("echo '{}' | sudo tee {}/.ssh/authorized_keys", new_content, home)).map_err(|e| e.message)?;
+    let key = key.trim();
+    shell::single_line("Key", key)?;
+    let ssh_dir = format!("{}/.ssh", home);
+    let keys_path = format!("{}/authorized_keys", ssh_dir);
+    client.execute_checked(&Cmd::sudo("mkdir").raw("-p").arg(&ssh_dir).and(Cmd::sudo("chmod").raw("700").arg(&ssh_dir)), CommandCategory::Action).map_err(|e| e.message)?;
+    let existing = client.run_command(&Cmd::sudo("cat").arg(&keys_path), CommandCategory::Query.timeout(), None).map_err(|e| e.message)?;
+    // A missing file just means no keys yet; anything else must not be overwritten blindly
+    if !existing.success() && !existing.stderr.contains("No such file") {
+        return Err(format!("Failed to read authorized_keys: {}", existing.stderr.trim()));
     }
-    Ok("SSH key deleted successfully".to_string())
+    if existing.stdout.lines().any(|line| line.trim() == key) { return Ok(false); }
+    let mut content = existing.stdout;
+    if !content.is_empty() && !content.ends_with('\n') { content.push('\n'); }
+    content.push_str(key);
+    content.push('\n');
+    let owner = format!("{}:", username);
+    let options = WriteOptions { sudo: true, mode: 0o600, owner: Some(&owner) };
+    remote_file::write_file(client, &keys_path, content.as_bytes(), options).map_err(|e| e.message)?;
+    client.execute_checked(&Cmd::sudo("chown").arg(&owner).arg(&ssh_dir), CommandCategory::Action).map_err(|e| e.message)?;
+    Ok(true)
 }
 
 #[tauri::command]
-pub async fn create_group(group_name: String, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-    client.execute_command(&format!("sudo groupadd {}", group_name)).map_err(|e| e.message)?;
-    Ok(format!("Group '{}' created successfully", group_name))
+pub async fn add_ssh_key(username: String, key: String, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        if install_authorized_key(client, &username, &key)? {
+            Ok("SSH key added successfully".to_string())
+        } else {
+            Ok("SSH key is already authorized".to_string())
+        }
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn delete_group(group_name: String, state: State<'_, AppState>) -> Result<String, String> {
-    let ssh_client = state.ssh_client.lock().await;
-    let client = ssh_client.as_ref().ok_or("Not connected")?;
-    if group_name == "root" || group_name == "sudo" || group_name == "wheel" || group_name.is_empty() {
-        return Err("Cannot delete protected group".to_string());
-    }
-    client.execute_command(&format!("sudo groupdel {}", group_name)).map_err(|e| e.message)?;
-    Ok(format!("Group '{}' deleted successfully", group_name))
+pub async fn delete_ssh_key(username: String, key_index: usize, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        let home_output = client.execute_command(&Cmd::new("getent").raw("passwd").arg(shell::username(&username)?).pipe(Cmd::new("cut").raw("-d: -f6"))).map_err(|e| e.message)?;
+        let home = home_output.trim();
+        if home.is_empty() { return Err("User home directory not found".to_string()); }
+        let keys_path = format!("{}/.ssh/authorized_keys", home);
+        let keys_output = client.execute_command(&Cmd::sudo("cat").arg(&keys_path).raw("2>/dev/null").or(Cmd::new("echo").arg(""))).unwrap_or_default();
+        let keys: Vec<&str> = keys_output.lines().filter(|l| !l.trim().is_empty()).collect();
+        if key_index >= keys.len() { return Err("Invalid key index".to_string()); }
+        let new_keys: Vec<&str> = keys.iter().enumerate().filter(|(i, _)| *i != key_index).map(|(_, &k)| k).collect();
+        if new_keys.is_empty() {
+            client.execute_checked(&Cmd::sudo("rm").raw("-f").arg(&keys_path), CommandCategory::Action).map_err(|e| e.message)?;
+        } else {
+            let new_content = format!("{}\n", new_keys.join("\n"));
+            remote_file::write_file(client, &keys_path, new_content.as_bytes(), WriteOptions::root(0o600)).map_err(|e| e.message)?;
+        }
+        Ok("SSH key deleted successfully".to_string())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn create_group(group_name: String, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        client.execute_checked(&Cmd::sudo("groupadd").raw("--").arg(shell::username(&group_name)?), CommandCategory::Action).map_err(|e| e.message)?;
+        Ok(format!("Group '{}' created successfully", group_name))
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn delete_group(group_name: String, server_id: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let client = state.client(server_id.as_deref()).await?;
+    client.run_blocking(move |client| {
+        if group_name == "root" || group_name == "sudo" || group_name == "wheel" || group_name.is_empty() {
+            return Err("Cannot delete protected group".to_string());
+        }
+        client.execute_checked(&Cmd::sudo("groupdel").raw("--").arg(shell::username(&group_name)?), CommandCategory::Action).map_err(|e| e.message)?;
+        Ok(format!("Group '{}' deleted successfully", group_name))
+    })
+    .await
 }
@@ -0,0 +1,556 @@
+use crate::commands::{profiles_from_json, profiles_to_json, PROFILES_KEY, STORE_FILENAME};
+use crate::types::*;
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_store::StoreExt;
+use zeroize::Zeroizing;
+
+pub const VAULT_FILENAME: &str = "credential_vault.json";
+const VAULT_KEY: &str = "vault";
+pub const VAULT_LOCKED_EVENT: &str = "vault://locked";
+
+/// Saved profiles hold `vault:<entry id>` in place of each password or passphrase
+pub const SECRET_REF_PREFIX: &str = "vault:";
+
+const DEFAULT_AUTO_LOCK_SECS: u64 = 15 * 60;
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// Encrypted on creation so a wrong master password is caught at unlock
+const CHECK_PLAINTEXT: &[u8] = b"dpanel-vault";
+
+/// Argon2id cost; stored alongside the salt so it can be raised later
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub salt: String,
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl KdfParams {
+    pub fn generate() -> Self {
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        KdfParams {
+            salt: STANDARD.encode(salt),
+            memory_kib: 64 * 1024,
+            iterations: 3,
+            parallelism: 1,
+        }
+    }
+
+    pub fn derive_key(&self, password: &str) -> Result<Zeroizing<[u8; 32]>, String> {
+        let salt = STANDARD.decode(&self.salt).map_err(|e| format!("Corrupt salt: {}", e))?;
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|e| format!("Invalid key derivation parameters: {}", e))?;
+        let mut key = Zeroizing::new([0u8; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(password.as_bytes(), &salt, key.as_mut())
+            .map_err(|e| format!("Key derivation failed: {}", e))?;
+        Ok(key)
+    }
+}
+
+/// XChaCha20-Poly1305 ciphertext; `aad` binds it to where it is stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sealed {
+    pub nonce: String,
+    pub data: String,
+}
+
+impl Sealed {
+    pub fn encrypt(key: &[u8; 32], plaintext: &[u8], aad: &[u8]) -> Result<Self, String> {
+        let cipher = XChaCha20Poly1305::new(key.into());
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let data = cipher
+            .encrypt(&nonce, Payload { msg: plaintext, aad })
+            .map_err(|_| "Encryption failed".to_string())?;
+        Ok(Sealed {
+            nonce: STANDARD.encode(nonce),
+            data: STANDARD.encode(data),
+        })
+    }
+
+    /// None when the key is wrong or the data was tampered with
+    pub fn decrypt(&self, key: &[u8; 32], aad: &[u8]) -> Option<Zeroizing<Vec<u8>>> {
+        let nonce = STANDARD.decode(&self.nonce).ok().filter(|n| n.len() == 24)?;
+        let data = STANDARD.decode(&self.data).ok()?;
+        XChaCha20Poly1305::new(key.into())
+            .decrypt(XNonce::from_slice(&nonce), Payload { msg: &data, aad })
+            .ok()
+            .map(Zeroizing::new)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct VaultFile {
+    kdf: KdfParams,
+    check: Sealed,
+    #[serde(default = "default_auto_lock")]
+    auto_lock_secs: u64,
+    #[serde(default)]
+    entries: HashMap<String, Sealed>,
+}
+
+fn default_auto_lock() -> u64 {
+    DEFAULT_AUTO_LOCK_SECS
+}
+
+/// A profile type whose passwords and passphrases can be moved into the vault
+pub trait ProfileSecrets {
+    fn profile_id(&self) -> &str;
+    /// Each secret with a slot name that is stable for the profile
+    fn secrets_mut(&mut self) -> Vec<(String, &mut String)>;
+}
+
+fn auth_secret(auth: &mut AuthMethod) -> Option<&mut String> {
+    match auth {
+        AuthMethod::Password { password } => Some(password),
+        AuthMethod::PrivateKey { passphrase: Some(passphrase), .. } => Some(passphrase),
+        _ => None,
+    }
+}
+
+fn collect_secrets<'a>(
+    auth: &'a mut AuthMethod,
+    jump_hosts: &'a mut [JumpHost],
+    escalation: &'a mut Escalation,
+) -> Vec<(String, &'a mut String)> {
+    let mut secrets = Vec::new();
+    if let Some(secret) = auth_secret(auth) {
+        secrets.push(("auth".to_string(), secret));
+    }
+    for (index, jump) in jump_hosts.iter_mut().enumerate() {
+        if let Some(secret) = auth_secret(&mut jump.auth_method) {
+            secrets.push((format!("jump{}", index), secret));
+        }
+    }
+    match escalation {
+        Escalation::SudoPassword { password: Some(password) } | Escalation::Su { password } => {
+            secrets.push(("escalation".to_string(), password));
+        }
+        _ => {}
+    }
+    secrets
+}
+
+impl ProfileSecrets for ServerProfile {
+    fn profile_id(&self) -> &str {
+        &self.id
+    }
+
+    fn secrets_mut(&mut self) -> Vec<(String, &mut String)> {
+        collect_secrets(&mut self.auth_method, &mut self.jump_hosts, &mut self.escalation)
+    }
+}
+
+impl ProfileSecrets for SavedServerProfile {
+    fn profile_id(&self) -> &str {
+        &self.id
+    }
+
+    fn secrets_mut(&mut self) -> Vec<(String, &mut String)> {
+        collect_secrets(&mut self.auth_method, &mut self.jump_hosts, &mut self.escalation)
+    }
+}
+
+pub fn is_secret_ref(value: &str) -> bool {
+    value.starts_with(SECRET_REF_PREFIX)
+}
+
+fn entry_prefix(profile_id: &str) -> String {
+    format!("{}/", profile_id)
+}
+
+/// The part of `seal` that doesn't touch the store. `key` is only needed when the
+/// profile holds plaintext secrets.
+fn seal_into(file: &mut VaultFile, key: Option<&[u8; 32]>, profile: &mut impl ProfileSecrets) -> Result<(), String> {
+    let id = profile.profile_id().to_string();
+    let mut referenced = Vec::new();
+    for (slot, secret) in profile.secrets_mut() {
+        if secret.is_empty() {
+            continue;
+        }
+        if let Some(entry_id) = secret.strip_prefix(SECRET_REF_PREFIX) {
+            referenced.push(entry_id.to_string());
+            continue;
+        }
+        let key = key.ok_or("Credential vault is locked")?;
+        let entry_id = format!("{}{}-{:08x}", entry_prefix(&id), slot, OsRng.next_u32());
+        let sealed = Sealed::encrypt(key, secret.as_bytes(), entry_id.as_bytes())?;
+        file.entries.insert(entry_id.clone(), sealed);
+        *secret = format!("{}{}", SECRET_REF_PREFIX, entry_id);
+        referenced.push(entry_id);
+    }
+
+    let prefix = entry_prefix(&id);
+    file.entries
+        .retain(|entry_id, _| !entry_id.starts_with(&prefix) || referenced.contains(entry_id));
+    Ok(())
+}
+
+struct Unlocked {
+    key: Zeroizing<[u8; 32]>,
+    last_used: Instant,
+}
+
+/// The vault key while unlocked. `generation` changes on every lock and unlock so a
+/// stale idle watcher knows to stop.
+#[derive(Default)]
+pub struct VaultState {
+    inner: Mutex<Option<Unlocked>>,
+    generation: Mutex<u64>,
+}
+
+fn load_file(app: &AppHandle) -> Result<Option<VaultFile>, String> {
+    let store = app.store(VAULT_FILENAME).map_err(|e| format!("Failed to open vault: {}", e))?;
+    match store.get(VAULT_KEY) {
+        Some(value) => serde_json::from_value(value)
+            .map(Some)
+            .map_err(|e| format!("Vault file is corrupt: {}", e)),
+        None => Ok(None),
+    }
+}
+
+fn save_file(app: &AppHandle, file: &VaultFile) -> Result<(), String> {
+    let store = app.store(VAULT_FILENAME).map_err(|e| format!("Failed to open vault: {}", e))?;
+    let value = serde_json::to_value(file).map_err(|e| format!("Failed to serialize vault: {}", e))?;
+    store.set(VAULT_KEY, value);
+    store.save().map_err(|e| format!("Failed to save vault: {}", e))
+}
+
+impl VaultState {
+    pub fn is_unlocked(&self) -> bool {
+        self.inner.lock().unwrap().is_some()
+    }
+
+    /// The key, if unlocked; counts as activity for auto-lock
+    fn key(&self, auto_lock: Duration) -> Result<Zeroizing<[u8; 32]>, String> {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.as_mut() {
+            Some(unlocked) if auto_lock.is_zero() || unlocked.last_used.elapsed() < auto_lock => {
+                unlocked.last_used = Instant::now();
+                Ok(unlocked.key.clone())
+            }
+            Some(_) => {
+                *inner = None;
+                Err("Credential vault is locked".to_string())
+            }
+            None => Err("Credential vault is locked".to_string()),
+        }
+    }
+
+    fn unlock_with(&self, key: Zeroizing<[u8; 32]>) -> u64 {
+        *self.inner.lock().unwrap() = Some(Unlocked { key, last_used: Instant::now() });
+        let mut generation = self.generation.lock().unwrap();
+        *generation += 1;
+        *generation
+    }
+
+    pub fn lock(&self) {
+        *self.inner.lock().unwrap() = None;
+        *self.generation.lock().unwrap() += 1;
+    }
+
+    /// Lock if idle for `auto_lock`. Some(true) when this locked the vault, None when
+    /// `generation` is stale because it was locked or unlocked again since.
+    fn expire(&self, generation: u64, auto_lock: Duration) -> Option<bool> {
+        if *self.generation.lock().unwrap() != generation {
+            return None;
+        }
+        let idle = self
+            .inner
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_none_or(|u| !auto_lock.is_zero() && u.last_used.elapsed() >= auto_lock);
+        if idle {
+            self.lock();
+        }
+        Some(idle)
+    }
+
+    /// Move plaintext secrets into the vault, leaving references in the profile.
+    /// Entries of this profile that are no longer referenced are dropped. Until the
+    /// vault has been set up, profiles keep their secrets as before; the first unlock
+    /// moves them in.
+    pub fn seal(&self, app: &AppHandle, profile: &mut impl ProfileSecrets) -> Result<(), String> {
+        let Some(mut file) = load_file(app)? else {
+            return Ok(());
+        };
+        let needs_key = profile.secrets_mut().iter().any(|(_, s)| !s.is_empty() && !is_secret_ref(s));
+        let key = if needs_key {
+            Some(self.key(Duration::from_secs(file.auto_lock_secs))?)
+        } else {
+            None
+        };
+        seal_into(&mut file, key.as_deref(), profile)?;
+        save_file(app, &file)
+    }
+
+    /// Replace vault references in a profile with the secrets they point to
+    pub fn open(&self, app: &AppHandle, profile: &mut impl ProfileSecrets) -> Result<(), String> {
+        if !profile.secrets_mut().iter().any(|(_, s)| is_secret_ref(s)) {
+            return Ok(());
+        }
+        let file = load_file(app)?.ok_or("Saved credentials are missing: the credential vault does not exist")?;
+        let key = self.key(Duration::from_secs(file.auto_lock_secs))?;
+
+        for (slot, secret) in profile.secrets_mut() {
+            let Some(entry_id) = secret.strip_prefix(SECRET_REF_PREFIX) else {
+                continue;
+            };
+            let plaintext = file
+                .entries
+                .get(entry_id)
+                .and_then(|sealed| sealed.decrypt(&key, entry_id.as_bytes()))
+                .ok_or_else(|| format!("Saved credential '{}' could not be read from the vault", slot))?;
+            *secret = String::from_utf8_lossy(&plaintext).into_owned();
+        }
+        Ok(())
+    }
+
+    /// Fail up front, before anything is changed, when `seal` could not store new secrets
+    pub fn ensure_unlocked(&self, app: &AppHandle) -> Result<(), String> {
+        match load_file(app)? {
+            Some(file) => self.key(Duration::from_secs(file.auto_lock_secs)).map(|_| ()),
+            None => Ok(()),
+        }
+    }
+
+    /// Drop every entry of a deleted profile; works while locked
+    pub fn forget_profile(&self, app: &AppHandle, profile_id: &str) -> Result<(), String> {
+        let Some(mut file) = load_file(app)? else {
+            return Ok(());
+        };
+        let prefix = entry_prefix(profile_id);
+        let before = file.entries.len();
+        file.entries.retain(|entry_id, _| !entry_id.starts_with(&prefix));
+        if file.entries.len() != before {
+            save_file(app, &file)?;
+        }
+        Ok(())
+    }
+}
+
+fn watch_idle(app: AppHandle, generation: u64, auto_lock: Duration) {
+    thread::spawn(move || loop {
+        thread::sleep(IDLE_CHECK_INTERVAL);
+        match app.state::<VaultState>().expire(generation, auto_lock) {
+            Some(false) => continue,
+            Some(true) => {
+                log::info!("Credential vault locked after {}s idle", auto_lock.as_secs());
+                if let Err(e) = app.emit(VAULT_LOCKED_EVENT, ()) {
+                    log::warn!("Failed to emit vault lock: {}", e);
+                }
+                return;
+            }
+            None => return,
+        }
+    });
+}
+
+/// Encrypt plaintext secrets already saved in profiles; returns the ids of profiles changed
+fn migrate_profiles(app: &AppHandle, vault: &VaultState) -> Result<Vec<String>, String> {
+    let store = app.store(STORE_FILENAME).map_err(|e| format!("Failed to open profile store: {}", e))?;
+    let mut profiles = profiles_from_json(store.get(PROFILES_KEY));
+    let mut migrated = Vec::new();
+
+    for profile in profiles.values_mut() {
+        let has_plaintext = profile.secrets_mut().iter().any(|(_, s)| !s.is_empty() && !is_secret_ref(s));
+        if has_plaintext {
+            vault.seal(app, profile)?;
+            migrated.push(profile.id.clone());
+        }
+    }
+
+    if !migrated.is_empty() {
+        store.set(PROFILES_KEY, profiles_to_json(&profiles));
+        store.save().map_err(|e| format!("Failed to save profiles: {}", e))?;
+        log::info!("Moved credentials of {} profile(s) into the vault", migrated.len());
+    }
+    Ok(migrated)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultStatus {
+    pub initialized: bool,
+    pub unlocked: bool,
+    /// 0 means never
+    pub auto_lock_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultUnlockResult {
+    /// True when this unlock created the vault with the given master password
+    pub created: bool,
+    /// Profiles whose plaintext credentials were moved into the vault
+    pub migrated: Vec<String>,
+}
+
+#[tauri::command]
+pub async fn get_vault_status(vault: State<'_, VaultState>, app: AppHandle) -> Result<VaultStatus, String> {
+    let file = load_file(&app)?;
+    Ok(VaultStatus {
+        initialized: file.is_some(),
+        unlocked: vault.is_unlocked(),
+        auto_lock_secs: file.map(|f| f.auto_lock_secs).unwrap_or(DEFAULT_AUTO_LOCK_SECS),
+    })
+}
+
+/// Unlock the credential vault. The first unlock creates it with `master_password`
+/// and moves any plaintext credentials in saved profiles into it.
+#[tauri::command]
+pub async fn unlock_vault(
+    master_password: String,
+    vault: State<'_, VaultState>,
+    app: AppHandle,
+) -> Result<VaultUnlockResult, String> {
+    let master_password = Zeroizing::new(master_password);
+    let existing = load_file(&app)?;
+    let created = existing.is_none();
+
+    // Argon2 is deliberately slow, keep it off the async workers
+    let (file, key) = tokio::task::spawn_blocking(move || -> Result<(VaultFile, Zeroizing<[u8; 32]>), String> {
+        match existing {
+            Some(file) => {
+                let key = file.kdf.derive_key(&master_password)?;
+                if file.check.decrypt(&key, CHECK_PLAINTEXT).is_none_or(|c| c.as_slice() != CHECK_PLAINTEXT) {
+                    return Err("Wrong master password".to_string());
+                }
+                Ok((file, key))
+            }
+            None => {
+                if master_password.is_empty() {
+                    return Err("Master password must not be empty".to_string());
+                }
+                let kdf = KdfParams::generate();
+                let key = kdf.derive_key(&master_password)?;
+                let check = Sealed::encrypt(&key, CHECK_PLAINTEXT, CHECK_PLAINTEXT)?;
+                let file = VaultFile {
+                    kdf,
+                    check,
+                    auto_lock_secs: DEFAULT_AUTO_LOCK_SECS,
+                    entries: HashMap::new(),
+                };
+                Ok((file, key))
+            }
+        }
+    })
+    .await
+    .map_err(|e| format!("Unlock task failed: {}", e))??;
+
+    if created {
+        save_file(&app, &file)?;
+    }
+    let generation = vault.unlock_with(key);
+    watch_idle(app.clone(), generation, Duration::from_secs(file.auto_lock_secs));
+
+    let migrated = migrate_profiles(&app, &vault)?;
+    Ok(VaultUnlockResult { created, migrated })
+}
+
+#[tauri::command]
+pub async fn lock_vault(vault: State<'_, VaultState>) -> Result<(), String> {
+    vault.lock();
+    Ok(())
+}
+
+/// Lock the vault after this many idle seconds; 0 keeps it unlocked until `lock_vault`
+#[tauri::command]
+pub async fn set_vault_auto_lock(secs: u64, vault: State<'_, VaultState>, app: AppHandle) -> Result<(), String> {
+    let mut file = load_file(&app)?.ok_or("The credential vault has not been set up")?;
+    file.auto_lock_secs = secs;
+    save_file(&app, &file)?;
+
+    // Restart the idle watcher with the new timeout
+    if vault.is_unlocked() {
+        let key = vault.key(Duration::ZERO)?;
+        let generation = vault.unlock_with(key);
+        watch_idle(app, generation, Duration::from_secs(secs));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vault_file(key: &[u8; 32]) -> VaultFile {
+        VaultFile {
+            kdf: KdfParams::generate(),
+            check: Sealed::encrypt(key, CHECK_PLAINTEXT, CHECK_PLAINTEXT).unwrap(),
+            auto_lock_secs: DEFAULT_AUTO_LOCK_SECS,
+            entries: HashMap::new(),
+        }
+    }
+
+    fn password_profile(id: &str, password: &str) -> ServerProfile {
+        ServerProfile {
+            id: id.to_string(),
+            name: id.to_string(),
+            host: "example.com".to_string(),
+            port: 22,
+            username: "deploy".to_string(),
+            auth_method: AuthMethod::Password { password: password.to_string() },
+            jump_hosts: Vec::new(),
+            escalation: Escalation::default(),
+        }
+    }
+
+    fn password(profile: &ServerProfile) -> &str {
+        match &profile.auth_method {
+            AuthMethod::Password { password } => password,
+            _ => "",
+        }
+    }
+
+    #[test]
+    fn test_sealed_round_trip_and_wrong_key() {
+        let key = [7u8; 32];
+        let sealed = Sealed::encrypt(&key, b"hunter2", b"web/auth").unwrap();
+        assert_eq!(sealed.decrypt(&key, b"web/auth").unwrap().as_slice(), b"hunter2");
+
+        assert!(sealed.decrypt(&[8u8; 32], b"web/auth").is_none());
+        // Moving the entry to another slot breaks it too
+        assert!(sealed.decrypt(&key, b"db/auth").is_none());
+    }
+
+    #[test]
+    fn test_seal_replaces_secrets_and_prunes_stale_entries() {
+        let key = [7u8; 32];
+        let mut file = vault_file(&key);
+        let mut other = password_profile("db", "other");
+        seal_into(&mut file, Some(&key), &mut other).unwrap();
+
+        let mut profile = password_profile("web", "first");
+        seal_into(&mut file, Some(&key), &mut profile).unwrap();
+        let first = password(&profile).strip_prefix(SECRET_REF_PREFIX).unwrap().to_string();
+        assert_eq!(file.entries[&first].decrypt(&key, first.as_bytes()).unwrap().as_slice(), b"first");
+
+        // Sealing again with only the reference needs no key and keeps the entry
+        seal_into(&mut file, None, &mut profile).unwrap();
+        assert!(file.entries.contains_key(&first));
+
+        // A new password replaces the old entry; the other profile's entry stays
+        profile.auth_method = AuthMethod::Password { password: "second".to_string() };
+        seal_into(&mut file, Some(&key), &mut profile).unwrap();
+        let second = password(&profile).strip_prefix(SECRET_REF_PREFIX).unwrap();
+        assert!(!file.entries.contains_key(&first));
+        assert!(file.entries.contains_key(second));
+        assert_eq!(file.entries.len(), 2);
+
+        // Plaintext without a key is refused
+        profile.auth_method = AuthMethod::Password { password: "third".to_string() };
+        assert!(seal_into(&mut file, None, &mut profile).is_err());
+    }
+}
@@ -35,6 +35,7 @@ import {
 } from '@tabler/icons-react';
 
 import { isTauri } from '../lib/tauri';
+import VaultPanel from './VaultPanel';
 
 export default function ConnectionManager() {
   const { setActiveServer, setIsConnected } = useServer();
@@ -97,6 +98,10 @@ export default function ConnectionManager() {
         setActiveServer(profile);
         setIsConnected(true);
         addToast(`Connected to ${formData.name} successfully!`, 'success');
+        if (result.warning) {
+          addToast(result.warning, 'error');
+        }
+        warnIfNoRoot(result);
         loadSavedProfiles();
         setShowForm(false);
         setEditingProfile(null);
@@ -110,6 +115,15 @@ export default function ConnectionManager() {
     }
   };
 
+  // Actions in the root panels will fail, so say so before the user runs into it
+  const warnIfNoRoot = (result: ConnectionResult) => {
+    const privileges = result.privileges;
+    if (privileges && !privileges.is_root && !privileges.can_escalate) {
+      const panels = privileges.root_panels.map((p) => p.panel).join(', ');
+      addToast(`No root access (${privileges.message ?? privileges.escalation}); ${panels} actions will fail`, 'error');
+    }
+  };
+
   const testConnection = async () => {
     if (!isTauri()) {
       addToast('Please run this app with: pnpm tauri dev', 'error');
@@ -159,6 +173,10 @@ export default function ConnectionManager() {
         setActiveServer(serverProfile);
         setIsConnected(true);
         addToast(`Connected to ${profile.name} successfully!`, 'success');
+        if (result.warning) {
+          addToast(result.warning, 'error');
+        }
+        warnIfNoRoot(result);
         loadSavedProfiles();
       } else {
         addToast(result.message, 'error');
@@ -268,6 +286,8 @@ export default function ConnectionManager() {
         </Stack>
       </motion.div>
 
+      <VaultPanel />
+
       {savedProfiles.length > 0 && !showForm && (
         <motion.div
           initial={{ opacity: 0, y: 15 }}
@@ -1,8 +1,9 @@
 import { useState, useEffect, useMemo, useCallback, memo } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import { useServer } from '../context/ServerContext';
 import { isTauri } from '../lib/tauri';
-import { SystemMetrics } from '../types';
+import { CommandOutput, MetricsUpdateEvent, SystemMetrics } from '../types';
 import {
   Paper,
   Text,
@@ -55,7 +56,8 @@ import {
 } from '@tabler/icons-react';
 
 const Dashboard = memo(function Dashboard() {
-  const { cachedMetrics, setCachedMetrics } = useServer();
+  const { activeServer, cachedMetrics, setCachedMetrics } = useServer();
+  const serverId = activeServer?.id;
   const [loading, setLoading] = useState(false);
   const [error, setError] = useState<string | null>(null);
   const [lastUpdate, setLastUpdate] = useState<Date>(new Date());
@@ -65,7 +67,7 @@ const Dashboard = memo(function Dashboard() {
     if (!isTauri()) return;
     setLoading(true);
     try {
-      const result = await invoke('get_system_metrics') as SystemMetrics;
+      const result = await invoke('get_system_metrics', { serverId }) as SystemMetrics;
       setCachedMetrics(result);
       setLastUpdate(new Date());
       setError(null);
@@ -80,8 +82,8 @@ const Dashboard = memo(function Dashboard() {
     if (!isTauri()) return;
     const fetchCpuCores = async () => {
       try {
-        const result = await invoke('execute_command', { command: 'nproc' });
-        const cores = parseInt(String(result).trim(), 10);
+        const result = await invoke<CommandOutput>('execute_command', { command: 'nproc' });
+        const cores = parseInt(result.stdout.trim(), 10);
         if (!isNaN(cores) && cores > 0) {
           setCpuCores(cores);
         }
@@ -92,12 +94,37 @@ const Dashboard = memo(function Dashboard() {
     fetchCpuCores();
   }, []);
 
+  // The backend samples in the background and pushes readings while we're subscribed;
+  // until the next one arrives, show the last reading it took
   useEffect(() => {
-    if (!isTauri()) return;
-    fetchMetrics();
-    const interval = setInterval(fetchMetrics, 10000);
-    return () => clearInterval(interval);
-  }, []);
+    if (!isTauri() || !serverId) return;
+    invoke<SystemMetrics | null>('get_latest_metrics', { serverId })
+      .then((latest) => {
+        if (latest) {
+          setCachedMetrics(latest);
+          setLastUpdate(new Date());
+        }
+      })
+      .catch((err) => console.error('Failed to load latest metrics:', err));
+    const unlisten = listen<MetricsUpdateEvent>('metrics://update', (event) => {
+      const update = event.payload;
+      if (update.server_id !== serverId) return;
+      if (update.metrics) {
+        setCachedMetrics(update.metrics);
+        setLastUpdate(new Date());
+        setError(null);
+      } else {
+        setError(update.error || 'Failed to fetch metrics');
+      }
+    });
+    invoke('subscribe_metrics', { serverId }).catch((err) => console.error('Failed to subscribe to metrics:', err));
+    return () => {
+      unlisten.then((stop) => stop());
+      invoke('unsubscribe_metrics', { serverId }).catch((err) =>
+        console.error('Failed to unsubscribe from metrics:', err)
+      );
+    };
+  }, [serverId]);
 
   // Fixed: Use binary units consistently (KiB, MiB, GiB)
   const formatBytes = (bytes: number, decimals = 1) => {
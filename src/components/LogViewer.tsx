@@ -35,6 +35,8 @@ import {
   IconSettings,
   IconDatabase,
 } from '@tabler/icons-react';
+import { CommandOutput } from '../types';
+import { errorMessage } from '../lib/tauri';
 
 type LogType = 'system' | 'service' | 'file' | 'docker' | 'custom';
 
@@ -178,14 +180,14 @@ export default function LogViewer() {
 
       if (type === 'system') {
         // Try journalctl for system logs
-        result = await invoke('execute_command', { command: 'journalctl -n 300 --no-pager 2>&1' });
+        result = (await invoke<CommandOutput>('execute_command', { command: 'journalctl -n 300 --no-pager 2>&1' })).stdout;
       } else if (type === 'service') {
         // Use dedicated service logs command with fallback logic
         result = await invoke('get_service_logs', { serviceName: value, lines: 300 });
       } else if (type === 'file') {
-        result = await invoke('execute_command', { command: `tail -n 300 ${value} 2>&1` });
+        result = (await invoke<CommandOutput>('execute_command', { command: `tail -n 300 ${value} 2>&1` })).stdout;
       } else {
-        result = await invoke('execute_command', { command: `docker logs --tail 300 ${value} 2>&1` });
+        result = (await invoke<CommandOutput>('execute_command', { command: `docker logs --tail 300 ${value} 2>&1` })).stdout;
       }
 
       const resultStr = String(result);
@@ -207,8 +209,8 @@ export default function LogViewer() {
       } else {
         addToast(`Loaded ${lines.length} log entries`, 'success');
       }
-    } catch (err: any) {
-      const errorMsg = String(err);
+    } catch (err) {
+      const errorMsg = errorMessage(err);
       let helpfulMessage = errorMsg;
 
       if (errorMsg.includes('journalctl') || errorMsg.includes('command not found')) {
@@ -35,6 +35,8 @@ import {
   IconCheck,
   IconX,
 } from '@tabler/icons-react';
+import { CommandError, CommandErrorCode, CommandOutput } from '../types';
+import { errorMessage } from '../lib/tauri';
 
 interface QuickCommand {
   id: string;
@@ -343,6 +345,7 @@ export default function QuickCommands() {
   const { addToast } = useToast();
   const [loading, setLoading] = useState<string | null>(null);
   const [output, setOutput] = useState<string>('');
+  const [failed, setFailed] = useState(false);
   const [showOutput, setShowOutput] = useState(false);
   const [selectedCategory, setSelectedCategory] = useState<string>('all');
   const [executedCommand, setExecutedCommand] = useState<string>('');
@@ -356,14 +359,22 @@ export default function QuickCommands() {
     setLoading(cmd.id);
     setExecutedCommand(cmd.command);
     try {
-      const result = await invoke('execute_command', { command: cmd.command });
-      const resultStr = String(result);
-      setOutput(resultStr);
+      const result = await invoke<CommandOutput>('execute_command', { command: cmd.command });
+      const text = [result.stdout.trimEnd(), result.stderr.trimEnd()].filter(Boolean).join('\n');
+      const succeeded = result.exit_code === 0;
+      setOutput(succeeded ? text : `${text}\n\n[exit code ${result.exit_code}]`.trimStart());
+      setFailed(!succeeded);
       setShowOutput(true);
-      addToast(`Command executed: ${cmd.label}`, 'success');
-    } catch (error: any) {
-      addToast(`Command failed: ${error.message || String(error)}`, 'error');
-      setOutput(String(error));
+      if (succeeded) {
+        addToast(`Command executed: ${cmd.label}`, 'success');
+      } else {
+        addToast(`Command failed with exit code ${result.exit_code}: ${cmd.label}`, 'error');
+      }
+    } catch (error) {
+      const { code } = error as CommandError;
+      addToast(code === CommandErrorCode.Timeout ? `Command timed out: ${cmd.label}` : `Command failed: ${errorMessage(error)}`, 'error');
+      setOutput(errorMessage(error));
+      setFailed(true);
       setShowOutput(true);
     } finally {
       setLoading(null);
@@ -500,9 +511,9 @@ export default function QuickCommands() {
               <ThemeIcon
                 size="md"
                 variant={loading ? 'light' : 'filled'}
-                color={loading ? 'blue' : output.includes('error') || output.includes('Error') ? 'red' : 'green'}
+                color={loading ? 'blue' : failed ? 'red' : 'green'}
               >
-                {loading ? <IconRefresh size={16} /> : output.includes('error') || output.includes('Error') ? <IconX size={16} /> : <IconCheck size={16} />}
+                {loading ? <IconRefresh size={16} /> : failed ? <IconX size={16} /> : <IconCheck size={16} />}
               </ThemeIcon>
               <Stack gap={0}>
                 <Text fw={600} size="sm">
@@ -559,7 +570,7 @@ export default function QuickCommands() {
               ) : (
                 <Text
                   component="span"
-                  c={output.includes('error') || output.includes('Error') ? 'red.4' : 'gray.3'}
+                  c={failed ? 'red.4' : 'gray.3'}
                   style={{ fontFamily: 'monospace' }}
                 >
                   {output}
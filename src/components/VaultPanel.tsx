@@ -0,0 +1,119 @@
+import { useState, useEffect } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import { useToast } from '../context/ToastContext';
+import { VaultStatus, VaultUnlockResult } from '../types';
+import { Paper, PasswordInput, Button, Group, Text, Badge } from '@mantine/core';
+import { IconLock, IconLockOpen, IconShieldLock } from '@tabler/icons-react';
+
+import { isTauri } from '../lib/tauri';
+
+// Set up, unlock and lock the credential vault that holds saved passwords and passphrases
+export default function VaultPanel() {
+  const { addToast } = useToast();
+  const [status, setStatus] = useState<VaultStatus | null>(null);
+  const [masterPassword, setMasterPassword] = useState('');
+  const [busy, setBusy] = useState(false);
+
+  const loadStatus = async () => {
+    try {
+      setStatus(await invoke<VaultStatus>('get_vault_status'));
+    } catch (error) {
+      addToast(String(error), 'error');
+    }
+  };
+
+  useEffect(() => {
+    if (!isTauri()) return;
+    loadStatus();
+    const unlisten = listen('vault://locked', () => {
+      loadStatus();
+      addToast('Credential vault locked after inactivity', 'info');
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
+  const handleUnlock = async () => {
+    setBusy(true);
+    try {
+      const result = await invoke<VaultUnlockResult>('unlock_vault', { masterPassword });
+      setMasterPassword('');
+      if (result.created) {
+        addToast('Credential vault created', 'success');
+      }
+      if (result.migrated.length > 0) {
+        addToast(`Moved credentials of ${result.migrated.length} saved server(s) into the vault`, 'success');
+      }
+      await loadStatus();
+    } catch (error) {
+      addToast(String(error), 'error');
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const handleLock = async () => {
+    try {
+      await invoke('lock_vault');
+      await loadStatus();
+    } catch (error) {
+      addToast(String(error), 'error');
+    }
+  };
+
+  if (!status) return null;
+
+  return (
+    <Paper
+      p="md"
+      radius="lg"
+      className="glass-card-glow"
+      style={{ border: '1px solid rgba(255, 255, 255, 0.06)' }}
+    >
+      <Group justify="space-between" mb={status.unlocked ? 0 : 'sm'}>
+        <Group gap="xs">
+          <IconShieldLock size={18} style={{ color: '#3b82f6' }} />
+          <Text fw={600} size="sm">Credential Vault</Text>
+          <Badge size="sm" variant="light" color={!status.initialized ? 'gray' : status.unlocked ? 'green' : 'yellow'}>
+            {!status.initialized ? 'Not set up' : status.unlocked ? 'Unlocked' : 'Locked'}
+          </Badge>
+        </Group>
+        {status.unlocked && (
+          <Button variant="subtle" size="xs" leftSection={<IconLock size={14} />} onClick={handleLock}>
+            Lock
+          </Button>
+        )}
+      </Group>
+      {!status.unlocked && (
+        <>
+          <Text size="xs" c="dimmed" mb="sm">
+            {status.initialized
+              ? 'Unlock to connect with saved passwords and to save new ones.'
+              : 'Choose a master password to encrypt saved passwords and passphrases. Until then they are stored unencrypted.'}
+          </Text>
+          <Group gap="sm" align="flex-end">
+            <PasswordInput
+              placeholder="Master password"
+              value={masterPassword}
+              onChange={(e) => setMasterPassword(e.currentTarget.value)}
+              onKeyDown={(e) => e.key === 'Enter' && masterPassword && handleUnlock()}
+              style={{ flex: 1 }}
+              size="sm"
+            />
+            <Button
+              size="sm"
+              leftSection={<IconLockOpen size={14} />}
+              loading={busy}
+              disabled={!masterPassword}
+              onClick={handleUnlock}
+            >
+              {status.initialized ? 'Unlock' : 'Set up'}
+            </Button>
+          </Group>
+        </>
+      )}
+    </Paper>
+  );
+}
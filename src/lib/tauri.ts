@@ -1 +1,5 @@
 export const isTauri = () => typeof window !== 'undefined' && '__TAURI_INTERNALS__' in window;
+
+// Text of a rejected invoke; commands that return a CommandError reject with an object
+export const errorMessage = (error: unknown): string =>
+  typeof error === 'object' && error !== null && 'message' in error ? String(error.message) : String(error);
@@ -5,17 +5,248 @@ export interface ServerProfile {
   port: number;
   username: string;
   auth_method: AuthMethod;
+  jump_hosts?: JumpHost[];
+  escalation?: Escalation;
 }
 
-export interface SavedServerProfile extends ServerProfile {
+export interface JumpHost {
+  host: string;
+  port: number;
+  username: string;
+  auth_method: AuthMethod;
+}
+
+export interface SavedServerProfile extends ServerProfile, ProfileLabels {
   created_at: number;
   last_connected: number | null;
   connect_on_startup: boolean;
 }
 
+export type Environment = "prod" | "staging" | "dev";
+
+export interface ProfileLabels {
+  tags: string[];
+  group: string | null;
+  environment: Environment | null;
+  color: string | null;
+  notes: string | null;
+}
+
+export interface ProfileFilter {
+  tags?: string[];
+  group?: string;
+  environment?: Environment;
+  search?: string;
+}
+
+export interface ProfileGroup {
+  name: string;
+  path: string;
+  profile_ids: string[];
+  children: ProfileGroup[];
+  total: number;
+}
+
+export interface ProfileTree {
+  groups: ProfileGroup[];
+  ungrouped: string[];
+}
+
+export interface TagCount {
+  tag: string;
+  count: number;
+}
+
 export type AuthMethod =
   | { type: "Password"; password: string }
-  | { type: "PrivateKey"; key_path: string; passphrase?: string };
+  | { type: "PrivateKey"; key_path: string; passphrase?: string }
+  | { type: "Agent"; identity?: string | null }
+  | { type: "KeyboardInteractive" };
+
+// How commands that need root get it; defaults to passwordless sudo
+export type Escalation =
+  | { type: "None" }
+  | { type: "Sudo" }
+  | { type: "SudoPassword"; password?: string | null }
+  | { type: "Doas" }
+  | { type: "Su"; password: string };
+
+export interface PrivilegeStatus {
+  escalation: "none" | "sudo" | "sudo_password" | "doas" | "su";
+  is_root: boolean;
+  can_escalate: boolean;
+  message: string | null;
+  root_panels: { panel: string; actions: string }[];
+}
+
+export interface VaultStatus {
+  initialized: boolean;
+  unlocked: boolean;
+  auto_lock_secs: number;
+}
+
+export interface VaultUnlockResult {
+  created: boolean;
+  migrated: string[];
+}
+
+export type ImportConflict = "skip" | "overwrite" | "rename";
+
+export interface ProfileImportResult {
+  added: string[];
+  overwritten: string[];
+  renamed: Record<string, string>;
+  skipped: string[];
+}
+
+export interface GeneratedKey {
+  private_key_path: string;
+  public_key: string;
+  fingerprint: string;
+}
+
+export interface KeyDeployResult {
+  installed: boolean;
+  verified: boolean;
+  profile_updated: boolean;
+  password_auth_disabled: boolean;
+  rolled_back: boolean;
+  message: string;
+}
+
+// Result of execute_command, whatever the exit code
+export interface CommandOutput {
+  stdout: string;
+  stderr: string;
+  exit_code: number;
+  duration: { secs: number; nanos: number };
+}
+
+// What execute_command and get_container_logs_stream reject with; code is the
+// command's exit status, or one of CommandErrorCode when it didn't finish
+export interface CommandError {
+  message: string;
+  code: number;
+}
+
+export const CommandErrorCode = {
+  Timeout: -20,
+  Cancelled: -21,
+} as const;
+
+export interface FleetTarget {
+  profile_ids?: string[];
+  filter?: ProfileFilter;
+}
+
+export interface BroadcastRequest extends FleetTarget {
+  command: string;
+  label?: string;
+  concurrency?: number;
+  timeout_secs?: number;
+  broadcast_id?: string;
+}
+
+export interface BroadcastHostResult {
+  broadcast_id: string;
+  server_id: string;
+  name: string;
+  host: string;
+  success: boolean;
+  exit_code: number | null;
+  stdout: string;
+  stderr: string;
+  duration_ms: number;
+  error: string | null;
+}
+
+export interface BroadcastSummary {
+  broadcast_id: string;
+  label: string | null;
+  command: string;
+  total: number;
+  succeeded: string[];
+  failed: { server_id: string; name: string; reason: string }[];
+  cancelled: boolean;
+  duration_ms: number;
+}
+
+export type HealthStatus = "unreachable" | "unknown" | "critical" | "warning" | "healthy";
+
+export type HealthSortKey = "status" | "name" | "cpu" | "memory" | "disk" | "load";
+
+export interface HostHealth {
+  server_id: string;
+  name: string;
+  host: string;
+  environment: Environment | null;
+  group: string | null;
+  status: HealthStatus;
+  issues: string[];
+  error: string | null;
+  cpu_percent: number | null;
+  memory_percent: number | null;
+  fullest_disk: DiskUsage | null;
+  load_avg: [number, number, number] | null;
+  cpu_count: number | null;
+  failed_units: string[];
+  unhealthy_containers: string[];
+  duration_ms: number;
+}
+
+export interface Offender {
+  metric: string;
+  server_id: string;
+  name: string;
+  value: number;
+  detail: string;
+}
+
+export interface FleetOverview {
+  hosts: HostHealth[];
+  counts: { healthy: number; warning: number; critical: number; unknown: number; unreachable: number };
+  worst_offenders: Offender[];
+  generated_at: number;
+  duration_ms: number;
+}
+
+export interface AuthPrompt {
+  text: string;
+  echo: boolean;
+}
+
+export interface AuthChallenge {
+  username: string;
+  instructions: string;
+  prompts: AuthPrompt[];
+}
+
+// Payload of the "ssh://auth-prompt" event; answer with invoke('answer_auth_prompt')
+export interface AuthPromptRequest {
+  request_id: string;
+  host: string;
+  challenge: AuthChallenge;
+}
+
+export interface AgentIdentity {
+  comment: string;
+  fingerprint: string;
+}
+
+export interface SshConfigHost {
+  alias: string;
+  host_name: string;
+  user: string | null;
+  port: number;
+  identity_file: string | null;
+  proxy_jump: string[];
+}
+
+export interface SshConfigImportResult {
+  added: string[];
+  updated: string[];
+  removed: string[];
+}
 
 export interface SystemMetrics {
   cpu_percent: number;
@@ -70,9 +301,82 @@ export interface ServiceInfo {
   description: string;
 }
 
+// Actions accepted by docker_container_action and service_action / nginx_action
+export type ContainerAction = "start" | "stop" | "restart" | "pause" | "unpause";
+
+export type ServiceAction = "start" | "stop" | "restart" | "reload" | "enable" | "disable";
+
+export type ConnectionState = "connected" | "reconnecting" | "failed" | "disconnected";
+
+export interface ConnectionStateEvent {
+  server_id: string;
+  host: string;
+  state: ConnectionState;
+  attempt: number;
+  message: string | null;
+}
+
+// Payload of the "metrics://update" event
+export interface MetricsUpdateEvent {
+  server_id: string;
+  metrics: SystemMetrics | null;
+  error: string | null;
+  next_poll_ms: number;
+}
+
+export type MetricsResolution = "raw" | "minute" | "hour";
+
+export interface MetricsPoint {
+  timestamp: number;
+  samples: number;
+  cpu_percent: number | null;
+  memory_percent: number | null;
+  memory_used: number | null;
+  load_avg: [number, number, number] | null;
+  network_rx_rate: number | null;
+  network_tx_rate: number | null;
+  disks: Record<string, number>;
+  containers: Record<string, { cpu_percent: number; memory_usage: number }>;
+}
+
+export interface MetricsRange {
+  server_id: string;
+  resolution: MetricsResolution;
+  from: number;
+  to: number;
+  points: MetricsPoint[];
+}
+
+// Payload of the "terminal://output" event; data is base64-encoded bytes
+export interface TerminalOutput {
+  terminal_id: string;
+  data: string;
+}
+
+export interface TerminalExit {
+  terminal_id: string;
+  exit_code: number | null;
+  message: string | null;
+}
+
 export interface ConnectionResult {
   success: boolean;
   message: string;
+  host_key: HostKeyInfo | null;
+  // Index of the failing hop: jump hosts first, then the target
+  failed_hop: number | null;
+  // Connected, but e.g. the profile couldn't be saved
+  warning: string | null;
+  // Whether root commands will work, checked right after connecting
+  privileges: PrivilegeStatus | null;
+}
+
+export interface HostKeyInfo {
+  host: string;
+  port: number;
+  key_type: string;
+  fingerprint: string;
+  status: "trusted" | "unknown" | "changed";
 }
 
 export interface UfwStatus {
@@ -238,3 +542,53 @@ export interface CreateUserRequest {
   groups: string[];
   create_home: boolean;
 }
+
+// ==================== FILE MANAGER TYPES ====================
+
+export interface RemoteFileEntry {
+  name: string;
+  path: string;
+  kind: "file" | "directory" | "symlink" | "other";
+  size: number;
+  mode: number;
+  permissions: string;
+  uid: number | null;
+  gid: number | null;
+  owner: string | null;
+  group: string | null;
+  modified: number | null;
+  symlink_target: string | null;
+}
+
+// Error payload of the file manager commands
+export interface SftpError {
+  kind:
+    | "not_connected"
+    | "not_found"
+    | "permission_denied"
+    | "already_exists"
+    | "not_empty"
+    | "not_a_directory"
+    | "cancelled"
+    | "checksum_mismatch"
+    | "other";
+  message: string;
+}
+
+// Payload of the "transfer://progress" event
+export interface TransferProgress {
+  transfer_id: string;
+  direction: "upload" | "download";
+  bytes_done: number;
+  total_bytes: number;
+  rate: number;
+  eta_secs: number | null;
+}
+
+export interface TransferResult {
+  transfer_id: string;
+  bytes: number;
+  resumed_from: number;
+  sha256: string;
+  verified: boolean;
+}